@@ -4,7 +4,10 @@ use std::sync::Arc;
 
 use axum::Router;
 
-use crate::{app::AppServices, auth, state, users};
+use crate::{
+    app::AppServices, auth, backups, bundles, content, display_outputs, imports, jobs, media,
+    previews, run_sheets, schedule, state, users,
+};
 
 pub fn route() -> Router<Arc<AppServices>> {
     Router::new()
@@ -12,4 +15,14 @@ pub fn route() -> Router<Arc<AppServices>> {
         .nest("/auth", auth::api::route())
         .nest("/users", users::api::route())
         .nest("/state", state::api::route())
+        .nest("/schedule", schedule::api::route())
+        .nest("/run-sheets", run_sheets::api::route())
+        .nest("/display-outputs", display_outputs::api::route())
+        .nest("/content", content::api::route())
+        .nest("/jobs", jobs::api::route())
+        .nest("/backups", backups::api::route())
+        .nest("/bundles", bundles::api::route())
+        .nest("/imports", imports::api::route())
+        .nest("/media", media::api::route())
+        .nest("/previews", previews::api::route())
 }
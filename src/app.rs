@@ -26,9 +26,19 @@ use crate::{
     api,
     audit::AuditService,
     auth::service::AuthService,
+    backups::service::BackupsService,
+    bundles::service::BundlesService,
     config::{file::AppConfig, service::ConfigService},
-    database::Database,
-    state::service::StateService,
+    content::{service::ContentService, template::TemplateService, visibility::VisibilityService},
+    database::{Database, DatabaseError},
+    display_outputs::service::DisplayOutputsService,
+    imports::service::ImportsService,
+    jobs::service::JobsService,
+    media::service::MediaService,
+    previews::service::PreviewsService,
+    run_sheets::service::RunSheetsService,
+    schedule::service::ScheduleService,
+    state::{service::StateService, stage::StageService},
     users::service::UsersService,
 };
 
@@ -41,6 +51,19 @@ pub struct AppServices {
     pub auth_service: AuthService,
     pub users_service: UsersService,
     pub state_service: StateService,
+    pub schedule_service: ScheduleService,
+    pub run_sheets_service: RunSheetsService,
+    pub display_outputs_service: DisplayOutputsService,
+    pub stage_service: StageService,
+    pub visibility_service: VisibilityService,
+    pub template_service: TemplateService,
+    pub content_service: ContentService,
+    pub media_service: MediaService,
+    pub previews_service: PreviewsService,
+    pub jobs_service: JobsService,
+    pub backups_service: BackupsService,
+    pub bundles_service: BundlesService,
+    pub imports_service: ImportsService,
 }
 
 pub struct App {
@@ -51,10 +74,11 @@ pub struct App {
 }
 
 impl App {
-    pub async fn build(config: &AppConfig) -> Self {
+    pub async fn build(config: &AppConfig) -> Result<Self, DatabaseError> {
         let shutdown_token = CancellationToken::new();
 
-        let database = Database::new(config);
+        let database = Database::new(config)?;
+        let jobs_service = JobsService::new(&database, config);
 
         let state = Arc::new(AppServices {
             config: config.clone(),
@@ -64,6 +88,19 @@ impl App {
             auth_service: AuthService::new(&database, config),
             users_service: UsersService::new(&database, config),
             state_service: StateService::new(),
+            schedule_service: ScheduleService::new(&database, config),
+            run_sheets_service: RunSheetsService::new(&database),
+            display_outputs_service: DisplayOutputsService::new(&database),
+            stage_service: StageService::new(&database, config),
+            visibility_service: VisibilityService::new(&database),
+            template_service: TemplateService::new(&database, config),
+            content_service: ContentService::new(&database, config),
+            media_service: MediaService::new(&database, config, &jobs_service),
+            previews_service: PreviewsService::new(config),
+            backups_service: BackupsService::new(&database, config),
+            bundles_service: BundlesService::new(&database, config),
+            imports_service: ImportsService::new(&database, config, &jobs_service),
+            jobs_service,
             database,
         });
 
@@ -177,11 +214,11 @@ impl App {
             )
             .with_state(state.clone());
 
-        Self {
+        Ok(Self {
             services: state,
             listener,
             router,
             shutdown_token,
-        }
+        })
     }
 }
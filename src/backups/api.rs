@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::json;
+use tokio::fs;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+};
+
+use super::service::BackupTrigger;
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/", get(list_backups))
+        .route("/", post(create_backup))
+        .route(
+            "/restore",
+            post(restore_upload).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/:name", get(download_backup))
+        .route("/:name/restore", post(restore_backup))
+}
+
+pub async fn list_backups(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, UserPermission::SYSTEM_ADMIN) else {
+        return AuthToken::failure_response();
+    };
+
+    let backups = state.backups_service.list();
+
+    Json(backups).into_response()
+}
+
+/// Takes a backup right away, which can then be downloaded by name
+pub async fn create_backup(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SYSTEM_ADMIN) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state
+        .backups_service
+        .create(BackupTrigger::Manual, Some(current_user.id))
+        .await;
+
+    match result {
+        Ok(backup) => Json(backup).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn download_backup(
+    State(state): State<Arc<AppServices>>,
+    Path(name): Path<String>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SYSTEM_ADMIN) else {
+        return AuthToken::failure_response();
+    };
+
+    let Some(path) = state.backups_service.path(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let file = fs::File::open(&path).await;
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "database_backup_download",
+        json!({
+            "name": name,
+            "success": file.is_ok()
+        }),
+    );
+
+    let Ok(file) = file else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let size = file
+        .metadata()
+        .await
+        .expect("Failed to read backup file metadata")
+        .len();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/vnd.sqlite3"),
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", name)).unwrap(),
+    );
+
+    (
+        StatusCode::OK,
+        headers,
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response()
+}
+
+/// Reloads what is kept in memory and has clients resync after the database was replaced
+fn after_restore(state: &AppServices) {
+    state.visibility_service.reload();
+    state.run_sheets_service.refresh_live();
+    state.state_service.request_resync();
+}
+
+/// Restores a backup from the backup directory, backing up the current database first
+pub async fn restore_backup(
+    State(state): State<Arc<AppServices>>,
+    Path(name): Path<String>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SYSTEM_ADMIN) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state
+        .backups_service
+        .restore_backup(&name, Some(current_user.id))
+        .await;
+
+    match result {
+        Ok(result) => {
+            after_restore(&state);
+            Json(result).into_response()
+        }
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Restores a database file sent as the raw request body, backing up the current database first
+pub async fn restore_upload(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    body: Body,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SYSTEM_ADMIN) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state
+        .backups_service
+        .restore_upload(body.into_data_stream(), Some(current_user.id))
+        .await;
+
+    match result {
+        Ok(result) => {
+            after_restore(&state);
+            Json(result).into_response()
+        }
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
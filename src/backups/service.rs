@@ -0,0 +1,287 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{fs, io::AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::{
+    audit::AuditService,
+    config::file::AppConfig,
+    database::{Database, DatabaseError},
+    helpers::errors::GenericError,
+};
+
+const BACKUP_NAME_PREFIX: &str = "backup-";
+const BACKUP_NAME_SUFFIX: &str = ".sqlite3";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// File name in the backup directory
+    pub name: String,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What caused a backup to be taken
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupTrigger {
+    Manual,
+    Scheduled,
+    /// Taken of the live database right before a restore replaces it
+    PreRestore,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RestoreResult {
+    /// Version of the restored database before it was migrated
+    pub from_version: u32,
+    /// Backup of the database as it was before the restore
+    pub previous: BackupInfo,
+}
+
+pub struct BackupsService {
+    config: AppConfig,
+    db: Database,
+    audit_service: AuditService,
+}
+
+impl BackupsService {
+    pub fn new(database: &Database, config: &AppConfig) -> Self {
+        Self {
+            config: config.clone(),
+            audit_service: AuditService::new(database),
+            db: database.clone(),
+        }
+    }
+
+    /// Gets the path of a backup by name, rejecting names that aren't backups
+    pub fn path(&self, name: &str) -> Option<PathBuf> {
+        let valid = name.starts_with(BACKUP_NAME_PREFIX)
+            && name.ends_with(BACKUP_NAME_SUFFIX)
+            && !name.contains(['/', '\\'])
+            && !name.contains("..");
+        valid.then(|| Path::new(&self.config.backup_directory).join(name))
+    }
+
+    fn info(&self, name: &str) -> Option<BackupInfo> {
+        let metadata = std::fs::metadata(self.path(name)?).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+
+        Some(BackupInfo {
+            name: name.to_owned(),
+            size: metadata.len(),
+            created_at: metadata.modified().ok()?.into(),
+        })
+    }
+
+    /// Lists backups, newest first (names sort by creation time)
+    pub fn list(&self) -> Vec<BackupInfo> {
+        let Ok(entries) = std::fs::read_dir(&self.config.backup_directory) else {
+            return Vec::new();
+        };
+
+        let mut backups: Vec<BackupInfo> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| self.info(&name))
+            .collect();
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+
+        backups
+    }
+
+    /// Time left until the next scheduled backup, counting from the latest backup
+    pub fn scheduled_due_in(&self) -> Duration {
+        let interval = Duration::from_secs(self.config.backup_interval);
+        let Some(latest) = self.list().into_iter().next() else {
+            return Duration::ZERO;
+        };
+
+        let age = (Utc::now() - latest.created_at)
+            .to_std()
+            .unwrap_or(Duration::ZERO);
+        interval.saturating_sub(age)
+    }
+
+    /// Backs up the live database, removing the oldest backups beyond the number to keep
+    pub async fn create(
+        &self,
+        trigger: BackupTrigger,
+        user_id: Option<Uuid>,
+    ) -> Result<BackupInfo, GenericError> {
+        let name = format!(
+            "{}{}{}",
+            BACKUP_NAME_PREFIX,
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            BACKUP_NAME_SUFFIX
+        );
+        let path = self
+            .path(&name)
+            .ok_or(GenericError::INTERNAL_SERVER_ERROR)?;
+
+        let db = self.db.clone();
+        let directory = self.config.backup_directory.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+            db.backup(&path)
+        })
+        .await
+        .unwrap();
+
+        self.audit_service.log_data(
+            user_id,
+            "database_backup",
+            json!({
+                "name": name,
+                "trigger": trigger,
+                "error": result.as_ref().err(),
+                "success": result.is_ok()
+            }),
+        );
+
+        if result.is_err() {
+            return Err(GenericError::INTERNAL_SERVER_ERROR);
+        }
+
+        self.prune();
+
+        self.info(&name).ok_or(GenericError::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Removes the oldest backups beyond the number to keep, always keeping the newest one
+    fn prune(&self) {
+        for backup in self.list().iter().skip(self.config.backup_keep.max(1)) {
+            if let Some(path) = self.path(&backup.name) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Temporary file to prepare a restore in, which never looks like a backup
+    fn restore_temp_path(&self) -> PathBuf {
+        Path::new(&self.config.backup_directory).join(format!("restore-{}.tmp", Uuid::new_v4()))
+    }
+
+    /// Restores a backup from the backup directory
+    pub async fn restore_backup(
+        &self,
+        name: &str,
+        user_id: Option<Uuid>,
+    ) -> Result<RestoreResult, GenericError> {
+        let Some(path) = self.path(name).filter(|path| path.is_file()) else {
+            return Err(GenericError::NOT_FOUND);
+        };
+
+        // the backup itself is kept as it was
+        let temp_path = self.restore_temp_path();
+        fs::copy(&path, &temp_path)
+            .await
+            .expect("Failed to copy backup for restore");
+
+        self.restore_file(&temp_path, name, user_id).await
+    }
+
+    /// Restores an uploaded database file
+    pub async fn restore_upload<S, E>(
+        &self,
+        mut stream: S,
+        user_id: Option<Uuid>,
+    ) -> Result<RestoreResult, GenericError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+    {
+        fs::create_dir_all(&self.config.backup_directory)
+            .await
+            .expect("Failed to create backup directory");
+        let temp_path = self.restore_temp_path();
+
+        let mut file = fs::File::create(&temp_path)
+            .await
+            .expect("Failed to create restore file");
+        let mut result = Ok(());
+        while let Some(chunk) = stream.next().await {
+            let Ok(chunk) = chunk else {
+                result = Err(GenericError::BAD_REQUEST);
+                break;
+            };
+            file.write_all(&chunk)
+                .await
+                .expect("Failed to write restore file");
+        }
+        file.flush().await.expect("Failed to write restore file");
+        drop(file);
+
+        if let Err(err) = result {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(err);
+        }
+
+        self.restore_file(&temp_path, "upload", user_id).await
+    }
+
+    /// Checks and migrates a copy of a database, then swaps it in, removing the copy afterwards
+    async fn restore_file(
+        &self,
+        temp_path: &Path,
+        source: &str,
+        user_id: Option<Uuid>,
+    ) -> Result<RestoreResult, GenericError> {
+        let result = self.restore_prepared(temp_path, user_id).await;
+        let _ = fs::remove_file(temp_path).await;
+
+        self.audit_service.log_data(
+            user_id,
+            "database_restore",
+            json!({
+                "source": source,
+                "from_version": result.as_ref().ok().map(|result| result.from_version),
+                "previous": result.as_ref().ok().map(|result| &result.previous.name),
+                "error": result.as_ref().err().map(|(_, err)| err),
+                "success": result.is_ok()
+            }),
+        );
+
+        result.map_err(|(err, _)| err)
+    }
+
+    async fn restore_prepared(
+        &self,
+        temp_path: &Path,
+        user_id: Option<Uuid>,
+    ) -> Result<RestoreResult, (GenericError, String)> {
+        let path = temp_path.to_owned();
+        let from_version = tokio::task::spawn_blocking(move || Database::prepare_restore(&path))
+            .await
+            .unwrap()
+            .map_err(|err| match err {
+                DatabaseError::TooNew { .. } => (GenericError::CONFLICT, err.to_string()),
+                _ => (GenericError::BAD_REQUEST, err.to_string()),
+            })?;
+
+        let previous = self
+            .create(BackupTrigger::PreRestore, user_id)
+            .await
+            .map_err(|err| (err, String::from("Failed to back up current database")))?;
+
+        let db = self.db.clone();
+        let path = temp_path.to_owned();
+        tokio::task::spawn_blocking(move || db.restore(&path))
+            .await
+            .unwrap()
+            .map_err(|err| (GenericError::INTERNAL_SERVER_ERROR, err))?;
+
+        Ok(RestoreResult {
+            from_version,
+            previous,
+        })
+    }
+}
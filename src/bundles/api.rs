@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::fs;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+    jobs::db::{JobStatus, JobType},
+};
+
+use super::model::{BundleExportJob, BundleImportJob, ConflictPolicy, ExportedBundle};
+
+/// Name the uploaded bundle is saved under in the directory of its import job
+const IMPORT_FILE_NAME: &str = "bundle.zip";
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/decks/:slide_deck_id/export", post(export_deck))
+        .route("/exports/:job_id", get(download_export))
+        .route(
+            "/import",
+            post(import_deck).layer(DefaultBodyLimit::disable()),
+        )
+}
+
+/// Queues writing a deck with everything it refers to as a zip file,
+/// downloaded once the job has succeeded
+pub async fn export_deck(
+    State(state): State<Arc<AppServices>>,
+    Path(slide_deck_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    if !state.bundles_service.deck_exists(slide_deck_id) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let job_id = state.jobs_service.enqueue(
+        JobType::BundleExport,
+        json!(BundleExportJob { slide_deck_id }),
+        Some(current_user.id),
+    );
+
+    match state.jobs_service.get(job_id) {
+        Some(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Downloads the zip file written by a deck export job
+pub async fn download_export(
+    State(state): State<Arc<AppServices>>,
+    Path(job_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let Some(job) = state
+        .jobs_service
+        .get(job_id)
+        .filter(|job| job.job_type == JobType::BundleExport)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if job.status != JobStatus::Succeeded {
+        return StatusCode::CONFLICT.into_response();
+    }
+    let Some(bundle) = job
+        .result
+        .and_then(|result| serde_json::from_value::<ExportedBundle>(result).ok())
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let path = state
+        .jobs_service
+        .files_directory(job_id)
+        .join(&bundle.file_name);
+    let Ok(file) = fs::File::open(path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(bundle.size));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", bundle.file_name)).unwrap(),
+    );
+
+    (
+        StatusCode::OK,
+        headers,
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+}
+
+/// Queues importing a deck bundle sent as the raw request body
+pub async fn import_deck(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<ImportQuery>,
+    token: AuthToken,
+    body: Body,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    // the bundle is saved where the job keeps its files, so it stays until the job is removed
+    let job_id = Uuid::new_v4();
+    let path = state
+        .jobs_service
+        .files_directory(job_id)
+        .join(IMPORT_FILE_NAME);
+    if let Err(err) = state
+        .bundles_service
+        .save(body.into_data_stream(), &path)
+        .await
+    {
+        let _ = fs::remove_dir_all(state.jobs_service.files_directory(job_id)).await;
+        return err.to_status_code().into_response();
+    }
+
+    state.jobs_service.enqueue_as(
+        job_id,
+        JobType::BundleImport,
+        json!(BundleImportJob {
+            path,
+            conflict: query.conflict,
+        }),
+        Some(current_user.id),
+    );
+
+    match state.jobs_service.get(job_id) {
+        Some(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
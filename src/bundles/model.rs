@@ -0,0 +1,161 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Identifies deck bundles among other zip files
+pub const BUNDLE_FORMAT: &str = "streamsys-deck";
+
+/// Version of the bundle layout written by this server.
+/// Bundles with a newer version can't be imported.
+pub const BUNDLE_VERSION: u32 = 1;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const DECK_FILE: &str = "deck.json";
+/// Most bytes read from the manifest or the deck file of a bundle
+pub const MAX_JSON_FILE_SIZE: u64 = 64 * 1024 * 1024;
+/// Directory media files are stored in, named by their hash
+pub const MEDIA_DIRECTORY: &str = "media/";
+
+/// Content of one layer, with `None` hiding values from lower priority layers
+pub type BundleContent = BTreeMap<String, Option<String>>;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// Version of the server the bundle was exported from
+    pub app_version: String,
+    pub deck_name: String,
+}
+
+/// Everything needed to recreate a deck on another server.
+/// Ids are those of the exporting server and are only used to link items within the bundle.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct DeckBundle {
+    pub slide_types: Vec<BundleSlideType>,
+    /// Parent groups always come before their children
+    pub slide_groups: Vec<BundleSlideGroup>,
+    pub slides: Vec<BundleSlide>,
+    pub deck: BundleDeck,
+    pub media: Vec<BundleMedia>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleSlideType {
+    pub id: Uuid,
+    pub name: String,
+    pub content: BundleContent,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleSlideGroup {
+    pub id: Uuid,
+    pub parent_group_id: Option<Uuid>,
+    pub name: String,
+    pub content: BundleContent,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleSlide {
+    pub id: Uuid,
+    pub slide_group_id: Option<Uuid>,
+    pub slide_type_id: Option<Uuid>,
+    pub name: String,
+    pub content: BundleContent,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BundleDeck {
+    pub name: String,
+    pub content: BundleContent,
+    /// Sections in order
+    pub sections: Vec<BundleDeckSection>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BundleDeckSection {
+    pub name: Option<String>,
+    pub slide_group_id: Option<Uuid>,
+    pub slide_type_override_id: Option<Uuid>,
+    pub content: BundleContent,
+    /// Slides in order
+    pub slides: Vec<BundleDeckSlide>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BundleDeckSlide {
+    pub name_override: Option<String>,
+    pub slide_id: Option<Uuid>,
+    pub slide_type_override_id: Option<Uuid>,
+    pub content: BundleContent,
+}
+
+/// Media item referred to by content, with its file at `media/<hash>`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleMedia {
+    pub id: Uuid,
+    pub name: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub hash: String,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+}
+
+/// How to handle items whose name is already taken on this server
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Uses the existing item as it is
+    #[default]
+    Skip,
+    /// Uses the existing item, replacing its content with the imported content
+    Overwrite,
+    /// Imports a new item, numbering its name to make it unique
+    Rename,
+}
+
+/// Counts of imported items by what happened to them
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ImportCounts {
+    pub created: u32,
+    pub skipped: u32,
+    pub overwritten: u32,
+    pub renamed: u32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub slide_deck_id: Uuid,
+    pub slide_types: ImportCounts,
+    pub slide_groups: ImportCounts,
+    pub slides: ImportCounts,
+    pub slide_decks: ImportCounts,
+    /// Media items added to the library
+    pub media_added: u32,
+    /// Media items already in the library with the same file
+    pub media_reused: u32,
+}
+
+/// A deck bundle written to a file, ready to be downloaded
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExportedBundle {
+    pub file_name: String,
+    pub size: u64,
+}
+
+/// Payload of a job exporting a deck bundle
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleExportJob {
+    pub slide_deck_id: Uuid,
+}
+
+/// Payload of a job importing a deck bundle, saved in the directory of the job
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleImportJob {
+    pub path: PathBuf,
+    pub conflict: ConflictPolicy,
+}
@@ -0,0 +1,1070 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use axum::body::Bytes;
+use chrono::Utc;
+use futures::{Stream, StreamExt};
+use rusqlite::{named_params, Connection, OptionalExtension, Transaction};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    config::file::AppConfig, database::Database, helpers::errors::GenericError,
+    media::service::MediaService,
+};
+
+use super::model::{
+    BundleContent, BundleDeck, BundleDeckSection, BundleDeckSlide, BundleManifest, BundleMedia,
+    BundleSlide, BundleSlideGroup, BundleSlideType, ConflictPolicy, DeckBundle, ExportedBundle,
+    ImportCounts, ImportResult, BUNDLE_FORMAT, BUNDLE_VERSION, DECK_FILE, MANIFEST_FILE,
+    MAX_JSON_FILE_SIZE, MEDIA_DIRECTORY,
+};
+
+/// Kinds of items with a layer of content
+#[derive(Clone, Copy)]
+enum ContentOwner {
+    SlideType,
+    SlideGroup,
+    Slide,
+    SlideDeck,
+    SlideDeckSection,
+    SlideDeckSlide,
+}
+
+impl ContentOwner {
+    fn table(&self) -> &'static str {
+        match self {
+            Self::SlideType => "slide_type_content",
+            Self::SlideGroup => "slide_group_content",
+            Self::Slide => "slide_content",
+            Self::SlideDeck => "slide_deck_content",
+            Self::SlideDeckSection => "slide_deck_section_content",
+            Self::SlideDeckSlide => "slide_deck_slide_content",
+        }
+    }
+
+    fn owner_column(&self) -> &'static str {
+        match self {
+            Self::SlideType => "slide_type_id",
+            Self::SlideGroup => "slide_group_id",
+            Self::Slide => "slide_id",
+            Self::SlideDeck => "slide_deck_id",
+            Self::SlideDeckSection => "slide_deck_section_id",
+            Self::SlideDeckSlide => "slide_deck_slide_id",
+        }
+    }
+}
+
+/// What an imported item becomes on this server
+enum ImportTarget {
+    Existing { id: Uuid, overwrite: bool },
+    New { name: String },
+}
+
+pub struct BundlesService {
+    config: AppConfig,
+    db: Database,
+}
+
+impl BundlesService {
+    pub fn new(database: &Database, config: &AppConfig) -> Self {
+        Self {
+            config: config.clone(),
+            db: database.clone(),
+        }
+    }
+
+    pub fn deck_exists(&self, slide_deck_id: Uuid) -> bool {
+        let db = self.db.get();
+
+        let exists = db
+            .prepare_cached("SELECT 1 FROM \"slide_decks\" WHERE \"id\" = :id;")
+            .unwrap()
+            .exists(named_params! {":id": slide_deck_id})
+            .expect("Error occurred getting deck from database");
+
+        exists
+    }
+
+    /// Writes a deck with everything it refers to into a zip file in a directory
+    pub async fn export(
+        &self,
+        slide_deck_id: Uuid,
+        media_service: &MediaService,
+        directory: &Path,
+    ) -> Result<ExportedBundle, GenericError> {
+        let bundle = self.collect(slide_deck_id)?;
+        let manifest = BundleManifest {
+            format: String::from(BUNDLE_FORMAT),
+            version: BUNDLE_VERSION,
+            exported_at: Utc::now(),
+            app_version: String::from(env!("CARGO_PKG_VERSION")),
+            deck_name: bundle.deck.name.clone(),
+        };
+        let file_name = format!("{}.zip", Self::file_name_safe(&bundle.deck.name));
+        let media_files: Vec<(String, PathBuf)> = bundle
+            .media
+            .iter()
+            .map(|media| (media.hash.clone(), media_service.file_path(&media.hash)))
+            .collect();
+
+        fs::create_dir_all(directory)
+            .await
+            .expect("Failed to create bundle directory");
+        let archive_path = directory.join(&file_name);
+        let result = tokio::task::spawn_blocking({
+            let archive_path = archive_path.clone();
+            move || Self::write_archive(&archive_path, &manifest, &bundle, &media_files)
+        })
+        .await
+        .unwrap();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&archive_path).await;
+            return Err(GenericError::INTERNAL_SERVER_ERROR);
+        }
+        let size = fs::metadata(&archive_path)
+            .await
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?
+            .len();
+
+        Ok(ExportedBundle { file_name, size })
+    }
+
+    /// Makes a name usable as a file name in a header, which only allows plain ASCII
+    fn file_name_safe(name: &str) -> String {
+        let safe: String = name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if !c.is_ascii() || c.is_ascii_control() => '_',
+                c => c,
+            })
+            .collect();
+        match safe.trim() {
+            "" => String::from("deck"),
+            safe => safe.to_owned(),
+        }
+    }
+
+    fn write_archive(
+        path: &Path,
+        manifest: &BundleManifest,
+        bundle: &DeckBundle,
+        media_files: &[(String, PathBuf)],
+    ) -> io::Result<()> {
+        let mut zip = ZipWriter::new(File::create(path)?);
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file(MANIFEST_FILE, options)?;
+        serde_json::to_writer_pretty(&mut zip, manifest)?;
+        zip.start_file(DECK_FILE, options)?;
+        serde_json::to_writer_pretty(&mut zip, bundle)?;
+
+        // media is mostly compressed already
+        for (hash, file_path) in media_files {
+            let mut file = File::open(file_path)?;
+            let options = SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .large_file(file.metadata()?.len() >= u32::MAX as u64);
+            zip.start_file(format!("{}{}", MEDIA_DIRECTORY, hash), options)?;
+            io::copy(&mut file, &mut zip)?;
+        }
+
+        zip.finish()?.flush()
+    }
+
+    /// Gathers a deck and the slides, groups, types and media it refers to
+    fn collect(&self, slide_deck_id: Uuid) -> Result<DeckBundle, GenericError> {
+        let db = self.db.get();
+
+        let deck_name: String = db
+            .prepare_cached("SELECT \"name\" FROM \"slide_decks\" WHERE \"id\" = :id;")
+            .unwrap()
+            .query_row(named_params! {":id": slide_deck_id}, |row| row.get("name"))
+            .optional()
+            .expect("Error occurred getting deck from database")
+            .ok_or(GenericError::NOT_FOUND)?;
+
+        let mut slide_ids = Vec::new();
+        let mut slide_group_ids = Vec::new();
+        let mut slide_type_ids = Vec::new();
+
+        let section_rows: Vec<(Uuid, BundleDeckSection)> = db
+            .prepare_cached("SELECT \"id\", \"name\", \"slide_group_id\", \"slide_type_override_id\" FROM \"slide_deck_sections\" WHERE \"slide_deck_id\" = :slide_deck_id ORDER BY \"order\";")
+            .unwrap()
+            .query_map(named_params! {":slide_deck_id": slide_deck_id}, |row| {
+                Ok((
+                    row.get("id")?,
+                    BundleDeckSection {
+                        name: row.get("name")?,
+                        slide_group_id: row.get("slide_group_id")?,
+                        slide_type_override_id: row.get("slide_type_override_id")?,
+                        ..Default::default()
+                    },
+                ))
+            })
+            .expect("Error occurred getting deck sections from database")
+            .map(|section| section.unwrap())
+            .collect();
+
+        let mut sections = Vec::new();
+        for (section_id, mut section) in section_rows {
+            section.content = Self::read_content(&db, ContentOwner::SlideDeckSection, section_id);
+            slide_group_ids.extend(section.slide_group_id);
+            slide_type_ids.extend(section.slide_type_override_id);
+
+            let slide_rows: Vec<(Uuid, BundleDeckSlide)> = db
+                .prepare_cached("SELECT \"id\", \"name_override\", \"slide_id\", \"slide_type_override_id\" FROM \"slide_deck_slides\" WHERE \"slide_deck_section_id\" = :slide_deck_section_id ORDER BY \"order\";")
+                .unwrap()
+                .query_map(named_params! {":slide_deck_section_id": section_id}, |row| {
+                    Ok((
+                        row.get("id")?,
+                        BundleDeckSlide {
+                            name_override: row.get("name_override")?,
+                            slide_id: row.get("slide_id")?,
+                            slide_type_override_id: row.get("slide_type_override_id")?,
+                            ..Default::default()
+                        },
+                    ))
+                })
+                .expect("Error occurred getting deck slides from database")
+                .map(|slide| slide.unwrap())
+                .collect();
+
+            for (deck_slide_id, mut deck_slide) in slide_rows {
+                deck_slide.content =
+                    Self::read_content(&db, ContentOwner::SlideDeckSlide, deck_slide_id);
+                slide_ids.extend(deck_slide.slide_id);
+                slide_type_ids.extend(deck_slide.slide_type_override_id);
+                section.slides.push(deck_slide);
+            }
+            sections.push(section);
+        }
+
+        let mut slides = Vec::new();
+        let mut seen = HashSet::new();
+        for slide_id in slide_ids {
+            if !seen.insert(slide_id) {
+                continue;
+            }
+            let mut slide = db
+                .prepare_cached("SELECT \"id\", \"slide_group_id\", \"slide_type_id\", \"name\" FROM \"slides\" WHERE \"id\" = :id;")
+                .unwrap()
+                .query_row(named_params! {":id": slide_id}, |row| {
+                    Ok(BundleSlide {
+                        id: row.get("id")?,
+                        slide_group_id: row.get("slide_group_id")?,
+                        slide_type_id: row.get("slide_type_id")?,
+                        name: row.get("name")?,
+                        content: BundleContent::new(),
+                    })
+                })
+                .expect("Error occurred getting slide from database");
+            slide.content = Self::read_content(&db, ContentOwner::Slide, slide_id);
+            slide_group_ids.extend(slide.slide_group_id);
+            slide_type_ids.extend(slide.slide_type_id);
+            slides.push(slide);
+        }
+
+        // groups are recreated with their ancestors so the hierarchy stays intact
+        let mut groups: HashMap<Uuid, BundleSlideGroup> = HashMap::new();
+        while let Some(group_id) = slide_group_ids.pop() {
+            if groups.contains_key(&group_id) {
+                continue;
+            }
+            let mut group = db
+                .prepare_cached("SELECT \"id\", \"parent_group_id\", \"name\" FROM \"slide_groups\" WHERE \"id\" = :id;")
+                .unwrap()
+                .query_row(named_params! {":id": group_id}, |row| {
+                    Ok(BundleSlideGroup {
+                        id: row.get("id")?,
+                        parent_group_id: row.get("parent_group_id")?,
+                        name: row.get("name")?,
+                        content: BundleContent::new(),
+                    })
+                })
+                .expect("Error occurred getting slide group from database");
+            group.content = Self::read_content(&db, ContentOwner::SlideGroup, group_id);
+            slide_group_ids.extend(group.parent_group_id);
+            groups.insert(group_id, group);
+        }
+        let mut slide_groups = Vec::new();
+        let mut group_ids: Vec<Uuid> = groups.keys().copied().collect();
+        group_ids.sort_by(|a, b| groups[a].name.cmp(&groups[b].name));
+        for group_id in group_ids {
+            Self::push_group_with_parents(group_id, &mut groups, &mut slide_groups);
+        }
+
+        let mut slide_types = Vec::new();
+        slide_type_ids.sort();
+        slide_type_ids.dedup();
+        for slide_type_id in slide_type_ids {
+            let name: String = db
+                .prepare_cached("SELECT \"name\" FROM \"slide_types\" WHERE \"id\" = :id;")
+                .unwrap()
+                .query_row(named_params! {":id": slide_type_id}, |row| row.get("name"))
+                .expect("Error occurred getting slide type from database");
+            slide_types.push(BundleSlideType {
+                id: slide_type_id,
+                name,
+                content: Self::read_content(&db, ContentOwner::SlideType, slide_type_id),
+            });
+        }
+        slide_types.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut bundle = DeckBundle {
+            slide_types,
+            slide_groups,
+            slides,
+            deck: BundleDeck {
+                name: deck_name,
+                content: Self::read_content(&db, ContentOwner::SlideDeck, slide_deck_id),
+                sections,
+            },
+            media: Vec::new(),
+        };
+
+        let mut media_ids: Vec<Uuid> = Self::contents(&bundle)
+            .flat_map(|content| content.values().flatten())
+            .flat_map(|value| Self::referenced_ids(value))
+            .collect();
+        media_ids.sort();
+        media_ids.dedup();
+        bundle.media = media_ids
+            .into_iter()
+            .filter_map(|media_id| {
+                db.prepare_cached("SELECT \"id\", \"name\", \"mime_type\", \"size\", \"hash\", \"width\", \"height\" FROM \"media\" WHERE \"id\" = :id;")
+                    .unwrap()
+                    .query_row(named_params! {":id": media_id}, |row| {
+                        Ok(BundleMedia {
+                            id: row.get("id")?,
+                            name: row.get("name")?,
+                            mime_type: row.get("mime_type")?,
+                            size: row.get("size")?,
+                            hash: row.get("hash")?,
+                            width: row.get("width")?,
+                            height: row.get("height")?,
+                        })
+                    })
+                    .optional()
+                    .expect("Error occurred getting media from database")
+            })
+            .collect();
+
+        Ok(bundle)
+    }
+
+    fn push_group_with_parents(
+        group_id: Uuid,
+        groups: &mut HashMap<Uuid, BundleSlideGroup>,
+        ordered: &mut Vec<BundleSlideGroup>,
+    ) {
+        let Some(group) = groups.remove(&group_id) else {
+            return;
+        };
+        if let Some(parent_group_id) = group.parent_group_id {
+            Self::push_group_with_parents(parent_group_id, groups, ordered);
+        }
+        ordered.push(group);
+    }
+
+    /// Every layer of content in a bundle
+    fn contents(bundle: &DeckBundle) -> impl Iterator<Item = &BundleContent> {
+        let types = bundle
+            .slide_types
+            .iter()
+            .map(|slide_type| &slide_type.content);
+        let groups = bundle.slide_groups.iter().map(|group| &group.content);
+        let slides = bundle.slides.iter().map(|slide| &slide.content);
+        let sections = bundle.deck.sections.iter().flat_map(|section| {
+            std::iter::once(&section.content)
+                .chain(section.slides.iter().map(|deck_slide| &deck_slide.content))
+        });
+
+        types
+            .chain(groups)
+            .chain(slides)
+            .chain(std::iter::once(&bundle.deck.content))
+            .chain(sections)
+    }
+
+    /// Finds ids in text, such as media in `${media:<id>}` or `/api/media/<id>/file`
+    fn referenced_ids(text: &str) -> Vec<Uuid> {
+        text.char_indices()
+            .filter_map(|(index, _)| text.get(index..index + 36))
+            .filter_map(|candidate| Uuid::try_parse(candidate).ok())
+            .collect()
+    }
+
+    fn read_content(conn: &Connection, owner: ContentOwner, owner_id: Uuid) -> BundleContent {
+        let content = conn
+            .prepare_cached(&format!(
+                "SELECT \"key\", \"value\" FROM \"{}\" WHERE \"{}\" = :owner_id;",
+                owner.table(),
+                owner.owner_column()
+            ))
+            .unwrap()
+            .query_map(named_params! {":owner_id": owner_id}, |row| {
+                Ok((row.get("key")?, row.get("value")?))
+            })
+            .expect("Error occurred getting content from database")
+            .map(|entry| entry.unwrap())
+            .collect();
+
+        content
+    }
+
+    /// Replaces the content of an item, updating references to media that got new ids
+    fn write_content(
+        tx: &Transaction,
+        owner: ContentOwner,
+        owner_id: Uuid,
+        content: &BundleContent,
+        media_ids: &[(String, String)],
+    ) {
+        tx.prepare_cached(&format!(
+            "DELETE FROM \"{}\" WHERE \"{}\" = :owner_id;",
+            owner.table(),
+            owner.owner_column()
+        ))
+        .unwrap()
+        .execute(named_params! {":owner_id": owner_id})
+        .expect("Error occurred removing content");
+
+        let mut insert = tx
+            .prepare_cached(&format!(
+                "INSERT INTO \"{}\" (\"id\", \"{}\", \"key\", \"value\") VALUES (:id, :owner_id, :key, :value);",
+                owner.table(),
+                owner.owner_column()
+            ))
+            .unwrap();
+        for (key, value) in content {
+            let value = value.as_ref().map(|value| {
+                media_ids
+                    .iter()
+                    .fold(value.clone(), |value, (from, to)| value.replace(from, to))
+            });
+            insert
+                .execute(named_params! {
+                    ":id": Uuid::new_v4(),
+                    ":owner_id": owner_id,
+                    ":key": key,
+                    ":value": value,
+                })
+                .expect("Error occurred adding content");
+        }
+    }
+
+    /// Saves a deck bundle sent as a stream to a file to be imported later, failing once it
+    /// grows past the upload size limit
+    pub async fn save<S, E>(&self, mut stream: S, path: &Path) -> Result<(), GenericError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+    {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)
+                .await
+                .expect("Failed to create bundle directory");
+        }
+        let mut file = fs::File::create(path)
+            .await
+            .expect("Failed to create bundle file");
+        let mut size: u64 = 0;
+        let mut result = Ok(());
+        while let Some(chunk) = stream.next().await {
+            let Ok(chunk) = chunk else {
+                result = Err(GenericError::BAD_REQUEST);
+                break;
+            };
+            size += chunk.len() as u64;
+            if size > self.config.media_max_upload_size {
+                result = Err(GenericError::PAYLOAD_TOO_LARGE);
+                break;
+            }
+            file.write_all(&chunk)
+                .await
+                .expect("Failed to write bundle file");
+        }
+        file.flush().await.expect("Failed to write bundle file");
+        drop(file);
+
+        if result.is_err() {
+            let _ = fs::remove_file(path).await;
+        }
+        result
+    }
+
+    /// Imports a deck bundle file, all in one transaction.
+    /// Returns the ids of media items added, whose variants still need generating.
+    pub async fn import(
+        &self,
+        path: &Path,
+        policy: ConflictPolicy,
+        media_service: &MediaService,
+        user_id: Option<Uuid>,
+    ) -> Result<(ImportResult, Vec<Uuid>), GenericError> {
+        let archive_path = path.to_owned();
+        let bundle = tokio::task::spawn_blocking(move || Self::read_bundle(&archive_path))
+            .await
+            .unwrap()?;
+
+        // media files may be no larger than the bundle says, nor than an upload could be
+        let max_size = self.config.media_max_upload_size;
+        let media_files: Vec<(String, u64, PathBuf)> = bundle
+            .media
+            .iter()
+            .map(|media| {
+                let size = u64::try_from(media.size).unwrap_or(0).min(max_size);
+                (
+                    media.hash.clone(),
+                    size,
+                    media_service.file_path(&media.hash),
+                )
+            })
+            .collect();
+        let media_temp_path = media_service.temp_path().await;
+
+        let db = self.db.clone();
+        let archive_path = path.to_owned();
+        tokio::task::spawn_blocking(move || {
+            Self::extract_media(&archive_path, &media_files, &media_temp_path)?;
+            Ok(Self::import_bundle(&db, &bundle, policy, user_id))
+        })
+        .await
+        .unwrap()
+    }
+
+    fn open_archive(path: &Path) -> Result<ZipArchive<File>, GenericError> {
+        let file = File::open(path).expect("Failed to open bundle file");
+        ZipArchive::new(file).map_err(|_| GenericError::BAD_REQUEST)
+    }
+
+    /// Reads the deck from a bundle, rejecting bundles from newer servers
+    fn read_bundle(path: &Path) -> Result<DeckBundle, GenericError> {
+        let mut zip = Self::open_archive(path)?;
+
+        let manifest: BundleManifest = zip
+            .by_name(MANIFEST_FILE)
+            .ok()
+            .and_then(|entry| serde_json::from_reader(entry.take(MAX_JSON_FILE_SIZE)).ok())
+            .ok_or(GenericError::BAD_REQUEST)?;
+        if manifest.format != BUNDLE_FORMAT {
+            return Err(GenericError::BAD_REQUEST);
+        }
+        if manifest.version > BUNDLE_VERSION {
+            return Err(GenericError::CONFLICT);
+        }
+
+        // a deck file cut off at the size limit fails to parse
+        let bundle: DeckBundle = zip
+            .by_name(DECK_FILE)
+            .ok()
+            .and_then(|entry| serde_json::from_reader(entry.take(MAX_JSON_FILE_SIZE)).ok())
+            .ok_or(GenericError::BAD_REQUEST)?;
+        if !Self::is_consistent(&bundle) {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        Ok(bundle)
+    }
+
+    /// Moves media files of a bundle that are missing from the library into place,
+    /// rejecting files larger than their given size
+    fn extract_media(
+        path: &Path,
+        media_files: &[(String, u64, PathBuf)],
+        media_temp_path: &Path,
+    ) -> Result<(), GenericError> {
+        let mut zip = Self::open_archive(path)?;
+
+        for (hash, size, file_path) in media_files {
+            if file_path.is_file() {
+                continue;
+            }
+
+            let mut entry = zip
+                .by_name(&format!("{}{}", MEDIA_DIRECTORY, hash))
+                .map_err(|_| GenericError::BAD_REQUEST)?;
+            let mut temp_file = File::create(media_temp_path).expect("Failed to create media file");
+            let mut hasher = Sha256::new();
+            let mut buffer = vec![0; 64 * 1024];
+            let mut written: u64 = 0;
+            let mut result = Ok(());
+            loop {
+                let read = match entry.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(_) => {
+                        result = Err(GenericError::BAD_REQUEST);
+                        break;
+                    }
+                };
+                written += read as u64;
+                if written > *size {
+                    result = Err(GenericError::BAD_REQUEST);
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                temp_file
+                    .write_all(&buffer[..read])
+                    .expect("Failed to write media file");
+            }
+            drop(temp_file);
+
+            // files are stored by hash, so they must be exactly what they claim to be
+            if result.is_ok() && hex::encode(hasher.finalize()) != *hash {
+                result = Err(GenericError::BAD_REQUEST);
+            }
+            if let Err(err) = result {
+                let _ = std::fs::remove_file(media_temp_path);
+                return Err(err);
+            }
+
+            std::fs::create_dir_all(file_path.parent().unwrap())
+                .expect("Failed to create media directory");
+            std::fs::rename(media_temp_path, file_path)
+                .expect("Failed to move media file into place");
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a bundle only links to items within it and has usable names
+    fn is_consistent(bundle: &DeckBundle) -> bool {
+        let type_ids: HashSet<Uuid> = bundle
+            .slide_types
+            .iter()
+            .map(|slide_type| slide_type.id)
+            .collect();
+        let slide_ids: HashSet<Uuid> = bundle.slides.iter().map(|slide| slide.id).collect();
+        let mut group_ids = HashSet::new();
+        for group in &bundle.slide_groups {
+            if group.name.is_empty()
+                || group
+                    .parent_group_id
+                    .is_some_and(|parent_group_id| !group_ids.contains(&parent_group_id))
+            {
+                return false;
+            }
+            group_ids.insert(group.id);
+        }
+
+        let known_type = |id: &Option<Uuid>| id.is_none_or(|id| type_ids.contains(&id));
+        let known_group = |id: &Option<Uuid>| id.is_none_or(|id| group_ids.contains(&id));
+
+        bundle
+            .slide_types
+            .iter()
+            .all(|slide_type| !slide_type.name.is_empty())
+            && bundle.slides.iter().all(|slide| {
+                !slide.name.is_empty()
+                    && known_group(&slide.slide_group_id)
+                    && known_type(&slide.slide_type_id)
+            })
+            && !bundle.deck.name.is_empty()
+            && bundle.deck.sections.iter().all(|section| {
+                known_group(&section.slide_group_id)
+                    && known_type(&section.slide_type_override_id)
+                    && section.slides.iter().all(|deck_slide| {
+                        deck_slide.slide_id.is_none_or(|id| slide_ids.contains(&id))
+                            && known_type(&deck_slide.slide_type_override_id)
+                    })
+            })
+            && bundle.media.iter().all(|media| {
+                media.hash.len() == 64 && media.hash.chars().all(|c| c.is_ascii_hexdigit())
+            })
+    }
+
+    /// Decides what happens to an item whose name may already be taken
+    fn resolve(
+        policy: ConflictPolicy,
+        name: &str,
+        find: impl Fn(&str) -> Option<Uuid>,
+        counts: &mut ImportCounts,
+    ) -> ImportTarget {
+        let Some(existing_id) = find(name) else {
+            counts.created += 1;
+            return ImportTarget::New {
+                name: name.to_owned(),
+            };
+        };
+
+        match policy {
+            ConflictPolicy::Skip => {
+                counts.skipped += 1;
+                ImportTarget::Existing {
+                    id: existing_id,
+                    overwrite: false,
+                }
+            }
+            ConflictPolicy::Overwrite => {
+                counts.overwritten += 1;
+                ImportTarget::Existing {
+                    id: existing_id,
+                    overwrite: true,
+                }
+            }
+            ConflictPolicy::Rename => {
+                counts.renamed += 1;
+                let name = (2..)
+                    .map(|number| format!("{} ({})", name, number))
+                    .find(|name| find(name).is_none())
+                    .unwrap();
+                ImportTarget::New { name }
+            }
+        }
+    }
+
+    fn find_id(
+        tx: &Transaction,
+        sql: &str,
+        params: &[(&str, &dyn rusqlite::ToSql)],
+    ) -> Option<Uuid> {
+        let id = tx
+            .prepare_cached(sql)
+            .unwrap()
+            .query_row(params, |row| row.get("id"))
+            .optional()
+            .expect("Error occurred finding item by name in database");
+
+        id
+    }
+
+    /// Imports a deck built by an importer rather than read from a bundle file.
+    /// The files of media it lists must already be in place.
+    /// Returns the ids of media items added, whose variants still need generating.
+    pub async fn import_deck(
+        &self,
+        bundle: DeckBundle,
+        policy: ConflictPolicy,
+        user_id: Option<Uuid>,
+    ) -> Result<(ImportResult, Vec<Uuid>), GenericError> {
+        if !Self::is_consistent(&bundle) {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        let db = self.db.clone();
+        let imported =
+            tokio::task::spawn_blocking(move || Self::import_bundle(&db, &bundle, policy, user_id))
+                .await
+                .unwrap();
+
+        Ok(imported)
+    }
+
+    /// Adds a bundle to the database with new ids for everything
+    fn import_bundle(
+        db: &Database,
+        bundle: &DeckBundle,
+        policy: ConflictPolicy,
+        user_id: Option<Uuid>,
+    ) -> (ImportResult, Vec<Uuid>) {
+        let mut result = ImportResult::default();
+        let mut added_media = Vec::new();
+
+        let mut conn = db.get();
+        let tx = conn.transaction().unwrap();
+
+        // media with the same file is shared rather than duplicated
+        let mut media_ids: HashMap<Uuid, Uuid> = HashMap::new();
+        for media in &bundle.media {
+            let existing_id = Self::find_id(
+                &tx,
+                "SELECT \"id\" FROM \"media\" WHERE \"hash\" = :hash ORDER BY \"created_at\" LIMIT 1;",
+                named_params! {":hash": media.hash},
+            );
+            let media_id = match existing_id {
+                Some(existing_id) => {
+                    result.media_reused += 1;
+                    existing_id
+                }
+                None => {
+                    let media_id = Uuid::new_v4();
+                    tx.prepare_cached("INSERT INTO \"media\" (\"id\", \"folder_id\", \"name\", \"mime_type\", \"size\", \"hash\", \"width\", \"height\", \"created_at\", \"uploaded_by\") VALUES (:id, NULL, :name, :mime_type, :size, :hash, :width, :height, :created_at, :uploaded_by);")
+                        .unwrap()
+                        .execute(named_params! {
+                            ":id": media_id,
+                            ":name": media.name,
+                            ":mime_type": media.mime_type,
+                            ":size": media.size,
+                            ":hash": media.hash,
+                            ":width": media.width,
+                            ":height": media.height,
+                            ":created_at": Utc::now(),
+                            ":uploaded_by": user_id,
+                        })
+                        .expect("Error occurred adding media to database");
+                    result.media_added += 1;
+                    if media.mime_type.starts_with("image/") {
+                        added_media.push(media_id);
+                    }
+                    media_id
+                }
+            };
+            media_ids.insert(media.id, media_id);
+        }
+        let media_replacements: Vec<(String, String)> = media_ids
+            .iter()
+            .filter(|(from, to)| from != to)
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect();
+
+        let mut slide_type_ids: HashMap<Uuid, Uuid> = HashMap::new();
+        for slide_type in &bundle.slide_types {
+            let find = |name: &str| {
+                Self::find_id(
+                    &tx,
+                    "SELECT \"id\" FROM \"slide_types\" WHERE \"name\" = :name;",
+                    named_params! {":name": name},
+                )
+            };
+            let slide_type_id =
+                match Self::resolve(policy, &slide_type.name, find, &mut result.slide_types) {
+                    ImportTarget::Existing { id, overwrite } => {
+                        if overwrite {
+                            Self::write_content(
+                                &tx,
+                                ContentOwner::SlideType,
+                                id,
+                                &slide_type.content,
+                                &media_replacements,
+                            );
+                        }
+                        id
+                    }
+                    ImportTarget::New { name } => {
+                        let id = Uuid::new_v4();
+                        tx.prepare_cached(
+                            "INSERT INTO \"slide_types\" (\"id\", \"name\") VALUES (:id, :name);",
+                        )
+                        .unwrap()
+                        .execute(named_params! {":id": id, ":name": name})
+                        .expect("Error occurred adding slide type");
+                        Self::write_content(
+                            &tx,
+                            ContentOwner::SlideType,
+                            id,
+                            &slide_type.content,
+                            &media_replacements,
+                        );
+                        id
+                    }
+                };
+            slide_type_ids.insert(slide_type.id, slide_type_id);
+        }
+        let slide_type_id = |id: Option<Uuid>| id.map(|id| slide_type_ids[&id]);
+
+        let mut slide_group_ids: HashMap<Uuid, Uuid> = HashMap::new();
+        for group in &bundle.slide_groups {
+            let parent_group_id = group.parent_group_id.map(|id| slide_group_ids[&id]);
+            let find = |name: &str| {
+                Self::find_id(
+                    &tx,
+                    "SELECT \"id\" FROM \"slide_groups\" WHERE \"parent_group_id\" IS :parent_group_id AND \"name\" = :name;",
+                    named_params! {":parent_group_id": parent_group_id, ":name": name},
+                )
+            };
+            let slide_group_id = match Self::resolve(
+                policy,
+                &group.name,
+                find,
+                &mut result.slide_groups,
+            ) {
+                ImportTarget::Existing { id, overwrite } => {
+                    if overwrite {
+                        Self::write_content(
+                            &tx,
+                            ContentOwner::SlideGroup,
+                            id,
+                            &group.content,
+                            &media_replacements,
+                        );
+                    }
+                    id
+                }
+                ImportTarget::New { name } => {
+                    let id = Uuid::new_v4();
+                    tx.prepare_cached("INSERT INTO \"slide_groups\" (\"id\", \"parent_group_id\", \"name\") VALUES (:id, :parent_group_id, :name);")
+                            .unwrap()
+                            .execute(named_params! {
+                                ":id": id,
+                                ":parent_group_id": parent_group_id,
+                                ":name": name,
+                            })
+                            .expect("Error occurred adding slide group");
+                    Self::write_content(
+                        &tx,
+                        ContentOwner::SlideGroup,
+                        id,
+                        &group.content,
+                        &media_replacements,
+                    );
+                    id
+                }
+            };
+            slide_group_ids.insert(group.id, slide_group_id);
+        }
+        let slide_group_id = |id: Option<Uuid>| id.map(|id| slide_group_ids[&id]);
+
+        let mut slide_ids: HashMap<Uuid, Uuid> = HashMap::new();
+        for slide in &bundle.slides {
+            let group_id = slide_group_id(slide.slide_group_id);
+            let type_id = slide_type_id(slide.slide_type_id);
+            let find = |name: &str| {
+                Self::find_id(
+                    &tx,
+                    "SELECT \"id\" FROM \"slides\" WHERE \"slide_group_id\" IS :slide_group_id AND \"name\" = :name;",
+                    named_params! {":slide_group_id": group_id, ":name": name},
+                )
+            };
+            let slide_id = match Self::resolve(policy, &slide.name, find, &mut result.slides) {
+                ImportTarget::Existing { id, overwrite } => {
+                    if overwrite {
+                        tx.prepare_cached(
+                            "UPDATE \"slides\" SET \"slide_type_id\" = :slide_type_id WHERE \"id\" = :id;",
+                        )
+                        .unwrap()
+                        .execute(named_params! {":id": id, ":slide_type_id": type_id})
+                        .expect("Error occurred updating slide");
+                        Self::write_content(
+                            &tx,
+                            ContentOwner::Slide,
+                            id,
+                            &slide.content,
+                            &media_replacements,
+                        );
+                    }
+                    id
+                }
+                ImportTarget::New { name } => {
+                    let id = Uuid::new_v4();
+                    tx.prepare_cached("INSERT INTO \"slides\" (\"id\", \"slide_group_id\", \"slide_type_id\", \"name\") VALUES (:id, :slide_group_id, :slide_type_id, :name);")
+                        .unwrap()
+                        .execute(named_params! {
+                            ":id": id,
+                            ":slide_group_id": group_id,
+                            ":slide_type_id": type_id,
+                            ":name": name,
+                        })
+                        .expect("Error occurred adding slide");
+                    Self::write_content(
+                        &tx,
+                        ContentOwner::Slide,
+                        id,
+                        &slide.content,
+                        &media_replacements,
+                    );
+                    id
+                }
+            };
+            slide_ids.insert(slide.id, slide_id);
+        }
+
+        let find = |name: &str| {
+            Self::find_id(
+                &tx,
+                "SELECT \"id\" FROM \"slide_decks\" WHERE \"name\" = :name;",
+                named_params! {":name": name},
+            )
+        };
+        let deck = &bundle.deck;
+        let slide_deck_id = match Self::resolve(policy, &deck.name, find, &mut result.slide_decks) {
+            // a skipped deck is left exactly as it is
+            ImportTarget::Existing {
+                id,
+                overwrite: false,
+            } => {
+                result.slide_deck_id = id;
+                None
+            }
+            ImportTarget::Existing {
+                id,
+                overwrite: true,
+            } => {
+                tx.prepare_cached(
+                    "DELETE FROM \"slide_deck_sections\" WHERE \"slide_deck_id\" = :slide_deck_id;",
+                )
+                .unwrap()
+                .execute(named_params! {":slide_deck_id": id})
+                .expect("Error occurred removing deck sections");
+                Some(id)
+            }
+            ImportTarget::New { name } => {
+                let id = Uuid::new_v4();
+                tx.prepare_cached(
+                    "INSERT INTO \"slide_decks\" (\"id\", \"name\") VALUES (:id, :name);",
+                )
+                .unwrap()
+                .execute(named_params! {":id": id, ":name": name})
+                .expect("Error occurred adding deck");
+                Some(id)
+            }
+        };
+
+        if let Some(slide_deck_id) = slide_deck_id {
+            result.slide_deck_id = slide_deck_id;
+            Self::write_content(
+                &tx,
+                ContentOwner::SlideDeck,
+                slide_deck_id,
+                &deck.content,
+                &media_replacements,
+            );
+
+            for (section_order, section) in deck.sections.iter().enumerate() {
+                let section_id = Uuid::new_v4();
+                tx.prepare_cached("INSERT INTO \"slide_deck_sections\" (\"id\", \"slide_deck_id\", \"name\", \"order\", \"slide_group_id\", \"slide_type_override_id\") VALUES (:id, :slide_deck_id, :name, :order, :slide_group_id, :slide_type_override_id);")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": section_id,
+                        ":slide_deck_id": slide_deck_id,
+                        ":name": section.name,
+                        ":order": section_order as i64,
+                        ":slide_group_id": slide_group_id(section.slide_group_id),
+                        ":slide_type_override_id": slide_type_id(section.slide_type_override_id),
+                    })
+                    .expect("Error occurred adding deck section");
+                Self::write_content(
+                    &tx,
+                    ContentOwner::SlideDeckSection,
+                    section_id,
+                    &section.content,
+                    &media_replacements,
+                );
+
+                for (slide_order, deck_slide) in section.slides.iter().enumerate() {
+                    let deck_slide_id = Uuid::new_v4();
+                    tx.prepare_cached("INSERT INTO \"slide_deck_slides\" (\"id\", \"slide_deck_section_id\", \"name_override\", \"order\", \"slide_id\", \"slide_type_override_id\") VALUES (:id, :slide_deck_section_id, :name_override, :order, :slide_id, :slide_type_override_id);")
+                        .unwrap()
+                        .execute(named_params! {
+                            ":id": deck_slide_id,
+                            ":slide_deck_section_id": section_id,
+                            ":name_override": deck_slide.name_override,
+                            ":order": slide_order as i64,
+                            ":slide_id": deck_slide.slide_id.map(|id| slide_ids[&id]),
+                            ":slide_type_override_id": slide_type_id(deck_slide.slide_type_override_id),
+                        })
+                        .expect("Error occurred adding deck slide");
+                    Self::write_content(
+                        &tx,
+                        ContentOwner::SlideDeckSlide,
+                        deck_slide_id,
+                        &deck_slide.content,
+                        &media_replacements,
+                    );
+                }
+            }
+        }
+
+        tx.commit().expect("Error occurred committing deck import");
+
+        (result, added_media)
+    }
+}
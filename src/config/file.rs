@@ -46,6 +46,72 @@ pub struct AppConfig {
 
     #[serde(default = "default_database_full_checkpoint_interval")]
     pub database_full_checkpoint_interval: u64,
+
+    #[serde(default = "default_default_timezone")]
+    pub default_timezone: String,
+
+    #[serde(default = "default_scheduler_interval")]
+    pub scheduler_interval: u64,
+
+    #[serde(default = "default_scheduled_cue_max_delay")]
+    pub scheduled_cue_max_delay: u64,
+
+    #[serde(default = "default_show_clock_interval")]
+    pub show_clock_interval: u64,
+
+    #[serde(default = "default_stage_notes_key_prefix")]
+    pub stage_notes_key_prefix: String,
+
+    #[serde(default = "default_slide_template_key")]
+    pub slide_template_key: String,
+
+    #[serde(default = "default_media_directory")]
+    pub media_directory: String,
+
+    #[serde(default = "default_media_max_upload_size")]
+    pub media_max_upload_size: u64,
+
+    #[serde(default = "default_media_thumbnail_size")]
+    pub media_thumbnail_size: u32,
+
+    #[serde(default = "default_media_display_width")]
+    pub media_display_width: u32,
+
+    #[serde(default = "default_media_display_height")]
+    pub media_display_height: u32,
+
+    #[serde(default = "default_preview_cache_directory")]
+    pub preview_cache_directory: String,
+
+    #[serde(default = "default_job_workers")]
+    pub job_workers: usize,
+
+    #[serde(default = "default_job_poll_interval")]
+    pub job_poll_interval: u64,
+
+    #[serde(default = "default_job_max_attempts")]
+    pub job_max_attempts: u32,
+
+    #[serde(default = "default_job_retry_delay")]
+    pub job_retry_delay: u64,
+
+    #[serde(default = "default_job_max_age")]
+    pub job_max_age: u64,
+
+    #[serde(default = "default_job_directory")]
+    pub job_directory: String,
+
+    #[serde(default = "default_backup_interval")]
+    pub backup_interval: u64,
+
+    #[serde(default = "default_backup_directory")]
+    pub backup_directory: String,
+
+    #[serde(default = "default_backup_keep")]
+    pub backup_keep: usize,
+
+    #[serde(default = "default_import_directory")]
+    pub import_directory: String,
 }
 
 impl AppConfig {
@@ -113,3 +179,69 @@ fn default_database_quick_checkpoint_interval() -> u64 {
 fn default_database_full_checkpoint_interval() -> u64 {
     60 * 5
 }
+fn default_default_timezone() -> String {
+    String::from("UTC")
+}
+fn default_scheduler_interval() -> u64 {
+    1
+}
+fn default_scheduled_cue_max_delay() -> u64 {
+    60
+}
+fn default_show_clock_interval() -> u64 {
+    1
+}
+fn default_stage_notes_key_prefix() -> String {
+    String::from("notes")
+}
+fn default_slide_template_key() -> String {
+    String::from("template")
+}
+fn default_media_directory() -> String {
+    String::from("./media")
+}
+fn default_media_max_upload_size() -> u64 {
+    1024 * 1024 * 1024
+}
+fn default_media_thumbnail_size() -> u32 {
+    320
+}
+fn default_media_display_width() -> u32 {
+    1920
+}
+fn default_media_display_height() -> u32 {
+    1080
+}
+fn default_preview_cache_directory() -> String {
+    String::from("./cache/previews")
+}
+fn default_job_workers() -> usize {
+    2
+}
+fn default_job_poll_interval() -> u64 {
+    5
+}
+fn default_job_max_attempts() -> u32 {
+    3
+}
+fn default_job_retry_delay() -> u64 {
+    10
+}
+fn default_job_max_age() -> u64 {
+    60 * 60 * 24 * 7
+}
+fn default_job_directory() -> String {
+    String::from("./jobs")
+}
+fn default_backup_interval() -> u64 {
+    60 * 60 * 24
+}
+fn default_backup_directory() -> String {
+    String::from("./backups")
+}
+fn default_backup_keep() -> usize {
+    7
+}
+fn default_import_directory() -> String {
+    String::from("./imports")
+}
@@ -0,0 +1,188 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+};
+
+use super::db::DbKeyVisibilityRule;
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/visibility", get(list_visibility_rules))
+        .route("/visibility", put(replace_visibility_rules))
+        .route("/templates/render", post(render_template))
+        .route(
+            "/slides/:slide_deck_slide_id/resolved",
+            get(get_resolved_slide),
+        )
+        .route(
+            "/slides/:slide_deck_slide_id/translations",
+            get(get_slide_translations),
+        )
+        .route(
+            "/decks/:slide_deck_id/missing-translations",
+            get(list_missing_translations),
+        )
+}
+
+#[derive(Deserialize)]
+pub struct ResolveQuery {
+    pub output_id: Option<Uuid>,
+}
+
+/// Gets the resolved content of a deck slide along with any placeholders that could not be evaluated
+pub async fn get_resolved_slide(
+    State(state): State<Arc<AppServices>>,
+    Path(slide_deck_slide_id): Path<Uuid>,
+    Query(query): Query<ResolveQuery>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state
+        .content_service
+        .resolve(slide_deck_slide_id, query.output_id);
+
+    match result {
+        Some(slide) => Json(slide).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RenderTemplateRequest {
+    pub template: String,
+    #[serde(default)]
+    pub content: HashMap<String, String>,
+}
+
+/// Renders a template against sample content to check it
+pub async fn render_template(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    Json(request): Json<RenderTemplateRequest>,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state
+        .template_service
+        .render(&request.template, &request.content);
+
+    match result {
+        Ok(rendered) => Json(rendered).into_response(),
+        Err(err) => (StatusCode::BAD_REQUEST, err).into_response(),
+    }
+}
+
+pub async fn list_visibility_rules(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let rules = state.visibility_service.list();
+
+    Json(rules).into_response()
+}
+
+pub async fn replace_visibility_rules(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    Json(request): Json<Vec<DbKeyVisibilityRule>>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.visibility_service.replace(&request);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "content_visibility_update",
+        json!({
+            "rules": request,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Gets all translations of a deck slide side by side
+pub async fn get_slide_translations(
+    State(state): State<Arc<AppServices>>,
+    Path(slide_deck_slide_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.content_service.translations(slide_deck_slide_id);
+
+    match result {
+        Some(translations) => Json(translations).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MissingTranslationsQuery {
+    /// Comma separated language tags, defaulting to every language used in the deck
+    pub languages: Option<String>,
+}
+
+pub async fn list_missing_translations(
+    State(state): State<Arc<AppServices>>,
+    Path(slide_deck_id): Path<Uuid>,
+    Query(query): Query<MissingTranslationsQuery>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let languages: Vec<String> = query
+        .languages
+        .iter()
+        .flat_map(|languages| languages.split(','))
+        .map(str::trim)
+        .filter(|language| !language.is_empty())
+        .map(String::from)
+        .collect();
+
+    let missing = state
+        .content_service
+        .missing_translations(slide_deck_id, &languages);
+
+    Json(missing).into_response()
+}
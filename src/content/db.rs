@@ -127,15 +127,7 @@ impl SlideContent {
             FROM \"display_output_content\" \
             WHERE \
                 \"display_output_id\" = :display_output_id \
-        ), \"cte_sorted_values\" AS ( \
-            SELECT \
-                \"key\", \
-                \"value\", \
-                row_number() OVER (PARTITION BY \"key\" ORDER BY \"priority\") AS \"priority\" \
-            FROM \"cte_all_values\" \
-        ), \"cte_top_values\" AS ( \
-            SELECT \"key\", \"value\" FROM \"cte_sorted_values\" WHERE \"priority\" = 1 \
-        ) SELECT * FROM \"cte_top_values\"";
+        ) SELECT \"key\", \"value\", \"priority\" FROM \"cte_all_values\" ORDER BY \"priority\"";
 
     pub fn from_row(row: &Row) -> Self {
         Self {
@@ -193,3 +185,64 @@ impl SlideContent {
         }
     }
 }
+
+/// Clients a content key is sent to
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentVisibility {
+    /// Sent to every client
+    #[default]
+    Public,
+    /// Sent to stage outputs and operators
+    Stage,
+    /// Sent to operators only
+    Operator,
+}
+impl ToSql for ContentVisibility {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Self::Public => "public",
+            Self::Stage => "stage",
+            Self::Operator => "operator",
+        }
+        .into())
+    }
+}
+impl FromSql for ContentVisibility {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("public") => Ok(Self::Public),
+            Ok("stage") => Ok(Self::Stage),
+            Ok("operator") => Ok(Self::Operator),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbKeyVisibilityRule {
+    /// Content key, or the start of content keys if `prefix` is set
+    pub key: String,
+    #[serde(default)]
+    pub prefix: bool,
+    pub visibility: ContentVisibility,
+}
+impl DbKeyVisibilityRule {
+    pub const TABLE_NAME: &'static str = "content_key_visibility";
+
+    pub const COLUMNS_SQL: &'static str = "\"key\", \"prefix\", \"visibility\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            key: row
+                .get("key")
+                .expect("Failed to get value from database row"),
+            prefix: row
+                .get("prefix")
+                .expect("Failed to get value from database row"),
+            visibility: row
+                .get("visibility")
+                .expect("Failed to get value from database row"),
+        }
+    }
+}
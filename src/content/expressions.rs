@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, NaiveTime, TimeDelta,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::media::service::MediaService;
+
+/// Start of a placeholder in a content value, `$${` being a literal `${`
+const PLACEHOLDER_START: &str = "${";
+const PLACEHOLDER_END: char = '}';
+
+/// Values placeholders can refer to besides other content keys
+pub struct ExpressionContext {
+    pub deck_name: Option<String>,
+    pub section_name: Option<String>,
+    /// Position of the slide in its deck, starting at 1
+    pub slide_index: usize,
+    pub slide_count: usize,
+    /// Position of the slide in its section, starting at 1
+    pub section_slide_index: usize,
+    pub section_slide_count: usize,
+    pub timestamp: DateTime<Tz>,
+}
+
+/// Placeholder that was left in the content as-is
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnresolvedReference {
+    /// Key whose value contains the placeholder
+    pub key: String,
+    pub reference: String,
+    pub reason: UnresolvedReason,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnresolvedReason {
+    /// Refers to a key that has no value
+    MissingKey,
+    /// Refers back to the key it is used in
+    Cycle,
+    /// Refers to a variable that is not available for this slide
+    Unavailable,
+    /// Not a valid placeholder
+    Invalid,
+}
+
+/// Evaluates placeholders in all values of resolved content.
+///
+/// Supported placeholders are `${key}` for the value of another key, `${deck.name}`,
+/// `${section.name}`, `${slide.index}`, `${slide.count}`, `${section.slide_index}`,
+/// `${section.slide_count}`, `${date}` and `${time}` with an optional strftime format after a
+/// colon (`${date:%A}`), `${countdown:HH:MM}` for the time left until a local time, and
+/// `${media:<id>}` for the URL of a media item.
+pub fn evaluate(
+    content: &HashMap<String, String>,
+    context: &ExpressionContext,
+) -> (HashMap<String, String>, Vec<UnresolvedReference>) {
+    let mut evaluator = Evaluator {
+        content,
+        context,
+        evaluated: HashMap::new(),
+        in_progress: HashSet::new(),
+        cyclic: HashSet::new(),
+        unresolved: Vec::new(),
+    };
+
+    let mut keys: Vec<&String> = content.keys().collect();
+    keys.sort();
+    for key in keys {
+        // values are kept even when they depend on a cycle, which is already reported
+        let _ = evaluator.evaluate_key(key);
+    }
+
+    let mut unresolved = evaluator.unresolved;
+    unresolved.sort_by(|a, b| (&a.key, &a.reference).cmp(&(&b.key, &b.reference)));
+    unresolved.dedup();
+
+    (evaluator.evaluated, unresolved)
+}
+
+struct Evaluator<'a> {
+    content: &'a HashMap<String, String>,
+    context: &'a ExpressionContext,
+    evaluated: HashMap<String, String>,
+    /// Keys currently being evaluated, to detect cycles
+    in_progress: HashSet<String>,
+    /// Keys whose value depends on a cycle
+    cyclic: HashSet<String>,
+    unresolved: Vec<UnresolvedReference>,
+}
+
+impl Evaluator<'_> {
+    fn evaluate_key(&mut self, key: &str) -> Result<String, UnresolvedReason> {
+        if self.cyclic.contains(key) {
+            return Err(UnresolvedReason::Cycle);
+        }
+        if let Some(value) = self.evaluated.get(key) {
+            return Ok(value.clone());
+        }
+        let Some(raw) = self.content.get(key) else {
+            return Err(UnresolvedReason::MissingKey);
+        };
+        if !self.in_progress.insert(key.to_owned()) {
+            return Err(UnresolvedReason::Cycle);
+        }
+
+        let mut hit_cycle = false;
+        let mut value = String::with_capacity(raw.len());
+        let mut rest = raw.as_str();
+        while let Some(start) = rest.find(PLACEHOLDER_START) {
+            // `$${` escapes a literal `${`
+            if rest[..start].ends_with('$') {
+                value.push_str(&rest[..start - 1]);
+                value.push_str(PLACEHOLDER_START);
+                rest = &rest[start + PLACEHOLDER_START.len()..];
+                continue;
+            }
+
+            value.push_str(&rest[..start]);
+            let after_start = &rest[start + PLACEHOLDER_START.len()..];
+            let Some(end) = after_start.find(PLACEHOLDER_END) else {
+                // unterminated placeholders are kept as text
+                self.unresolved.push(UnresolvedReference {
+                    key: key.to_owned(),
+                    reference: after_start.to_owned(),
+                    reason: UnresolvedReason::Invalid,
+                });
+                value.push_str(&rest[start..]);
+                rest = "";
+                break;
+            };
+
+            let reference = after_start[..end].trim();
+            match self.evaluate_reference(reference) {
+                Ok(replacement) => value.push_str(&replacement),
+                Err(reason) => {
+                    hit_cycle |= reason == UnresolvedReason::Cycle;
+                    self.unresolved.push(UnresolvedReference {
+                        key: key.to_owned(),
+                        reference: reference.to_owned(),
+                        reason,
+                    });
+                    value.push_str(&rest[start..start + PLACEHOLDER_START.len() + end + 1]);
+                }
+            }
+            rest = &after_start[end + 1..];
+        }
+        value.push_str(rest);
+
+        self.in_progress.remove(key);
+        self.evaluated.insert(key.to_owned(), value.clone());
+
+        // keys depending on a cycle are reported wherever they are used
+        if hit_cycle {
+            self.cyclic.insert(key.to_owned());
+            return Err(UnresolvedReason::Cycle);
+        }
+        Ok(value)
+    }
+
+    fn evaluate_reference(&mut self, reference: &str) -> Result<String, UnresolvedReason> {
+        let (name, argument) = match reference.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument)),
+            None => (reference, None),
+        };
+
+        let context = self.context;
+        match (name, argument) {
+            ("deck.name", None) => context
+                .deck_name
+                .clone()
+                .ok_or(UnresolvedReason::Unavailable),
+            ("section.name", None) => context
+                .section_name
+                .clone()
+                .ok_or(UnresolvedReason::Unavailable),
+            ("slide.index", None) => Ok(context.slide_index.to_string()),
+            ("slide.count", None) => Ok(context.slide_count.to_string()),
+            ("section.slide_index", None) => Ok(context.section_slide_index.to_string()),
+            ("section.slide_count", None) => Ok(context.section_slide_count.to_string()),
+            ("date", format) => Self::format_timestamp(context, format.unwrap_or("%Y-%m-%d")),
+            ("time", format) => Self::format_timestamp(context, format.unwrap_or("%H:%M")),
+            ("countdown", Some(target)) => Self::countdown(context, target.trim()),
+            ("media", Some(media_id)) => media_id
+                .trim()
+                .parse()
+                .map(MediaService::file_url)
+                .map_err(|_| UnresolvedReason::Invalid),
+            ("", _) | ("countdown", None) | ("media", None) => Err(UnresolvedReason::Invalid),
+            (key, None) => self.evaluate_key(key),
+            _ => Err(UnresolvedReason::Invalid),
+        }
+    }
+
+    fn format_timestamp(
+        context: &ExpressionContext,
+        format: &str,
+    ) -> Result<String, UnresolvedReason> {
+        // formatting panics on invalid specifiers, so they are checked first
+        let items = StrftimeItems::new(format).collect::<Vec<_>>();
+        if items.iter().any(|item| matches!(item, Item::Error)) {
+            return Err(UnresolvedReason::Invalid);
+        }
+
+        Ok(context
+            .timestamp
+            .format_with_items(items.into_iter())
+            .to_string())
+    }
+
+    fn countdown(context: &ExpressionContext, target: &str) -> Result<String, UnresolvedReason> {
+        let target = NaiveTime::parse_from_str(target, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(target, "%H:%M"))
+            .map_err(|_| UnresolvedReason::Invalid)?;
+
+        let remaining = std::cmp::max(
+            TimeDelta::zero(),
+            target - context.timestamp.naive_local().time(),
+        );
+
+        let seconds = remaining.num_seconds();
+        Ok(if seconds >= 3600 {
+            format!(
+                "{}:{:02}:{:02}",
+                seconds / 3600,
+                seconds % 3600 / 60,
+                seconds % 60
+            )
+        } else {
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        })
+    }
+}
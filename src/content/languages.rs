@@ -0,0 +1,77 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Separates a content key from its language tag, as in `text@en`
+pub const LANGUAGE_SEPARATOR: char = '@';
+
+/// Checks that a tag looks like a language tag, such as `en`, `pt-BR` or `zh-Hant`:
+/// a language of 2 to 8 letters followed by subtags of 1 to 8 letters or digits
+pub fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language_valid = subtags.next().is_some_and(|language| {
+        (2..=8).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic())
+    });
+    language_valid
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Splits a key into its base key and language tag.
+/// Only a valid language tag after the last separator is split off, so keys such as an
+/// email address are left whole.
+pub fn split_key(key: &str) -> (&str, Option<&str>) {
+    match key.rsplit_once(LANGUAGE_SEPARATOR) {
+        Some((base, language)) if !base.is_empty() && is_language_tag(language) => {
+            (base, Some(language))
+        }
+        _ => (key, None),
+    }
+}
+
+/// Picks the best language of each key within each content layer, then the value of the
+/// highest priority layer having the key, and removes the language tags.
+///
+/// Within a layer, languages are tried in order of preference, then the untagged value, then
+/// the first remaining language so that a key is never dropped only because it is not
+/// translated. Layers are given highest priority first, and a `None` value picked in a layer
+/// hides the key from lower priority layers.
+pub fn select(
+    layers: &[HashMap<String, Option<String>>],
+    preferences: &[String],
+) -> HashMap<String, String> {
+    let mut selected: HashMap<String, Option<String>> = HashMap::new();
+    for layer in layers {
+        for (key, mut translations) in group_by_key(layer) {
+            if selected.contains_key(&key) {
+                continue;
+            }
+            let value = preferences
+                .iter()
+                .find_map(|language| translations.remove(language))
+                .or_else(|| translations.remove(""))
+                .or_else(|| translations.into_values().find(Option::is_some));
+            if let Some(value) = value {
+                selected.insert(key, value);
+            }
+        }
+    }
+
+    selected
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key, value)))
+        .collect()
+}
+
+/// Groups values by base key and language, untagged values having an empty language
+pub fn group_by_key<T: Clone>(
+    content: &HashMap<String, T>,
+) -> BTreeMap<String, BTreeMap<String, T>> {
+    let mut keys: BTreeMap<String, BTreeMap<String, T>> = BTreeMap::new();
+    for (key, value) in content {
+        let (base, language) = split_key(key);
+        keys.entry(base.to_owned())
+            .or_default()
+            .insert(language.unwrap_or_default().to_owned(), value.clone());
+    }
+    keys
+}
@@ -1 +1,7 @@
+pub mod api;
 pub mod db;
+pub mod expressions;
+pub mod languages;
+pub mod service;
+pub mod template;
+pub mod visibility;
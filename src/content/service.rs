@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use rusqlite::{named_params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::file::AppConfig,
+    database::{display_outputs::DbDisplayOutput, Database},
+};
+
+use super::{
+    db::SlideContent,
+    expressions::{self, ExpressionContext, UnresolvedReference},
+    languages,
+};
+
+/// Content of a deck slide with all content layers applied
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ResolvedSlide {
+    pub slide_deck_slide_id: Uuid,
+    pub slide_type_id: Option<Uuid>,
+    pub content: HashMap<String, String>,
+    /// Placeholders in the content that could not be evaluated
+    #[serde(default)]
+    pub unresolved: Vec<UnresolvedReference>,
+}
+
+/// Values of each key of a slide by language, the empty language being untagged values
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SlideTranslations {
+    pub slide_deck_slide_id: Uuid,
+    pub languages: Vec<String>,
+    pub keys: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MissingTranslation {
+    pub slide_deck_slide_id: Uuid,
+    pub key: String,
+    pub language: String,
+}
+
+pub struct ContentService {
+    config: AppConfig,
+    db: Database,
+}
+
+impl ContentService {
+    pub fn new(database: &Database, config: &AppConfig) -> Self {
+        Self {
+            config: config.clone(),
+            db: database.clone(),
+        }
+    }
+
+    /// Resolves the content of a deck slide for a display output
+    pub fn resolve(
+        &self,
+        slide_deck_slide_id: Uuid,
+        display_output_id: Option<Uuid>,
+    ) -> Option<ResolvedSlide> {
+        let db = self.db.get();
+
+        let (slide_type_id, slide_deck_id, slide_deck_section_id, deck_name, section_name): (
+            Option<Uuid>,
+            Uuid,
+            Uuid,
+            Option<String>,
+            Option<String>,
+        ) = db
+            .prepare_cached(
+                "SELECT COALESCE(\"slide_deck_slides\".\"slide_type_override_id\", \"slide_deck_sections\".\"slide_type_override_id\", \"slides\".\"slide_type_id\") AS \"slide_type_id\", \
+                \"slide_deck_sections\".\"slide_deck_id\" AS \"slide_deck_id\", \
+                \"slide_deck_slides\".\"slide_deck_section_id\" AS \"slide_deck_section_id\", \
+                \"slide_decks\".\"name\" AS \"deck_name\", \
+                \"slide_deck_sections\".\"name\" AS \"section_name\" \
+                FROM \"slide_deck_slides\" \
+                INNER JOIN \"slide_deck_sections\" ON \"slide_deck_slides\".\"slide_deck_section_id\" = \"slide_deck_sections\".\"id\" \
+                INNER JOIN \"slide_decks\" ON \"slide_deck_sections\".\"slide_deck_id\" = \"slide_decks\".\"id\" \
+                LEFT JOIN \"slides\" ON \"slide_deck_slides\".\"slide_id\" = \"slides\".\"id\" \
+                WHERE \"slide_deck_slides\".\"id\" = :slide_deck_slide_id;",
+            )
+            .unwrap()
+            .query_row(
+                named_params! {":slide_deck_slide_id": slide_deck_slide_id},
+                |row| {
+                    Ok((
+                        row.get("slide_type_id")?,
+                        row.get("slide_deck_id")?,
+                        row.get("slide_deck_section_id")?,
+                        row.get("deck_name")?,
+                        row.get("section_name")?,
+                    ))
+                },
+            )
+            .optional()
+            .expect("Error occurred getting deck slide from database")?;
+
+        drop(db);
+
+        let languages = display_output_id
+            .map(|id| self.output_languages(id))
+            .unwrap_or_default();
+        let content = languages::select(
+            &self.content_layers(slide_deck_slide_id, display_output_id),
+            &languages,
+        );
+
+        let deck_slide_ids = self.deck_slide_ids(slide_deck_id);
+        let section_slide_ids = self.section_slide_ids(slide_deck_section_id);
+        let position_of = |ids: &[Uuid]| {
+            ids.iter()
+                .position(|id| *id == slide_deck_slide_id)
+                .map_or(0, |index| index + 1)
+        };
+        let timezone = self
+            .config
+            .default_timezone
+            .parse::<Tz>()
+            .unwrap_or(Tz::UTC);
+
+        let context = ExpressionContext {
+            deck_name,
+            section_name,
+            slide_index: position_of(&deck_slide_ids),
+            slide_count: deck_slide_ids.len(),
+            section_slide_index: position_of(&section_slide_ids),
+            section_slide_count: section_slide_ids.len(),
+            timestamp: Utc::now().with_timezone(&timezone),
+        };
+        let (content, unresolved) = expressions::evaluate(&content, &context);
+
+        Some(ResolvedSlide {
+            slide_deck_slide_id,
+            slide_type_id,
+            content,
+            unresolved,
+        })
+    }
+
+    /// Gets the content layers of a deck slide, highest priority first, keeping language tags.
+    /// Values set to null hide values from lower priority layers.
+    fn content_layers(
+        &self,
+        slide_deck_slide_id: Uuid,
+        display_output_id: Option<Uuid>,
+    ) -> Vec<HashMap<String, Option<String>>> {
+        let db = self.db.get();
+
+        let mut layers: BTreeMap<i64, HashMap<String, Option<String>>> = BTreeMap::new();
+        db.prepare_cached(SlideContent::CONTENT_FOR_SLIDE_DECK_SLIDE)
+            .unwrap()
+            .query_map(
+                named_params! {
+                    ":slide_deck_slide_id": slide_deck_slide_id,
+                    ":display_output_id": display_output_id,
+                },
+                |row| {
+                    Ok((
+                        row.get::<_, i64>("priority")?,
+                        row.get::<_, String>("key")?,
+                        row.get::<_, Option<String>>("value")?,
+                    ))
+                },
+            )
+            .expect("Error occurred resolving deck slide content from database")
+            .for_each(|entry| {
+                let (priority, key, value) = entry.unwrap();
+                layers.entry(priority).or_default().insert(key, value);
+            });
+
+        layers.into_values().collect()
+    }
+
+    /// Gets the content of a deck slide with all content layers applied, keeping language tags
+    pub fn layered_content(
+        &self,
+        slide_deck_slide_id: Uuid,
+        display_output_id: Option<Uuid>,
+    ) -> HashMap<String, String> {
+        let mut content: HashMap<String, Option<String>> = HashMap::new();
+        for layer in self.content_layers(slide_deck_slide_id, display_output_id) {
+            for (key, value) in layer {
+                content.entry(key).or_insert(value);
+            }
+        }
+
+        content
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| (key, value)))
+            .collect()
+    }
+
+    /// Gets the language preference list of a display output
+    fn output_languages(&self, display_output_id: Uuid) -> Vec<String> {
+        let db = self.db.get();
+
+        let output_result: Option<DbDisplayOutput> = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"{}\" WHERE \"id\" = :id;",
+                DbDisplayOutput::COLUMNS_SQL,
+                DbDisplayOutput::TABLE_NAME
+            ))
+            .unwrap()
+            .query_row(named_params! {":id": display_output_id}, |row| {
+                Ok(DbDisplayOutput::from_row(row))
+            })
+            .optional()
+            .expect("Error occurred getting display output from database");
+
+        output_result
+            .map(|output| output.languages)
+            .unwrap_or_default()
+    }
+
+    /// Gets all translations of each key of a deck slide
+    pub fn translations(&self, slide_deck_slide_id: Uuid) -> Option<SlideTranslations> {
+        if !self.deck_slide_exists(slide_deck_slide_id) {
+            return None;
+        }
+
+        let content = self.layered_content(slide_deck_slide_id, None);
+        let keys = languages::group_by_key(&content);
+        let languages = keys
+            .values()
+            .flat_map(|translations| translations.keys().cloned())
+            .filter(|language| !language.is_empty())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        Some(SlideTranslations {
+            slide_deck_slide_id,
+            languages,
+            keys,
+        })
+    }
+
+    /// Lists translations missing from the slides of a deck.
+    /// Without languages given, every language used anywhere in the deck is expected.
+    pub fn missing_translations(
+        &self,
+        slide_deck_id: Uuid,
+        languages: &[String],
+    ) -> Vec<MissingTranslation> {
+        let slides = self
+            .deck_slide_ids(slide_deck_id)
+            .into_iter()
+            .filter_map(|id| self.translations(id))
+            .collect::<Vec<_>>();
+
+        let expected = if languages.is_empty() {
+            slides
+                .iter()
+                .flat_map(|slide| slide.languages.iter().cloned())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        } else {
+            languages.to_vec()
+        };
+
+        slides
+            .iter()
+            .flat_map(|slide| {
+                // keys without any language tag are the same in every language
+                slide
+                    .keys
+                    .iter()
+                    .filter(|(_, translations)| {
+                        translations.keys().any(|language| !language.is_empty())
+                    })
+                    .flat_map(|(key, translations)| {
+                        expected
+                            .iter()
+                            .filter(|language| !translations.contains_key(*language))
+                            .map(|language| MissingTranslation {
+                                slide_deck_slide_id: slide.slide_deck_slide_id,
+                                key: key.clone(),
+                                language: language.clone(),
+                            })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn deck_slide_exists(&self, slide_deck_slide_id: Uuid) -> bool {
+        let db = self.db.get();
+
+        let exists = db
+            .prepare_cached("SELECT 1 FROM \"slide_deck_slides\" WHERE \"id\" = :id;")
+            .unwrap()
+            .exists(named_params! {":id": slide_deck_slide_id})
+            .expect("Error occurred checking deck slide in database");
+
+        exists
+    }
+
+    /// Gets the slides of a deck in playback order
+    pub fn deck_slide_ids(&self, slide_deck_id: Uuid) -> Vec<Uuid> {
+        let db = self.db.get();
+
+        let ids = db
+            .prepare_cached(
+                "SELECT \"slide_deck_slides\".\"id\" AS \"id\" FROM \"slide_deck_slides\" \
+                INNER JOIN \"slide_deck_sections\" ON \"slide_deck_slides\".\"slide_deck_section_id\" = \"slide_deck_sections\".\"id\" \
+                WHERE \"slide_deck_sections\".\"slide_deck_id\" = :slide_deck_id \
+                ORDER BY \"slide_deck_sections\".\"order\", \"slide_deck_slides\".\"order\";",
+            )
+            .unwrap()
+            .query_map(named_params! {":slide_deck_id": slide_deck_id}, |row| {
+                row.get("id")
+            })
+            .expect("Error occurred getting deck slides from database")
+            .map(|id| id.unwrap())
+            .collect();
+
+        ids
+    }
+
+    /// Gets the slides of a deck section in order
+    pub fn section_slide_ids(&self, slide_deck_section_id: Uuid) -> Vec<Uuid> {
+        let db = self.db.get();
+
+        let ids = db
+            .prepare_cached(
+                "SELECT \"id\" FROM \"slide_deck_slides\" WHERE \"slide_deck_section_id\" = :slide_deck_section_id ORDER BY \"order\";",
+            )
+            .unwrap()
+            .query_map(
+                named_params! {":slide_deck_section_id": slide_deck_section_id},
+                |row| row.get("id"),
+            )
+            .expect("Error occurred getting section slides from database")
+            .map(|id| id.unwrap())
+            .collect();
+
+        ids
+    }
+
+    /// Gets the slide following a slide in its deck
+    pub fn next_slide_id(&self, slide_deck_id: Uuid, slide_deck_slide_id: Uuid) -> Option<Uuid> {
+        let ids = self.deck_slide_ids(slide_deck_id);
+        let index = ids.iter().position(|id| *id == slide_deck_slide_id)?;
+
+        ids.get(index + 1).copied()
+    }
+}
@@ -0,0 +1,100 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use handlebars::Handlebars;
+use rusqlite::{named_params, OptionalExtension};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{config::file::AppConfig, database::Database, state::models::DisplayState};
+
+/// Most compiled templates kept before the cache is emptied
+const MAX_CACHED_TEMPLATES: usize = 256;
+
+/// Renders slide type templates against slide content.
+///
+/// Templates use Handlebars syntax with values escaped for HTML and SVG. Content values are
+/// available as `content.<key>` and their lines as `lines.<key>` for use in `#each` loops.
+pub struct TemplateService {
+    config: AppConfig,
+    db: Database,
+    /// Compiled templates, registered under their own source so each is only compiled once
+    handlebars: Mutex<Handlebars<'static>>,
+}
+
+impl TemplateService {
+    pub fn new(database: &Database, config: &AppConfig) -> Self {
+        Self {
+            config: config.clone(),
+            db: database.clone(),
+            handlebars: Mutex::new(Handlebars::new()),
+        }
+    }
+
+    /// Gets the template of a slide type
+    pub fn get_template(&self, slide_type_id: Uuid) -> Option<String> {
+        let db = self.db.get();
+
+        let template_result = db
+            .prepare_cached(
+                "SELECT \"value\" FROM \"slide_type_content\" WHERE \"slide_type_id\" = :slide_type_id AND \"key\" = :key;",
+            )
+            .unwrap()
+            .query_row(
+                named_params! {
+                    ":slide_type_id": slide_type_id,
+                    ":key": self.config.slide_template_key,
+                },
+                |row| row.get::<_, Option<String>>("value"),
+            )
+            .optional()
+            .expect("Error occurred getting slide type template from database")
+            .flatten();
+
+        template_result
+    }
+
+    /// Renders a template against content
+    pub fn render(
+        &self,
+        template: &str,
+        content: &HashMap<String, String>,
+    ) -> Result<String, String> {
+        let lines: HashMap<&String, Vec<&str>> = content
+            .iter()
+            .map(|(key, value)| (key, value.lines().collect()))
+            .collect();
+
+        let mut handlebars = self.handlebars.lock().unwrap();
+        if !handlebars.has_template(template) {
+            if handlebars.get_templates().len() >= MAX_CACHED_TEMPLATES {
+                handlebars.clear_templates();
+            }
+            handlebars
+                .register_template_string(template, template)
+                .map_err(|err| err.to_string())?;
+        }
+
+        handlebars
+            .render(template, &json!({ "content": content, "lines": lines }))
+            .map_err(|err| err.to_string())
+    }
+
+    /// Renders the template of a display state, from its content or else its slide type.
+    /// The template is removed from the content, and states without a template or with a
+    /// broken one are otherwise left unchanged.
+    pub fn render_display(&self, display: &mut DisplayState) {
+        let template = match display.content.remove(&self.config.slide_template_key) {
+            Some(template) => Some(template),
+            None => display
+                .slide_type_id
+                .and_then(|slide_type_id| self.get_template(slide_type_id)),
+        };
+        let Some(template) = template else {
+            return;
+        };
+
+        if let Ok(rendered) = self.render(&template, &display.content) {
+            display.rendered = Some(rendered);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use rusqlite::named_params;
+
+use crate::{database::Database, helpers::errors::GenericError, state::models::CurrentState};
+
+use super::{
+    db::{ContentVisibility, DbKeyVisibilityRule},
+    languages,
+};
+
+/// Decides which content keys each client may receive
+pub struct VisibilityService {
+    db: Database,
+    /// Rules are checked on every state update so they are kept in memory
+    rules: Mutex<Vec<DbKeyVisibilityRule>>,
+}
+
+impl VisibilityService {
+    pub fn new(database: &Database) -> Self {
+        let service = Self {
+            db: database.clone(),
+            rules: Mutex::new(Vec::new()),
+        };
+        *service.rules.lock().unwrap() = service.load();
+        service
+    }
+
+    fn load(&self) -> Vec<DbKeyVisibilityRule> {
+        let db = self.db.get();
+
+        let rules = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"{}\" ORDER BY \"key\", \"prefix\";",
+                DbKeyVisibilityRule::COLUMNS_SQL,
+                DbKeyVisibilityRule::TABLE_NAME
+            ))
+            .unwrap()
+            .query_map(named_params! {}, |row| {
+                Ok(DbKeyVisibilityRule::from_row(row))
+            })
+            .expect("Error occurred getting content visibility rules from database")
+            .map(|rule| rule.unwrap())
+            .collect();
+
+        rules
+    }
+
+    /// Reloads the rules after the database changed underneath
+    pub fn reload(&self) {
+        *self.rules.lock().unwrap() = self.load();
+    }
+
+    pub fn list(&self) -> Vec<DbKeyVisibilityRule> {
+        self.rules.lock().unwrap().clone()
+    }
+
+    /// Replaces all visibility rules
+    pub fn replace(&self, rules: &[DbKeyVisibilityRule]) -> Result<(), GenericError> {
+        {
+            let mut db = self.db.get();
+            let tx = db.transaction().unwrap();
+
+            tx.prepare_cached("DELETE FROM \"content_key_visibility\";")
+                .unwrap()
+                .execute(named_params! {})
+                .expect("Error occurred removing content visibility rules");
+
+            for rule in rules {
+                let success = tx
+                    .prepare_cached("INSERT INTO \"content_key_visibility\" (\"key\", \"prefix\", \"visibility\") VALUES (:key, :prefix, :visibility);")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":key": rule.key,
+                        ":prefix": rule.prefix,
+                        ":visibility": rule.visibility,
+                    })
+                    .is_ok();
+                if !success {
+                    return Err(GenericError::BAD_REQUEST);
+                }
+            }
+
+            tx.commit()
+                .expect("Error occurred committing content visibility rules");
+        }
+
+        *self.rules.lock().unwrap() = self.load();
+
+        Ok(())
+    }
+
+    /// Gets the visibility of a key, exact rules winning over the longest matching prefix.
+    /// Language tags are left out, so `notes@fr` is as visible as `notes`.
+    pub fn visibility_of(&self, key: &str) -> ContentVisibility {
+        let rules = self.rules.lock().unwrap();
+        Self::match_rules(&rules, key)
+    }
+
+    fn match_rules(rules: &[DbKeyVisibilityRule], key: &str) -> ContentVisibility {
+        let (key, _) = languages::split_key(key);
+
+        if let Some(rule) = rules.iter().find(|rule| !rule.prefix && rule.key == key) {
+            return rule.visibility;
+        }
+
+        rules
+            .iter()
+            .filter(|rule| rule.prefix && key.starts_with(&rule.key))
+            .max_by_key(|rule| rule.key.len())
+            .map(|rule| rule.visibility)
+            .unwrap_or_default()
+    }
+
+    /// Removes keys a client with the given access may not see
+    pub fn filter(
+        &self,
+        content: &HashMap<String, String>,
+        access: ContentVisibility,
+    ) -> HashMap<String, String> {
+        let rules = self.rules.lock().unwrap();
+        content
+            .iter()
+            .filter(|(key, _)| Self::match_rules(&rules, key) <= access)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Filters all content of a state for a client
+    pub fn filter_state(&self, state: &CurrentState, access: ContentVisibility) -> CurrentState {
+        if access == ContentVisibility::Operator {
+            return state.clone();
+        }
+
+        let mut state = state.clone();
+        state.display.content = self.filter(&state.display.content, access);
+        if let Some(ref mut stage) = state.stage {
+            stage.current.content = self.filter(&stage.current.content, access);
+            if let Some(ref mut next) = stage.next {
+                next.content = self.filter(&next.content, access);
+            }
+            stage.notes = self.filter(&stage.notes, access);
+        }
+        state
+    }
+}
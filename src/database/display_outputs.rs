@@ -1,8 +1,82 @@
+use rusqlite::{
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+    Row, ToSql,
+};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputKind {
+    /// Shows whatever operators set
+    #[default]
+    Audience,
+    /// Confidence monitor following the playback of a target output
+    Stage,
+}
+impl ToSql for OutputKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Self::Audience => "audience",
+            Self::Stage => "stage",
+        }
+        .into())
+    }
+}
+impl FromSql for OutputKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("audience") => Ok(Self::Audience),
+            Ok("stage") => Ok(Self::Stage),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DbDisplayOutput {
     pub id: Option<Uuid>,
     pub name: String,
+    #[serde(default)]
+    pub kind: OutputKind,
+    /// Output followed by a stage output, `None` being the default output
+    #[serde(default)]
+    pub target_output_id: Option<Uuid>,
+    /// Language tags of content in order of preference
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Key given by stage displays to be sent stage content, generated when left empty
+    #[serde(default)]
+    pub access_key: String,
+}
+impl DbDisplayOutput {
+    pub const TABLE_NAME: &'static str = "display_outputs";
+
+    pub const COLUMNS_SQL: &'static str =
+        "\"id\", \"name\", \"kind\", \"target_output_id\", \"languages\", \"access_key\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            id: row
+                .get("id")
+                .expect("Failed to get value from database row"),
+            name: row
+                .get("name")
+                .expect("Failed to get value from database row"),
+            kind: row
+                .get("kind")
+                .expect("Failed to get value from database row"),
+            target_output_id: row
+                .get("target_output_id")
+                .expect("Failed to get value from database row"),
+            languages: serde_json::from_str(
+                &row.get::<_, String>("languages")
+                    .expect("Failed to get value from database row"),
+            )
+            .expect("Failed to parse output languages"),
+            access_key: row
+                .get("access_key")
+                .expect("Failed to get value from database row"),
+        }
+    }
 }
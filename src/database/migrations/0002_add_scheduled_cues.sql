@@ -0,0 +1,12 @@
+CREATE TABLE "scheduled_cues" (
+    "id" BLOB PRIMARY KEY NOT NULL DEFAULT (randomblob(16)),
+    "name" TEXT NOT NULL DEFAULT "",
+    "display_output_id" BLOB REFERENCES "display_outputs" ("id") ON UPDATE CASCADE ON DELETE CASCADE,
+    "execute_at" TEXT NOT NULL,
+    "timezone" TEXT NOT NULL,
+    "action_json" TEXT NOT NULL,
+    "status" TEXT NOT NULL DEFAULT 'pending',
+    "created_by" BLOB,
+    "executed_at" TEXT
+);
+CREATE INDEX "index__scheduled_cues__status__execute_at" ON "scheduled_cues" ("status", "execute_at");
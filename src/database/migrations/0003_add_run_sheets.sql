@@ -0,0 +1,20 @@
+CREATE TABLE "run_sheets" (
+    "id" BLOB PRIMARY KEY NOT NULL DEFAULT (randomblob(16)),
+    "name" TEXT NOT NULL UNIQUE,
+    "display_output_id" BLOB REFERENCES "display_outputs" ("id") ON UPDATE CASCADE ON DELETE SET NULL,
+    "current_item_id" BLOB REFERENCES "run_sheet_items" ("id") ON UPDATE CASCADE ON DELETE SET NULL,
+    "hard_out" TEXT
+);
+
+CREATE TABLE "run_sheet_items" (
+    "id" BLOB PRIMARY KEY NOT NULL DEFAULT (randomblob(16)),
+    "run_sheet_id" BLOB NOT NULL REFERENCES "run_sheets" ("id") ON UPDATE CASCADE ON DELETE CASCADE,
+    "order" INTEGER NOT NULL DEFAULT 0,
+    "name" TEXT NOT NULL DEFAULT "",
+    "slide_deck_id" BLOB REFERENCES "slide_decks" ("id") ON UPDATE CASCADE ON DELETE SET NULL,
+    "planned_duration" INTEGER NOT NULL DEFAULT 0,
+    "owner" TEXT NOT NULL DEFAULT "",
+    "notes" TEXT NOT NULL DEFAULT "",
+    "started_at" TEXT
+);
+CREATE INDEX "index__run_sheet_items__run_sheet_id__order" ON "run_sheet_items" ("run_sheet_id", "order");
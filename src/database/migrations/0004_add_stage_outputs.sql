@@ -0,0 +1,2 @@
+ALTER TABLE "display_outputs" ADD COLUMN "kind" TEXT NOT NULL DEFAULT 'audience';
+ALTER TABLE "display_outputs" ADD COLUMN "target_output_id" BLOB REFERENCES "display_outputs" ("id") ON UPDATE CASCADE ON DELETE SET NULL;
@@ -0,0 +1,10 @@
+CREATE TABLE "content_key_visibility" (
+    "id" BLOB PRIMARY KEY NOT NULL DEFAULT (randomblob(16)),
+    "key" TEXT NOT NULL,
+    "prefix" INTEGER NOT NULL DEFAULT 0,
+    "visibility" TEXT NOT NULL,
+    UNIQUE("key", "prefix")
+);
+
+-- notes are for the stage until an admin decides otherwise
+INSERT INTO "content_key_visibility" ("key", "prefix", "visibility") VALUES ('notes', 1, 'stage');
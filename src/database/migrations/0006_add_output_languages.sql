@@ -0,0 +1 @@
+ALTER TABLE "display_outputs" ADD COLUMN "languages" TEXT NOT NULL DEFAULT '[]';
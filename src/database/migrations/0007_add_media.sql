@@ -0,0 +1,21 @@
+CREATE TABLE "media_folders" (
+    "id" BLOB PRIMARY KEY NOT NULL DEFAULT (randomblob(16)),
+    "parent_folder_id" BLOB REFERENCES "media_folders" ("id") ON UPDATE CASCADE ON DELETE CASCADE,
+    "name" TEXT NOT NULL
+);
+CREATE UNIQUE INDEX "index_null__media_folders__parent_folder_id__name" ON "media_folders" (IFNULL("parent_folder_id", 0), "name");
+
+CREATE TABLE "media" (
+    "id" BLOB PRIMARY KEY NOT NULL DEFAULT (randomblob(16)),
+    "folder_id" BLOB REFERENCES "media_folders" ("id") ON UPDATE CASCADE ON DELETE SET NULL,
+    "name" TEXT NOT NULL,
+    "mime_type" TEXT NOT NULL,
+    "size" INTEGER NOT NULL,
+    "hash" TEXT NOT NULL,
+    "width" INTEGER,
+    "height" INTEGER,
+    "created_at" TEXT NOT NULL,
+    "uploaded_by" BLOB
+);
+CREATE INDEX "index__media__folder_id__name" ON "media" ("folder_id", "name");
+CREATE INDEX "index__media__hash" ON "media" ("hash");
@@ -0,0 +1,18 @@
+CREATE TABLE "jobs" (
+    "id" BLOB PRIMARY KEY NOT NULL DEFAULT (randomblob(16)),
+    "job_type" TEXT NOT NULL,
+    "payload_json" TEXT NOT NULL DEFAULT '{}',
+    "status" TEXT NOT NULL DEFAULT 'queued',
+    "progress" REAL NOT NULL DEFAULT 0,
+    "progress_message" TEXT,
+    "attempts" INTEGER NOT NULL DEFAULT 0,
+    "max_attempts" INTEGER NOT NULL,
+    "run_after" TEXT NOT NULL,
+    "result_json" TEXT,
+    "error" TEXT,
+    "created_by" BLOB,
+    "created_at" TEXT NOT NULL,
+    "started_at" TEXT,
+    "finished_at" TEXT
+);
+CREATE INDEX "index__jobs__status__run_after" ON "jobs" ("status", "run_after");
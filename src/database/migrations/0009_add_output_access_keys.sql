@@ -0,0 +1,2 @@
+ALTER TABLE "display_outputs" ADD COLUMN "access_key" TEXT NOT NULL DEFAULT '';
+UPDATE "display_outputs" SET "access_key" = hex(randomblob(16));
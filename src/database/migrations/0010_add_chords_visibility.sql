@@ -0,0 +1,2 @@
+-- chords kept by song imports are for the band, not the audience
+INSERT OR IGNORE INTO "content_key_visibility" ("key", "prefix", "visibility") VALUES ('chords', 0, 'stage');
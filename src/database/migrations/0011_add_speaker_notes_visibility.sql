@@ -0,0 +1,2 @@
+-- speaker notes of imported presentations are for whoever runs them, not the stage
+INSERT OR IGNORE INTO "content_key_visibility" ("key", "prefix", "visibility") VALUES ('notes.speaker', 0, 'operator');
@@ -1,14 +1,159 @@
-use r2d2::PooledConnection;
-use r2d2_sqlite::SqliteConnectionManager;
+use std::fmt::Display;
 
+use rusqlite::Connection;
+
+/// Schema change from the version before it to `version`
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    /// Statements to run, without any transaction handling of their own
+    pub sql: &'static str,
+}
+
+/// Version created by `database.sql`, which all migrations build upon
+pub const BASE_VERSION: u32 = 1;
+
+/// All migrations in order of their versions, which follow each other without gaps.
+/// Each one is kept in its own SQL file in this directory, e.g.
+/// `Migration { version: 2, name: "add_example", sql: include_str!("0002_add_example.sql") }`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        name: "add_scheduled_cues",
+        sql: include_str!("0002_add_scheduled_cues.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_run_sheets",
+        sql: include_str!("0003_add_run_sheets.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "add_stage_outputs",
+        sql: include_str!("0004_add_stage_outputs.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "add_content_key_visibility",
+        sql: include_str!("0005_add_content_key_visibility.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "add_output_languages",
+        sql: include_str!("0006_add_output_languages.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "add_media",
+        sql: include_str!("0007_add_media.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "add_jobs",
+        sql: include_str!("0008_add_jobs.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "add_output_access_keys",
+        sql: include_str!("0009_add_output_access_keys.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "add_chords_visibility",
+        sql: include_str!("0010_add_chords_visibility.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "add_speaker_notes_visibility",
+        sql: include_str!("0011_add_speaker_notes_visibility.sql"),
+    },
+];
+
+/// Version of the database after all migrations
+pub const fn latest_version() -> u32 {
+    match MIGRATIONS.last() {
+        Some(migration) => migration.version,
+        None => BASE_VERSION,
+    }
+}
+
+#[derive(Debug)]
+pub struct MigrationError {
+    pub version: u32,
+    pub name: &'static str,
+    pub error: String,
+}
+
+impl std::error::Error for MigrationError {}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Migration to version {} ({}) failed: {}",
+            self.version, self.name, self.error
+        )
+    }
+}
+
+/// Gets the migrations needed to bring a database from `current_version` up to `latest_version`
+pub fn pending_migrations(
+    current_version: u32,
+    latest_version: u32,
+) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS.iter().filter(move |migration| {
+        migration.version > current_version && migration.version <= latest_version
+    })
+}
+
+/// Applies migrations in order, each in its own transaction which also bumps `user_version`.
+/// Migrations applied before a failing one are kept.
 pub fn apply_migrations(
-    conn: &PooledConnection<SqliteConnectionManager>,
+    conn: &mut Connection,
     current_version: u32,
     latest_version: u32,
-) {
-    let _conn = conn;
-    let _current_version = current_version;
-    let _latest_version = latest_version;
+) -> Result<Vec<&'static Migration>, MigrationError> {
+    // foreign keys can't be switched off inside a transaction, but rebuilding tables needs it
+    conn.execute_batch("PRAGMA foreign_keys = 0;")
+        .expect("Error occurred disabling foreign keys for migrations");
+
+    let mut applied = Vec::new();
+    let mut result = Ok(());
+    for migration in pending_migrations(current_version, latest_version) {
+        result = apply_migration(conn, migration).map_err(|error| MigrationError {
+            version: migration.version,
+            name: migration.name,
+            error,
+        });
+        if result.is_err() {
+            break;
+        }
+        applied.push(migration);
+    }
+
+    conn.execute_batch("PRAGMA foreign_keys = 1;")
+        .expect("Error occurred enabling foreign keys after migrations");
+
+    result.map(|_| applied)
+}
+
+fn apply_migration(conn: &mut Connection, migration: &Migration) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|err| err.to_string())?;
+
+    tx.execute_batch(migration.sql)
+        .map_err(|err| err.to_string())?;
+
+    // changes with foreign keys switched off must still leave them consistent
+    let violations = tx
+        .prepare("PRAGMA foreign_key_check;")
+        .and_then(|mut stmt| stmt.query_map([], |_| Ok(())).map(|rows| rows.count()))
+        .map_err(|err| err.to_string())?;
+    if violations > 0 {
+        return Err(format!("{} foreign key violations", violations));
+    }
+
+    tx.pragma_update(None, "user_version", migration.version)
+        .map_err(|err| err.to_string())?;
 
-    todo!();
+    tx.commit().map_err(|err| err.to_string())
 }
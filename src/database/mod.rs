@@ -7,11 +7,18 @@ pub mod slide_groups;
 pub mod slide_types;
 pub mod slides;
 
-use std::{thread, time::Duration};
+use std::{error::Error, fmt::Display, path::Path, thread, time::Duration};
 
+use chrono::Utc;
+use migrations::MigrationError;
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{
+    backup::{Backup, StepResult},
+    Connection, DatabaseName, OpenFlags,
+};
 use serde_json::json;
+use uuid::Uuid;
 
 use crate::{
     audit::AuditService,
@@ -22,19 +29,79 @@ use crate::{
 
 const DATABASE_DEFINITION_SQL: &str = include_str!("../../database.sql");
 
-const DATABASE_VERSION_MIN: u32 = 1;
+const DATABASE_VERSION_MIN: u32 = migrations::latest_version();
 const DATABASE_VERSION_MAX: u32 = 999;
 
 const OPTIMIZE_QUICK_INCREMENTAL_VACUUM_PAGES: u64 = 1;
 
+const BACKUP_BUSY_WAIT_MILLIS: u64 = 10;
+const BACKUP_BUSY_RETRIES: u32 = 6000;
+
+/// Reasons the database can't be opened, reported at startup
+#[derive(Debug)]
+pub enum DatabaseError {
+    Connection(String),
+    /// Created by a newer version of the application
+    TooNew {
+        version: u32,
+    },
+    Backup(String),
+    /// Not a database created by this application
+    Invalid,
+    Migration {
+        error: MigrationError,
+        /// Copy of the database from before migrating
+        backup: Option<String>,
+    },
+    IntegrityCheck,
+}
+
+impl Error for DatabaseError {}
+
+impl Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connection(err) => write!(f, "Error occurred opening database: {}", err),
+            Self::TooNew { version } => write!(
+                f,
+                "Database version {} is too new (supported up to {}). Please check for application updates.",
+                version, DATABASE_VERSION_MAX
+            ),
+            Self::Backup(err) => write!(f, "Error occurred backing up database: {}", err),
+            Self::Invalid => write!(f, "File is not a database of this application"),
+            Self::Migration {
+                error,
+                backup: Some(backup),
+            } => write!(
+                f,
+                "{}. The database was left at the last successful migration, a backup from before migrating is at \"{}\".",
+                error, backup
+            ),
+            Self::Migration {
+                error,
+                backup: None,
+            } => write!(f, "{}", error),
+            Self::IntegrityCheck => write!(f, "Database failed integrity checks"),
+        }
+    }
+}
+
+/// Outcome of checking the migrations of a database without changing it
+pub struct MigrationCheck {
+    pub current_version: u32,
+    pub latest_version: u32,
+    /// Versions and names of the migrations that would be applied
+    pub pending: Vec<(u32, &'static str)>,
+}
+
 #[derive(Clone)]
 pub struct Database {
     pool: Pool<SqliteConnectionManager>,
 }
 
 impl Database {
-    /// Creates a new database connection pool
-    pub fn new(config: &AppConfig) -> Self {
+    /// Creates a new database connection pool, creating or migrating the database as needed
+    pub fn new(config: &AppConfig) -> Result<Self, DatabaseError> {
         let manager = SqliteConnectionManager::file(&config.database_file).with_init(|c| {
             // set pragmas
             c.execute_batch(
@@ -47,23 +114,21 @@ impl Database {
             )
         });
 
-        let pool = r2d2::Pool::new(manager).expect("Error occurred connecting to database");
-        let conn = pool
+        let pool =
+            r2d2::Pool::new(manager).map_err(|err| DatabaseError::Connection(err.to_string()))?;
+        let mut conn = pool
             .get()
-            .expect("Error occurred getting database connection for initialization");
+            .map_err(|err| DatabaseError::Connection(err.to_string()))?;
 
         let db = Self { pool };
 
-        let version: u32 = conn
-            .prepare("PRAGMA user_version;")
-            .unwrap()
-            .query_row([], |row| row.get(0))
-            .expect("Error occurred getting database user version");
+        let mut version = user_version(&conn).map_err(DatabaseError::Connection)?;
 
         if version == 0 {
             // create database if not yet created
             conn.execute_batch(DATABASE_DEFINITION_SQL)
                 .expect("Error occurred while running database initialization commands");
+            version = migrations::BASE_VERSION;
 
             let audit_service = AuditService::new(&db);
 
@@ -84,13 +149,46 @@ impl Database {
                 .expect("Error occurred creating default admin user");
 
             audit_service.log_data(None, "default_user_created", json!({"user_id": user_id}));
+
+            // new databases are brought up to date without a backup as there is nothing to lose
+            migrations::apply_migrations(&mut conn, version, DATABASE_VERSION_MIN).map_err(
+                |error| DatabaseError::Migration {
+                    error,
+                    backup: None,
+                },
+            )?;
         } else if version > DATABASE_VERSION_MAX {
-            // panic of database is too new (i.e. unsupported)
-            panic!("Database version is too new. Please check for application updates.");
+            return Err(DatabaseError::TooNew { version });
         } else if version < DATABASE_VERSION_MIN {
-            // apply migrations if older than latest database version
-            migrations::apply_migrations(&conn, version, DATABASE_VERSION_MIN);
+            // apply migrations if older than latest database version, keeping a copy of the old one
+            let backup_path = format!(
+                "{}.v{}-{}.bak",
+                config.database_file,
+                version,
+                Utc::now().format("%Y%m%dT%H%M%S")
+            );
+            conn.backup(DatabaseName::Main, &backup_path, None)
+                .map_err(|err| DatabaseError::Backup(err.to_string()))?;
+
+            let result = migrations::apply_migrations(&mut conn, version, DATABASE_VERSION_MIN);
+
+            AuditService::new(&db).log_data(
+                None,
+                "database_migrate",
+                json!({
+                    "from_version": version,
+                    "to_version": DATABASE_VERSION_MIN,
+                    "backup": backup_path,
+                    "success": result.is_ok(),
+                }),
+            );
+
+            result.map_err(|error| DatabaseError::Migration {
+                error,
+                backup: Some(backup_path),
+            })?;
         }
+        drop(conn);
 
         // run full database optimization
         db.optimize(true);
@@ -98,7 +196,68 @@ impl Database {
         // run full checkpoint
         db.checkpoint(true);
 
-        db
+        Ok(db)
+    }
+
+    /// Checks which migrations an existing database needs by applying them to a temporary copy,
+    /// leaving the database itself untouched
+    pub fn check(config: &AppConfig) -> Result<MigrationCheck, DatabaseError> {
+        let conn = Connection::open_with_flags(
+            &config.database_file,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|err| DatabaseError::Connection(err.to_string()))?;
+
+        let current_version = user_version(&conn).map_err(DatabaseError::Connection)?;
+        if current_version > DATABASE_VERSION_MAX {
+            return Err(DatabaseError::TooNew {
+                version: current_version,
+            });
+        }
+
+        // databases that are yet to be created start at the base version
+        let from_version = current_version.max(migrations::BASE_VERSION);
+        let pending = migrations::pending_migrations(from_version, DATABASE_VERSION_MIN)
+            .map(|migration| (migration.version, migration.name))
+            .collect();
+
+        if current_version != 0 {
+            let temp_path =
+                std::env::temp_dir().join(format!("streamsys-check-{}.sqlite3", Uuid::new_v4()));
+            let result = Self::check_copy(&conn, &temp_path, current_version);
+            let _ = std::fs::remove_file(&temp_path);
+            result?;
+        }
+
+        Ok(MigrationCheck {
+            current_version,
+            latest_version: DATABASE_VERSION_MIN,
+            pending,
+        })
+    }
+
+    fn check_copy(
+        conn: &Connection,
+        temp_path: &Path,
+        current_version: u32,
+    ) -> Result<(), DatabaseError> {
+        conn.backup(DatabaseName::Main, temp_path, None)
+            .map_err(|err| DatabaseError::Backup(err.to_string()))?;
+
+        let mut copy = Connection::open(temp_path)
+            .map_err(|err| DatabaseError::Connection(err.to_string()))?;
+        migrations::apply_migrations(&mut copy, current_version, DATABASE_VERSION_MIN).map_err(
+            |error| DatabaseError::Migration {
+                error,
+                backup: None,
+            },
+        )?;
+
+        if !integrity_check(&copy).unwrap_or(false) {
+            return Err(DatabaseError::IntegrityCheck);
+        }
+
+        Ok(())
     }
 
     /// Gets an instance of the database connection pool
@@ -153,6 +312,108 @@ impl Database {
         }
     }
 
+    /// Copies the live database into a new file without blocking writers.
+    /// The copy is taken in a single step from one read transaction, so it is consistent.
+    pub fn backup(&self, path: &Path) -> Result<(), String> {
+        let conn = self
+            .pool
+            .get()
+            .expect("Error occurred getting database connection for backup");
+
+        // written under a temporary name so incomplete backups are never picked up
+        let temp_path = path.with_extension("tmp");
+        let result = Connection::open(&temp_path)
+            .and_then(|mut dst| {
+                let backup = Backup::new(&conn, &mut dst)?;
+                for _ in 0..BACKUP_BUSY_RETRIES {
+                    if backup.step(-1)? == StepResult::Done {
+                        return Ok(true);
+                    }
+                    thread::sleep(Duration::from_millis(BACKUP_BUSY_WAIT_MILLIS));
+                }
+                Ok(false)
+            })
+            .map_err(|err| err.to_string())
+            .and_then(|done| match done {
+                true => std::fs::rename(&temp_path, path).map_err(|err| err.to_string()),
+                false => Err(String::from("Database stayed busy")),
+            });
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// Checks a database file about to be restored and migrates it to the current version,
+    /// returning the version it had
+    pub fn prepare_restore(path: &Path) -> Result<u32, DatabaseError> {
+        let mut conn =
+            Connection::open(path).map_err(|err| DatabaseError::Connection(err.to_string()))?;
+
+        let version = user_version(&conn).map_err(|_| DatabaseError::Invalid)?;
+        if version == 0 {
+            return Err(DatabaseError::Invalid);
+        } else if version > DATABASE_VERSION_MAX {
+            return Err(DatabaseError::TooNew { version });
+        }
+
+        match integrity_check(&conn) {
+            Ok(true) => {}
+            Ok(false) => return Err(DatabaseError::IntegrityCheck),
+            Err(_) => return Err(DatabaseError::Invalid),
+        }
+
+        migrations::apply_migrations(&mut conn, version, DATABASE_VERSION_MIN).map_err(
+            |error| DatabaseError::Migration {
+                error,
+                backup: None,
+            },
+        )?;
+
+        // folds any write-ahead log into the file, leaving a single file to copy and remove
+        conn.execute_batch("PRAGMA journal_mode = DELETE;")
+            .map_err(|err| DatabaseError::Connection(err.to_string()))?;
+
+        Ok(version)
+    }
+
+    /// Replaces the contents of the live database with a prepared database file.
+    /// Other connections are left open: the pages are copied in a single step as one write
+    /// transaction, so SQLite's locking keeps other writers waiting until it is done and readers
+    /// see either the old contents or the restored ones. Fails if the database stays busy.
+    pub fn restore(&self, path: &Path) -> Result<(), String> {
+        let mut conn = self
+            .pool
+            .get()
+            .expect("Error occurred getting database connection for restore");
+
+        let src = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|err| err.to_string())?;
+
+        let done = {
+            let backup = Backup::new(&src, &mut conn).map_err(|err| err.to_string())?;
+            let mut done = false;
+            for _ in 0..BACKUP_BUSY_RETRIES {
+                if backup.step(-1).map_err(|err| err.to_string())? == StepResult::Done {
+                    done = true;
+                    break;
+                }
+                thread::sleep(Duration::from_millis(BACKUP_BUSY_WAIT_MILLIS));
+            }
+            done
+        };
+        drop(conn);
+
+        if !done {
+            return Err(String::from("Database stayed busy"));
+        }
+
+        self.checkpoint(true);
+
+        Ok(())
+    }
+
     /// Checks database integrity
     pub fn integrity_check(&self) -> bool {
         let conn = self
@@ -160,28 +421,30 @@ impl Database {
             .get()
             .expect("Error occurred getting database connection for integrity checks");
 
-        if conn
-            .prepare("PRAGMA integrity_check(1);")
-            .unwrap()
-            .query_row([], |row| Ok(row.get::<_, String>(0)))
-            .expect("Error occurred while checking database integrity")
-            .unwrap()
-            != *"ok"
-        {
-            return false;
-        }
+        integrity_check(&conn).expect("Error occurred while checking database integrity")
+    }
+}
 
-        if conn
-            .prepare("PRAGMA foreign_key_check;")
-            .unwrap()
-            .query_map([], |row| Ok(row.get::<_, String>(0)))
-            .expect("Error occurred while checking database foreign key integrity")
-            .count()
-            > 0
-        {
-            return false;
-        }
+/// Gets the schema version of a database, 0 if it hasn't been created
+fn user_version(conn: &Connection) -> Result<u32, String> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|err| err.to_string())
+}
 
-        true
+/// Checks the integrity and foreign keys of a database.
+/// Files that can't be read as databases at all fail with an error.
+pub fn integrity_check(conn: &Connection) -> Result<bool, String> {
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check(1);", [], |row| row.get(0))
+        .map_err(|err| err.to_string())?;
+    if integrity != "ok" {
+        return Ok(false);
     }
+
+    let foreign_key_violations = conn
+        .prepare("PRAGMA foreign_key_check;")
+        .and_then(|mut stmt| stmt.query_map([], |_| Ok(())).map(|rows| rows.count()))
+        .map_err(|err| err.to_string())?;
+
+    Ok(foreign_key_violations == 0)
 }
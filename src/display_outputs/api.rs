@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+    database::display_outputs::{DbDisplayOutput, OutputKind},
+};
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/", get(list_outputs))
+        .route("/", post(create_output))
+        .route("/:output_id", get(get_output))
+        .route("/:output_id", put(update_output))
+        .route("/:output_id", delete(delete_output))
+}
+
+pub async fn list_outputs(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let outputs = state.display_outputs_service.list();
+
+    Json(outputs).into_response()
+}
+
+pub async fn get_output(
+    State(state): State<Arc<AppServices>>,
+    Path(output_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.display_outputs_service.get(output_id);
+
+    match result {
+        Some(output) => Json(output).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn create_output(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    Json(request): Json<DbDisplayOutput>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.display_outputs_service.create(&request);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "display_output_create",
+        json!({
+            "output_id": result.as_ref().ok(),
+            "name": request.name,
+            "kind": request.kind,
+            "target_output_id": request.target_output_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    if result.is_ok() && request.kind == OutputKind::Stage {
+        state
+            .stage_service
+            .update_stage_outputs(&state.state_service, request.target_output_id);
+    }
+
+    match result {
+        Ok(id) => Json(id).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn update_output(
+    State(state): State<Arc<AppServices>>,
+    Path(output_id): Path<Uuid>,
+    token: AuthToken,
+    Json(request): Json<DbDisplayOutput>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let mut output = request.clone();
+    output.id = Some(output_id);
+
+    let result = state.display_outputs_service.update(&output);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "display_output_update",
+        json!({
+            "output_id": output_id,
+            "name": output.name,
+            "kind": output.kind,
+            "target_output_id": output.target_output_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    if result.is_ok() && output.kind == OutputKind::Stage {
+        state
+            .stage_service
+            .update_stage_outputs(&state.state_service, output.target_output_id);
+    }
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn delete_output(
+    State(state): State<Arc<AppServices>>,
+    Path(output_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.display_outputs_service.delete(output_id);
+    if result.is_ok() {
+        state.state_service.remove_output(output_id);
+    }
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "display_output_delete",
+        json!({
+            "output_id": output_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
@@ -0,0 +1,208 @@
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    rngs::OsRng,
+};
+use rusqlite::{named_params, OptionalExtension};
+use uuid::Uuid;
+
+use crate::{
+    content::languages,
+    database::{
+        display_outputs::{DbDisplayOutput, OutputKind},
+        Database,
+    },
+    helpers::errors::GenericError,
+};
+
+const ACCESS_KEY_LENGTH: usize = 32;
+
+pub struct DisplayOutputsService {
+    db: Database,
+}
+
+impl DisplayOutputsService {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            db: database.clone(),
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<DbDisplayOutput> {
+        let db = self.db.get();
+
+        let output_result: Option<DbDisplayOutput> = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"display_outputs\" WHERE \"id\" = :id;",
+                DbDisplayOutput::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_row(named_params! {":id": id}, |row| {
+                Ok(DbDisplayOutput::from_row(row))
+            })
+            .optional()
+            .expect("Error occurred getting display output by id from database");
+
+        output_result
+    }
+
+    pub fn list(&self) -> Vec<DbDisplayOutput> {
+        let db = self.db.get();
+
+        let outputs = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"display_outputs\" ORDER BY \"name\";",
+                DbDisplayOutput::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_map(named_params! {}, |row| Ok(DbDisplayOutput::from_row(row)))
+            .expect("Error occurred getting all display outputs from database")
+            .map(|output| output.unwrap())
+            .collect();
+
+        outputs
+    }
+
+    /// Gets the stage outputs following an output
+    pub fn list_stage_outputs_for(&self, target_output_id: Option<Uuid>) -> Vec<DbDisplayOutput> {
+        let db = self.db.get();
+
+        let outputs = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"display_outputs\" WHERE \"kind\" = :kind AND \"target_output_id\" IS :target_output_id;",
+                DbDisplayOutput::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_map(
+                named_params! {":kind": OutputKind::Stage, ":target_output_id": target_output_id},
+                |row| Ok(DbDisplayOutput::from_row(row)),
+            )
+            .expect("Error occurred getting stage outputs from database")
+            .map(|output| output.unwrap())
+            .collect();
+
+        outputs
+    }
+
+    /// Checks that a stage output doesn't follow itself or another stage output
+    fn validate(&self, output: &DbDisplayOutput) -> Result<(), GenericError> {
+        if !output
+            .languages
+            .iter()
+            .all(|language| languages::is_language_tag(language))
+        {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        if output.kind == OutputKind::Stage {
+            if let Some(target_output_id) = output.target_output_id {
+                if output.id == Some(target_output_id) {
+                    return Err(GenericError::BAD_REQUEST);
+                }
+                match self.get(target_output_id) {
+                    Some(target) if target.kind == OutputKind::Audience => {}
+                    _ => return Err(GenericError::BAD_REQUEST),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Uses the access key given for an output, or generates one
+    fn access_key(access_key: &str) -> String {
+        match access_key.is_empty() {
+            true => Alphanumeric.sample_string(&mut OsRng, ACCESS_KEY_LENGTH),
+            false => access_key.to_owned(),
+        }
+    }
+
+    pub fn create(&self, output: &DbDisplayOutput) -> Result<Uuid, GenericError> {
+        self.validate(output)?;
+
+        let output_id = Uuid::new_v4();
+
+        let db = self.db.get();
+        let success = db
+            .prepare_cached("INSERT INTO \"display_outputs\" (\"id\", \"name\", \"kind\", \"target_output_id\", \"languages\", \"access_key\") VALUES (:id, :name, :kind, :target_output_id, :languages, :access_key);")
+            .unwrap()
+            .execute(named_params! {
+                ":id": output_id,
+                ":name": output.name,
+                ":kind": output.kind,
+                ":target_output_id": output.target_output_id,
+                ":languages": serde_json::to_string(&output.languages).unwrap(),
+                ":access_key": Self::access_key(&output.access_key),
+            })
+            .is_ok();
+
+        if success {
+            Ok(output_id)
+        } else {
+            Err(GenericError::BAD_REQUEST)
+        }
+    }
+
+    pub fn update(&self, output: &DbDisplayOutput) -> Result<Uuid, GenericError> {
+        let Some(output_id) = output.id else {
+            return Err(GenericError::BAD_REQUEST);
+        };
+        let Some(old_output) = self.get(output_id) else {
+            return Err(GenericError::NOT_FOUND);
+        };
+
+        self.validate(output)?;
+
+        // outputs followed by stage outputs must stay audience outputs
+        if output.kind == OutputKind::Stage
+            && !self.list_stage_outputs_for(Some(output_id)).is_empty()
+        {
+            return Err(GenericError::CONFLICT);
+        }
+
+        let db = self.db.get();
+        let success = db
+            .prepare_cached("UPDATE \"display_outputs\" SET \"name\" = :name, \"kind\" = :kind, \"target_output_id\" = :target_output_id, \"languages\" = :languages, \"access_key\" = :access_key WHERE \"id\" = :id;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": output_id,
+                ":name": output.name,
+                ":kind": output.kind,
+                ":target_output_id": output.target_output_id,
+                ":languages": serde_json::to_string(&output.languages).unwrap(),
+                // keys are kept unless replaced
+                ":access_key": match output.access_key.is_empty() {
+                    true => old_output.access_key,
+                    false => output.access_key.clone(),
+                },
+            })
+            .is_ok();
+
+        if success {
+            Ok(output_id)
+        } else {
+            Err(GenericError::BAD_REQUEST)
+        }
+    }
+
+    pub fn delete(&self, output_id: Uuid) -> Result<(), GenericError> {
+        let Some(output) = self.get(output_id) else {
+            return Err(GenericError::NOT_FOUND);
+        };
+
+        let db = self.db.get();
+
+        let success = db
+            .prepare_cached("DELETE FROM \"display_outputs\" WHERE \"id\" = :id;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": output.id,
+            })
+            .is_ok();
+
+        if success {
+            Ok(())
+        } else {
+            Err(GenericError::BAD_REQUEST)
+        }
+    }
+}
@@ -11,11 +11,17 @@ impl GenericError {
     pub const FORBIDDEN: GenericError = GenericError(403);
     pub const NOT_FOUND: GenericError = GenericError(404);
     pub const CONFLICT: GenericError = GenericError(409);
+    pub const PAYLOAD_TOO_LARGE: GenericError = GenericError(413);
     pub const INTERNAL_SERVER_ERROR: GenericError = GenericError(500);
 
     pub fn to_status_code(&self) -> StatusCode {
         StatusCode::from_u16(self.0).unwrap()
     }
+
+    /// Whether the error is caused by what was asked for, so asking again fails the same way
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.0)
+    }
 }
 
 impl Error for GenericError {}
@@ -30,6 +36,7 @@ impl Display for GenericError {
                 403 => "Forbidden",
                 404 => "Not Found",
                 409 => "Conflict",
+                413 => "Payload Too Large",
                 500 => "Internal Server Error",
                 _ => "Unknown Error",
             }
@@ -0,0 +1,516 @@
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+    helpers::errors::GenericError,
+    jobs::db::JobType,
+};
+
+use super::{
+    chordpro::ChordProImportOptions,
+    csv::CsvImportOptions,
+    images::ImageImportOptions,
+    model::{ImportJob, ImportOptions, ImportReport, Importer},
+    openlyrics,
+    text::TextImportOptions,
+};
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/csv", post(import_csv).layer(DefaultBodyLimit::disable()))
+        .route(
+            "/openlyrics",
+            post(import_openlyrics).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/openlyrics/:slide_group_id", get(export_openlyrics))
+        .route(
+            "/chordpro",
+            post(import_chordpro).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/text",
+            post(import_text).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/openlp",
+            post(import_openlp).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/propresenter",
+            post(import_propresenter).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/pptx",
+            post(import_pptx).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/images",
+            post(import_images).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/images/folder", post(import_image_folder))
+}
+
+/// Reads a multipart form of an `options` JSON field followed by a `file` field,
+/// saving the file to a temporary path
+async fn read_upload<T: DeserializeOwned>(
+    state: &AppServices,
+    mut multipart: Multipart,
+) -> Result<(T, PathBuf), GenericError> {
+    let mut options = None;
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => return Err(GenericError::BAD_REQUEST),
+            Err(_) => return Err(GenericError::BAD_REQUEST),
+        };
+
+        match field.name() {
+            Some("options") => {
+                let text = field.text().await.map_err(|_| GenericError::BAD_REQUEST)?;
+                options = Some(serde_json::from_str(&text).map_err(|_| GenericError::BAD_REQUEST)?);
+            }
+            Some("file") => {
+                let Some(options) = options else {
+                    return Err(GenericError::BAD_REQUEST);
+                };
+                let path = state.imports_service.save_upload(field).await?;
+                return Ok((options, path));
+            }
+            _ => continue,
+        }
+    }
+}
+
+/// Reads a multipart form of an `options` JSON field followed by any number of `file` fields,
+/// saving the files to temporary paths kept with their file names
+async fn read_uploads<T: DeserializeOwned>(
+    state: &AppServices,
+    mut multipart: Multipart,
+) -> Result<(T, Vec<(String, PathBuf)>), GenericError> {
+    let mut options = None;
+    let mut paths = Vec::new();
+
+    let result = loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break Ok(()),
+            Err(_) => break Err(GenericError::BAD_REQUEST),
+        };
+
+        match field.name() {
+            Some("options") => {
+                let Ok(text) = field.text().await else {
+                    break Err(GenericError::BAD_REQUEST);
+                };
+                match serde_json::from_str(&text) {
+                    Ok(parsed) => options = Some(parsed),
+                    Err(_) => break Err(GenericError::BAD_REQUEST),
+                }
+            }
+            Some("file") if options.is_some() => {
+                let file_name = field.file_name().unwrap_or_default().to_owned();
+                match state.imports_service.save_upload(field).await {
+                    Ok(path) => paths.push((file_name, path)),
+                    Err(err) => break Err(err),
+                }
+            }
+            Some("file") => break Err(GenericError::BAD_REQUEST),
+            _ => continue,
+        }
+    };
+
+    match (result, options) {
+        (Ok(()), Some(options)) if !paths.is_empty() => Ok((options, paths)),
+        (result, _) => {
+            for (_, path) in paths {
+                let _ = fs::remove_file(path).await;
+            }
+            Err(result.err().unwrap_or(GenericError::BAD_REQUEST))
+        }
+    }
+}
+
+/// Previews slides made from the rows of a CSV file, or queues saving them
+pub async fn import_csv(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, path) = match read_upload::<CsvImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    if options.commit {
+        return queue_import(
+            &state,
+            current_user.id,
+            Importer::Csv(options),
+            vec![(String::new(), path)],
+        )
+        .await;
+    }
+
+    let result = state.imports_service.import_csv(&path, &options).await;
+    let _ = fs::remove_file(&path).await;
+
+    match result {
+        Ok(preview) => Json(preview).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Queues saving an import as a job, keeping its uploaded files as long as the job
+async fn queue_import(
+    state: &AppServices,
+    user_id: Uuid,
+    importer: Importer,
+    files: Vec<(String, PathBuf)>,
+) -> axum::response::Response {
+    let job_id = Uuid::new_v4();
+    let paths: Vec<PathBuf> = files.iter().map(|(_, path)| path.clone()).collect();
+    let kept_paths = state.jobs_service.keep_files(job_id, &paths).await;
+    let files = files
+        .into_iter()
+        .map(|(file_name, _)| file_name)
+        .zip(kept_paths)
+        .collect();
+
+    state.jobs_service.enqueue_as(
+        job_id,
+        JobType::Import,
+        json!(ImportJob { importer, files }),
+        Some(user_id),
+    );
+
+    match state.jobs_service.get(job_id) {
+        Some(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Sends the report of an import preview
+fn preview_response(result: Result<ImportReport, GenericError>) -> axum::response::Response {
+    match result {
+        Ok(report) => Json(report).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Previews an OpenLyrics song, or queues saving it as a group and deck
+pub async fn import_openlyrics(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, path) = match read_upload::<ImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    if options.commit {
+        return queue_import(
+            &state,
+            current_user.id,
+            Importer::OpenLyrics(options),
+            vec![(String::new(), path)],
+        )
+        .await;
+    }
+
+    let result = state
+        .imports_service
+        .import_openlyrics(&path, &options, Some(current_user.id))
+        .await;
+    let _ = fs::remove_file(&path).await;
+
+    preview_response(result)
+}
+
+/// Downloads a slide group as an OpenLyrics song
+pub async fn export_openlyrics(
+    State(state): State<Arc<AppServices>>,
+    Path(slide_group_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let Some(song) = state.imports_service.song(slide_group_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // header values only allow plain ASCII
+    let file_name: String = song
+        .title
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || " -_.()".contains(c) => c,
+            _ => '_',
+        })
+        .collect();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/xml"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}.xml\"", file_name)).unwrap(),
+    );
+
+    (StatusCode::OK, headers, openlyrics::write(&song)).into_response()
+}
+
+/// Previews a ChordPro song, or queues saving it as a group and deck
+pub async fn import_chordpro(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, path) = match read_upload::<ChordProImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    if options.import.commit {
+        return queue_import(
+            &state,
+            current_user.id,
+            Importer::ChordPro(options),
+            vec![(String::new(), path)],
+        )
+        .await;
+    }
+
+    let result = state
+        .imports_service
+        .import_chordpro(&path, &options, Some(current_user.id))
+        .await;
+    let _ = fs::remove_file(&path).await;
+
+    preview_response(result)
+}
+
+/// Previews plain text or Markdown, or queues saving it as a group and deck
+pub async fn import_text(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, path) = match read_upload::<TextImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    if options.import.commit {
+        return queue_import(
+            &state,
+            current_user.id,
+            Importer::Text(options),
+            vec![(String::new(), path)],
+        )
+        .await;
+    }
+
+    let result = state
+        .imports_service
+        .import_text(&path, &options, Some(current_user.id))
+        .await;
+    let _ = fs::remove_file(&path).await;
+
+    preview_response(result)
+}
+
+/// Previews the songs and custom slides of OpenLP databases, or queues saving them,
+/// each uploaded as a `file` field
+pub async fn import_openlp(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SYSTEM_ADMIN) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, files) = match read_uploads::<ImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    if options.commit {
+        return queue_import(&state, current_user.id, Importer::OpenLp(options), files).await;
+    }
+
+    let paths: Vec<PathBuf> = files.into_iter().map(|(_, path)| path).collect();
+    let result = state
+        .imports_service
+        .import_openlp(&paths, &options, Some(current_user.id))
+        .await;
+    for path in paths {
+        let _ = fs::remove_file(path).await;
+    }
+
+    preview_response(result)
+}
+
+/// Previews ProPresenter 6 documents, or queues saving them, each uploaded as a `file` field
+pub async fn import_propresenter(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, files) = match read_uploads::<ImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    if options.commit {
+        return queue_import(
+            &state,
+            current_user.id,
+            Importer::ProPresenter(options),
+            files,
+        )
+        .await;
+    }
+
+    let result = state
+        .imports_service
+        .import_propresenter(&files, &options, Some(current_user.id))
+        .await;
+    for (_, path) in files {
+        let _ = fs::remove_file(path).await;
+    }
+
+    preview_response(result)
+}
+
+/// Previews a PowerPoint presentation, or queues saving it as a group and deck
+pub async fn import_pptx(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, files) = match read_uploads::<ImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    // a presentation makes a deck of its own
+    let [(file_name, path)] = files.as_slice() else {
+        for (_, path) in files {
+            let _ = fs::remove_file(path).await;
+        }
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if options.commit {
+        return queue_import(&state, current_user.id, Importer::Pptx(options), files).await;
+    }
+
+    let result = state
+        .imports_service
+        .import_pptx(path, file_name, &options, Some(current_user.id))
+        .await;
+    let _ = fs::remove_file(path).await;
+
+    preview_response(result)
+}
+
+/// Previews the images of a zip file, or queues saving them as a slideshow group and deck
+pub async fn import_images(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let (options, files) = match read_uploads::<ImageImportOptions>(&state, multipart).await {
+        Ok(upload) => upload,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    let [(file_name, path)] = files.as_slice() else {
+        for (_, path) in files {
+            let _ = fs::remove_file(path).await;
+        }
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if options.import.commit {
+        return queue_import(&state, current_user.id, Importer::Images(options), files).await;
+    }
+
+    let result = state
+        .imports_service
+        .import_images(Some((file_name, path)), &options, Some(current_user.id))
+        .await;
+    let _ = fs::remove_file(path).await;
+
+    preview_response(result)
+}
+
+/// Previews the images of a folder in the import directory, or queues saving them as a
+/// slideshow group and deck
+pub async fn import_image_folder(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    Json(options): Json<ImageImportOptions>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+    if options.folder.is_none() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    if options.import.commit {
+        return queue_import(
+            &state,
+            current_user.id,
+            Importer::Images(options),
+            Vec::new(),
+        )
+        .await;
+    }
+
+    let result = state
+        .imports_service
+        .import_images(None, &options, Some(current_user.id))
+        .await;
+
+    preview_response(result)
+}
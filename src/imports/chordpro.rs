@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    model::{ImportOptions, ImportWarning},
+    songs::{Song, SongVerse, LABEL_KEY, TEXT_KEY},
+};
+
+/// Slide content key for lyrics with chords above them, hidden from audience outputs
+pub const CHORDS_KEY: &str = "chords";
+
+/// Group content keys for metadata directives, by directive name and abbreviation
+const METADATA_DIRECTIVES: &[(&str, &str)] = &[
+    ("subtitle", "subtitle"),
+    ("st", "subtitle"),
+    ("artist", "artist"),
+    ("composer", "composer"),
+    ("lyricist", "lyricist"),
+    ("copyright", "copyright"),
+    ("album", "album"),
+    ("year", "year"),
+    ("key", "key"),
+    ("time", "time"),
+    ("tempo", "tempo"),
+    ("capo", "capo"),
+    ("ccli", "ccli_number"),
+];
+
+/// What to do with chords in the lyrics
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChordHandling {
+    /// Removes chords
+    Strip,
+    /// Removes chords from the text, keeping them above the lyrics in the `chords` key
+    #[default]
+    Keep,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ChordProImportOptions {
+    #[serde(flatten)]
+    pub import: ImportOptions,
+    #[serde(default)]
+    pub chords: ChordHandling,
+}
+
+/// Kinds of sections, by the prefix of their verse names
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SectionKind {
+    Verse,
+    Chorus,
+    Bridge,
+}
+
+impl SectionKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Self::Verse => "v",
+            Self::Chorus => "c",
+            Self::Bridge => "b",
+        }
+    }
+}
+
+struct Section {
+    kind: SectionKind,
+    label: Option<String>,
+    lines: Vec<String>,
+}
+
+/// Splits a line into its lyrics and a line of its chords placed above them
+fn split_chords(line: &str) -> (String, String) {
+    let mut text = String::new();
+    let mut chords = String::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find('[') {
+        let Some(length) = rest[start..].find(']') else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        let chord = &rest[start + 1..start + length];
+
+        // chords start where their lyric does, pushed along if the previous one is still in the way
+        let column = text.chars().count();
+        let used = chords.chars().count();
+        if used < column {
+            chords.push_str(&" ".repeat(column - used));
+        } else if used > 0 {
+            chords.push(' ');
+        }
+        chords.push_str(chord);
+
+        rest = &rest[start + length + 1..];
+    }
+    text.push_str(rest);
+
+    (text.trim_end().to_owned(), chords.trim_end().to_owned())
+}
+
+/// Splits a directive such as `{title: Song}` into its lowercase name and value
+fn directive(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('{')?.strip_suffix('}')?;
+    let (name, value) = match inner.split_once([':', ' ']) {
+        Some((name, value)) => (name, value.trim()),
+        None => (inner, ""),
+    };
+    // labels may also be given as an attribute, as in `{start_of_verse label="Verse 2"}`
+    let value = value
+        .strip_prefix("label=")
+        .map(|label| label.trim_matches('"'))
+        .unwrap_or(value);
+    Some((name.trim().to_lowercase(), value.to_owned()))
+}
+
+fn section_start(name: &str) -> Option<SectionKind> {
+    match name {
+        "start_of_verse" | "sov" => Some(SectionKind::Verse),
+        "start_of_chorus" | "soc" => Some(SectionKind::Chorus),
+        "start_of_bridge" | "sob" => Some(SectionKind::Bridge),
+        _ => None,
+    }
+}
+
+/// Ends the current section, keeping it unless it has no lyrics
+fn finish(current: &mut Option<Section>, sections: &mut Vec<Section>, order: &mut Vec<usize>) {
+    if let Some(section) = current.take() {
+        if section.lines.iter().any(|line| !line.trim().is_empty()) {
+            order.push(sections.len());
+            sections.push(section);
+        }
+    }
+}
+
+/// Reads a ChordPro song. Text outside of sections is split into verses at blank lines,
+/// and tabs, grids and comments are left out.
+pub fn parse(text: &str, chords: ChordHandling, warnings: &mut Vec<ImportWarning>) -> Song {
+    let mut song = Song::default();
+    let mut sections: Vec<Section> = Vec::new();
+    // sections in the order they are sung, as indexes into `sections`
+    let mut order: Vec<usize> = Vec::new();
+
+    let mut current: Option<Section> = None;
+    let mut in_section = false;
+    let mut skipping = false;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        let trimmed = line.trim();
+
+        if trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some((name, value)) = directive(trimmed) {
+            if let Some(kind) = section_start(&name) {
+                finish(&mut current, &mut sections, &mut order);
+                current = Some(Section {
+                    kind,
+                    label: Some(value).filter(|value| !value.is_empty()),
+                    lines: Vec::new(),
+                });
+                in_section = true;
+                continue;
+            }
+            match name.as_str() {
+                "end_of_verse" | "eov" | "end_of_chorus" | "eoc" | "end_of_bridge" | "eob" => {
+                    finish(&mut current, &mut sections, &mut order);
+                    in_section = false;
+                }
+                "start_of_tab" | "sot" | "start_of_grid" | "sog" => skipping = true,
+                "end_of_tab" | "eot" | "end_of_grid" | "eog" => skipping = false,
+                "title" | "t" => song.title = value,
+                // repeats the latest chorus
+                "chorus" => {
+                    finish(&mut current, &mut sections, &mut order);
+                    match sections
+                        .iter()
+                        .rposition(|section| section.kind == SectionKind::Chorus)
+                    {
+                        Some(index) => order.push(index),
+                        None => warnings.push(ImportWarning {
+                            source: format!("line {}", number + 1),
+                            message: String::from("Chorus repeated before any chorus"),
+                        }),
+                    }
+                }
+                "comment" | "c" | "comment_italic" | "ci" | "comment_box" | "cb" | "highlight" => {}
+                name => match METADATA_DIRECTIVES
+                    .iter()
+                    .find(|(directive, _)| *directive == name)
+                {
+                    Some((_, key)) => {
+                        song.metadata.insert(String::from(*key), value);
+                    }
+                    None => warnings.push(ImportWarning {
+                        source: format!("line {}", number + 1),
+                        message: format!("Directive \"{}\" is not supported", name),
+                    }),
+                },
+            }
+            continue;
+        }
+
+        if skipping {
+            continue;
+        }
+
+        // text outside of sections is split into verses at blank lines
+        if trimmed.is_empty() && !in_section {
+            finish(&mut current, &mut sections, &mut order);
+            continue;
+        }
+        current
+            .get_or_insert_with(|| Section {
+                kind: SectionKind::Verse,
+                label: None,
+                lines: Vec::new(),
+            })
+            .lines
+            .push(line.to_owned());
+    }
+    finish(&mut current, &mut sections, &mut order);
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for section in &sections {
+        let count = counts.entry(section.kind.prefix()).or_default();
+        *count += 1;
+        let name = format!("{}{}", section.kind.prefix(), count);
+
+        let mut text_lines = Vec::new();
+        let mut chord_lines = Vec::new();
+        for line in &section.lines {
+            let (text, line_chords) = split_chords(line);
+            if !line_chords.is_empty() {
+                chord_lines.push(line_chords);
+            }
+            chord_lines.push(text.clone());
+            text_lines.push(text);
+        }
+
+        let mut content = BTreeMap::new();
+        content.insert(
+            String::from(TEXT_KEY),
+            text_lines.join("\n").trim().to_owned(),
+        );
+        if chords == ChordHandling::Keep {
+            content.insert(
+                String::from(CHORDS_KEY),
+                chord_lines.join("\n").trim_matches('\n').to_owned(),
+            );
+        }
+        if let Some(label) = &section.label {
+            content.insert(String::from(LABEL_KEY), label.clone());
+        }
+
+        song.verses.push(SongVerse {
+            name,
+            slides: vec![content],
+        });
+    }
+
+    song.verse_order = order
+        .into_iter()
+        .map(|index| song.verses[index].name.clone())
+        .collect();
+
+    song
+}
@@ -0,0 +1,97 @@
+use std::{collections::BTreeMap, path::Path};
+
+use rusqlite::{vtab::csvtab, Connection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::helpers::errors::GenericError;
+
+/// How the columns of a CSV file become slides
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CsvImportOptions {
+    /// Group to add the slides to, `None` for slides outside any group
+    pub slide_group_id: Option<Uuid>,
+    pub slide_type_id: Option<Uuid>,
+    /// Column holding the name of each slide
+    pub name_column: String,
+    /// Content key to store each column in, other columns are ignored
+    #[serde(default)]
+    pub columns: BTreeMap<String, String>,
+    /// Field delimiter if not a comma
+    pub delimiter: Option<char>,
+    /// Saves the slides if nothing is wrong, otherwise only previews them
+    #[serde(default)]
+    pub commit: bool,
+}
+
+/// Slide made from a row, as it would be saved
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CsvSlide {
+    /// Row in the file, counting the header as row 1
+    pub row: usize,
+    pub name: String,
+    pub content: BTreeMap<String, String>,
+}
+
+/// Problem with the file or a row which stops the import from being saved
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CsvImportError {
+    /// Row in the file, or `None` for problems with the whole file
+    pub row: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CsvImportPreview {
+    /// Columns found in the header row
+    pub columns: Vec<String>,
+    pub row_count: usize,
+    /// The first rows as slides
+    pub slides: Vec<CsvSlide>,
+    pub errors: Vec<CsvImportError>,
+    /// Whether the slides were saved
+    pub committed: bool,
+}
+
+pub struct CsvTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Reads a CSV file with a header row through SQLite's CSV virtual table
+pub fn read_csv(path: &Path, delimiter: Option<char>) -> Result<CsvTable, GenericError> {
+    let delimiter = match delimiter {
+        Some(delimiter) if delimiter.is_ascii() && delimiter != '\'' => delimiter,
+        Some(_) => return Err(GenericError::BAD_REQUEST),
+        None => ',',
+    };
+    let filename = path.to_str().ok_or(GenericError::INTERNAL_SERVER_ERROR)?;
+
+    // a separate connection keeps the virtual table away from the application database
+    let conn = Connection::open_in_memory().expect("Error occurred opening CSV database");
+    csvtab::load_module(&conn).expect("Error occurred loading CSV module");
+
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE temp.\"csv\" USING csv(filename='{}', header=yes, delimiter='{}');",
+        filename.replace('\'', "''"),
+        delimiter
+    ))
+    .map_err(|_| GenericError::BAD_REQUEST)?;
+
+    let mut stmt = conn
+        .prepare("SELECT * FROM temp.\"csv\";")
+        .map_err(|_| GenericError::BAD_REQUEST)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let rows = stmt
+        .query_map([], |row| {
+            (0..columns.len())
+                .map(|index| row.get::<_, Option<String>>(index))
+                .map(|value| value.map(Option::unwrap_or_default))
+                .collect::<Result<Vec<String>, _>>()
+        })
+        .map_err(|_| GenericError::BAD_REQUEST)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| GenericError::BAD_REQUEST)?;
+
+    Ok(CsvTable { columns, rows })
+}
@@ -0,0 +1,185 @@
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{self, Read},
+    iter::Peekable,
+    path::{Component, Path, PathBuf},
+    str::Chars,
+};
+
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::helpers::errors::GenericError;
+
+use super::model::{ImportOptions, ImportWarning};
+
+/// Slide content key for how many seconds a slide shows before advancing on its own
+pub const DURATION_KEY: &str = "duration";
+/// Seconds each slide shows for when no duration is given
+const DEFAULT_DURATION: u64 = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ImageImportOptions {
+    #[serde(flatten)]
+    pub import: ImportOptions,
+    /// Seconds each slide shows before advancing on its own
+    #[serde(default = "default_duration")]
+    pub duration: u64,
+    /// Folder within the import directory to read images from, when not uploading a zip file
+    pub folder: Option<String>,
+}
+
+fn default_duration() -> u64 {
+    DEFAULT_DURATION
+}
+
+/// Image file read for an import, from a folder or extracted from a zip file
+pub struct ImageFile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+pub fn is_image(name: &str) -> bool {
+    mime_guess::from_path(name)
+        .first()
+        .is_some_and(|mime_type| mime_type.type_() == mime_guess::mime::IMAGE)
+}
+
+/// Files left by other programs, such as `.DS_Store` or `__MACOSX/._image.png`
+fn is_hidden(path: &str) -> bool {
+    path.split(['/', '\\'])
+        .any(|segment| segment.starts_with('.') || segment == "__MACOSX")
+}
+
+/// Takes the number at the start of some characters, without leading zeros
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+    digits.trim_start_matches('0').to_owned()
+}
+
+/// Compares file names with numbers in them by value, so `Slide2` comes before `Slide10`
+pub fn compare_names(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                let ordering = x.len().cmp(&y.len()).then(x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Resolves a folder given relative to the import directory, rejecting any way out of it
+pub fn folder_path(import_directory: &str, folder: &str) -> Option<PathBuf> {
+    let valid = Path::new(folder)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    valid.then(|| Path::new(import_directory).join(folder))
+}
+
+/// Lists the images directly in a folder, leaving out anything else
+pub fn read_folder(
+    path: &Path,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Vec<ImageFile>, GenericError> {
+    let entries = std::fs::read_dir(path).map_err(|_| GenericError::NOT_FOUND)?;
+
+    let mut images = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if is_hidden(&name) || !entry.path().is_file() {
+            continue;
+        }
+        if !is_image(&name) {
+            warnings.push(ImportWarning {
+                source: name,
+                message: String::from("File isn't an image, file skipped"),
+            });
+            continue;
+        }
+        images.push(ImageFile {
+            name,
+            path: entry.path(),
+        });
+    }
+
+    Ok(images)
+}
+
+/// Extracts the images of a zip file into a directory, wherever they are in the zip file,
+/// leaving out anything else and images larger than `max_size`
+pub fn extract_zip(
+    path: &Path,
+    directory: &Path,
+    max_size: u64,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Vec<ImageFile>, GenericError> {
+    let file = File::open(path).map_err(|_| GenericError::BAD_REQUEST)?;
+    let mut zip = ZipArchive::new(file).map_err(|_| GenericError::BAD_REQUEST)?;
+    std::fs::create_dir_all(directory).expect("Failed to create import directory");
+
+    let mut images = Vec::new();
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(|_| GenericError::BAD_REQUEST)?;
+        let entry_name = entry.name().to_owned();
+        if entry.is_dir() || is_hidden(&entry_name) {
+            continue;
+        }
+
+        let name = entry_name
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(&entry_name)
+            .to_owned();
+        if !is_image(&name) {
+            warnings.push(ImportWarning {
+                source: entry_name,
+                message: String::from("File isn't an image, file skipped"),
+            });
+            continue;
+        }
+
+        // files are extracted by index as names may repeat in different folders
+        let image_path = directory.join(index.to_string());
+        let mut image_file = File::create(&image_path).expect("Failed to create image file");
+        let copied = io::copy(&mut (&mut entry).take(max_size + 1), &mut image_file);
+        match copied {
+            Ok(size) if size <= max_size => images.push(ImageFile {
+                name,
+                path: image_path,
+            }),
+            Ok(_) => warnings.push(ImportWarning {
+                source: entry_name,
+                message: String::from("Image is too large, image skipped"),
+            }),
+            Err(_) => warnings.push(ImportWarning {
+                source: entry_name,
+                message: String::from("Image could not be extracted, image skipped"),
+            }),
+        }
+    }
+
+    Ok(images)
+}
@@ -0,0 +1,12 @@
+pub mod api;
+pub mod chordpro;
+pub mod csv;
+pub mod images;
+pub mod model;
+pub mod openlp;
+pub mod openlyrics;
+pub mod pptx;
+pub mod propresenter;
+pub mod service;
+pub mod songs;
+pub mod text;
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bundles::model::{ConflictPolicy, DeckBundle, ImportResult};
+
+use super::{
+    chordpro::ChordProImportOptions, csv::CsvImportOptions, images::ImageImportOptions,
+    text::TextImportOptions,
+};
+
+/// Slide content key for the image shown behind a slide, as a media URL
+pub const BACKGROUND_KEY: &str = "background";
+/// Slide content key for video or audio played with a slide, as a media URL
+pub const MEDIA_KEY: &str = "media";
+
+/// Options shared by importers that turn a file into a new deck
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// How to handle groups, slides and decks whose names are already taken
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+    /// Name of the deck to create instead of one taken from the file
+    pub deck_name: Option<String>,
+    /// Saves the import, otherwise it is only previewed
+    #[serde(default)]
+    pub commit: bool,
+}
+
+/// Problem with part of an imported file, which is left out of the import
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ImportWarning {
+    /// Where in the file the problem is, such as a verse or entry name
+    pub source: String,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// What is imported, or would be when only previewing
+    pub bundle: DeckBundle,
+    pub warnings: Vec<ImportWarning>,
+    /// Outcome of saving the import, `None` for previews
+    pub result: Option<ImportResult>,
+}
+
+/// Importer a job saves slides with, and its options
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "importer", content = "options", rename_all = "snake_case")]
+pub enum Importer {
+    Csv(CsvImportOptions),
+    #[serde(rename = "openlyrics")]
+    OpenLyrics(ImportOptions),
+    #[serde(rename = "chordpro")]
+    ChordPro(ChordProImportOptions),
+    Text(TextImportOptions),
+    #[serde(rename = "openlp")]
+    OpenLp(ImportOptions),
+    #[serde(rename = "propresenter")]
+    ProPresenter(ImportOptions),
+    Pptx(ImportOptions),
+    Images(ImageImportOptions),
+}
+
+/// Payload of a job saving an import, whose uploaded files are kept in the directory of the job
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ImportJob {
+    #[serde(flatten)]
+    pub importer: Importer,
+    /// Uploaded files with their original names
+    pub files: Vec<(String, PathBuf)>,
+}
@@ -0,0 +1,306 @@
+use std::{collections::BTreeMap, path::Path};
+
+use roxmltree::Document;
+use rusqlite::{named_params, Connection, OpenFlags};
+
+use crate::helpers::errors::GenericError;
+
+use super::{
+    model::ImportWarning,
+    openlyrics::{AUTHORS_KEY, AUTHOR_SEPARATOR},
+    songs::{Song, SongVerse, TEXT_KEY},
+};
+
+/// Markers splitting a verse or custom slide over several slides
+const SLIDE_SPLITS: &[&str] = &["[---]", "[===]"];
+/// Prefix of the slide names of custom slides, which have no verse types
+const CUSTOM_SLIDE_PREFIX: &str = "s";
+pub const CREDITS_KEY: &str = "credits";
+
+/// Group content keys for song columns kept on import
+const SONG_COLUMNS: &[(&str, &str)] = &[
+    ("alternate_title", "alternate_title"),
+    ("copyright", "copyright"),
+    ("ccli_number", "ccli_number"),
+    ("comments", "comments"),
+];
+
+/// Kinds of OpenLP databases
+enum Library {
+    Songs,
+    Custom,
+}
+
+/// Removes formatting tags such as `{st}` and `{/st}`, keeping line breaks given as `{br}`
+fn strip_tags(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        stripped.push_str(&rest[..start]);
+        let tag = &rest[start + 1..start + length];
+        if tag == "br" {
+            stripped.push('\n');
+        } else if !tag
+            .trim_start_matches('/')
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            // braces that aren't tags are part of the text
+            stripped.push_str(&rest[start..start + length + 1]);
+        }
+        rest = &rest[start + length + 1..];
+    }
+    stripped.push_str(rest);
+
+    stripped
+}
+
+/// Splits the text of a verse into the text of its slides
+fn split_slides(text: &str) -> Vec<String> {
+    let mut text = strip_tags(text);
+    for split in &SLIDE_SPLITS[1..] {
+        text = text.replace(split, SLIDE_SPLITS[0]);
+    }
+    text.split(SLIDE_SPLITS[0])
+        .map(|slide| {
+            slide
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_owned()
+        })
+        .filter(|slide| !slide.is_empty())
+        .collect()
+}
+
+/// Reads the lyrics XML of a song or custom slide into verses named by their type and label,
+/// custom slides being named with `name_prefix` as they have no type
+fn read_verses(xml: &str, name_prefix: Option<&str>) -> Result<Vec<SongVerse>, String> {
+    if xml.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let document = Document::parse(xml).map_err(|err| err.to_string())?;
+
+    let mut verses: Vec<SongVerse> = Vec::new();
+    for (index, verse) in document
+        .descendants()
+        .filter(|node| node.has_tag_name("verse"))
+        .enumerate()
+    {
+        let label = verse
+            .attribute("label")
+            .map(str::to_lowercase)
+            .unwrap_or_else(|| (index + 1).to_string());
+        let name = match name_prefix {
+            Some(prefix) => format!("{}{}", prefix, label),
+            // older versions spell out types, as in "Verse"
+            None => {
+                let verse_type = verse.attribute("type").unwrap_or("v");
+                let verse_type = verse_type.chars().next().unwrap_or('v');
+                format!("{}{}", verse_type.to_ascii_lowercase(), label)
+            }
+        };
+
+        let text: String = verse
+            .descendants()
+            .filter(|node| node.is_text())
+            .filter_map(|node| node.text())
+            .collect();
+        let slides: Vec<BTreeMap<String, String>> = split_slides(&text)
+            .into_iter()
+            .map(|text| BTreeMap::from([(String::from(TEXT_KEY), text)]))
+            .collect();
+        if slides.is_empty() {
+            continue;
+        }
+
+        match verses.iter_mut().find(|existing| existing.name == name) {
+            Some(existing) => existing.slides.extend(slides),
+            None => verses.push(SongVerse { name, slides }),
+        }
+    }
+
+    Ok(verses)
+}
+
+fn library_kind(db: &Connection) -> Option<Library> {
+    let tables: Vec<String> = db
+        .prepare("SELECT \"name\" FROM \"sqlite_master\" WHERE \"type\" = 'table';")
+        .ok()?
+        .query_map([], |row| row.get(0))
+        .ok()?
+        .filter_map(Result::ok)
+        .collect();
+
+    if tables.iter().any(|table| table == "songs") {
+        Some(Library::Songs)
+    } else if tables.iter().any(|table| table == "custom_slide") {
+        Some(Library::Custom)
+    } else {
+        None
+    }
+}
+
+fn read_songs(db: &Connection, warnings: &mut Vec<ImportWarning>) -> rusqlite::Result<Vec<Song>> {
+    let mut authors_query = db.prepare(
+        "SELECT \"authors\".\"display_name\" FROM \"authors\" JOIN \"authors_songs\" ON \"authors_songs\".\"author_id\" = \"authors\".\"id\" WHERE \"authors_songs\".\"song_id\" = :song_id ORDER BY \"authors\".\"display_name\";",
+    )?;
+
+    let mut query = db.prepare(
+        "SELECT \"id\", \"title\", \"alternate_title\", \"lyrics\", \"verse_order\", \"copyright\", \"comments\", \"ccli_number\" FROM \"songs\" ORDER BY \"title\", \"id\";",
+    )?;
+    let mut rows = query.query([])?;
+
+    let mut songs = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get("id")?;
+        let title: String = row.get::<_, Option<String>>("title")?.unwrap_or_default();
+        let source = format!("song {} \"{}\"", id, title);
+
+        let lyrics: Option<String> = row.get("lyrics")?;
+        let verses = match read_verses(&lyrics.unwrap_or_default(), None) {
+            Ok(verses) if !verses.is_empty() => verses,
+            Ok(_) => {
+                warnings.push(ImportWarning {
+                    source,
+                    message: String::from("Song has no lyrics, song skipped"),
+                });
+                continue;
+            }
+            Err(err) => {
+                warnings.push(ImportWarning {
+                    source,
+                    message: format!("Lyrics could not be read ({}), song skipped", err),
+                });
+                continue;
+            }
+        };
+
+        let mut metadata = BTreeMap::new();
+        for (column, key) in SONG_COLUMNS {
+            // numbers are stored as text, though not always
+            let value = match row.get_ref(*column)? {
+                rusqlite::types::ValueRef::Integer(value) => value.to_string(),
+                rusqlite::types::ValueRef::Text(value) => {
+                    String::from_utf8_lossy(value).trim().to_owned()
+                }
+                _ => continue,
+            };
+            if !value.is_empty() {
+                metadata.insert(String::from(*key), value);
+            }
+        }
+
+        let authors: Vec<String> = authors_query
+            .query_map(named_params! {":song_id": id}, |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        if !authors.is_empty() {
+            metadata.insert(String::from(AUTHORS_KEY), authors.join(AUTHOR_SEPARATOR));
+        }
+
+        // the number is often left out of orders for verses that there's only one of
+        let verse_order = row
+            .get::<_, Option<String>>("verse_order")?
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .map(|name| {
+                let numbered = format!("{}1", name);
+                match verses.iter().any(|verse| verse.name == name) {
+                    false if verses.iter().any(|verse| verse.name == numbered) => numbered,
+                    _ => name,
+                }
+            })
+            .collect();
+
+        songs.push(Song {
+            title,
+            metadata,
+            verses,
+            verse_order,
+        });
+    }
+
+    Ok(songs)
+}
+
+fn read_custom_slides(
+    db: &Connection,
+    warnings: &mut Vec<ImportWarning>,
+) -> rusqlite::Result<Vec<Song>> {
+    let mut query = db.prepare(
+        "SELECT \"id\", \"title\", \"text\", \"credits\" FROM \"custom_slide\" ORDER BY \"title\", \"id\";",
+    )?;
+    let mut rows = query.query([])?;
+
+    let mut songs = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get("id")?;
+        let title: String = row.get::<_, Option<String>>("title")?.unwrap_or_default();
+        let source = format!("custom slide {} \"{}\"", id, title);
+
+        let text: Option<String> = row.get("text")?;
+        let verses = match read_verses(&text.unwrap_or_default(), Some(CUSTOM_SLIDE_PREFIX)) {
+            Ok(verses) if !verses.is_empty() => verses,
+            Ok(_) => {
+                warnings.push(ImportWarning {
+                    source,
+                    message: String::from("Custom slide has no text, custom slide skipped"),
+                });
+                continue;
+            }
+            Err(err) => {
+                warnings.push(ImportWarning {
+                    source,
+                    message: format!("Text could not be read ({}), custom slide skipped", err),
+                });
+                continue;
+            }
+        };
+
+        let mut metadata = BTreeMap::new();
+        if let Some(credits) = row.get::<_, Option<String>>("credits")? {
+            if !credits.trim().is_empty() {
+                metadata.insert(String::from(CREDITS_KEY), credits.trim().to_owned());
+            }
+        }
+
+        songs.push(Song {
+            title,
+            metadata,
+            verses,
+            verse_order: Vec::new(),
+        });
+    }
+
+    Ok(songs)
+}
+
+/// Reads the songs of an OpenLP song database or the slides of an OpenLP custom slide
+/// database, telling them apart by their tables. Entries that can't be read are skipped
+/// with a warning.
+pub fn read_library(
+    path: &Path,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Vec<Song>, GenericError> {
+    let db = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|_| GenericError::BAD_REQUEST)?;
+
+    let songs = match library_kind(&db) {
+        Some(Library::Songs) => read_songs(&db, warnings),
+        Some(Library::Custom) => read_custom_slides(&db, warnings),
+        None => return Err(GenericError::BAD_REQUEST),
+    };
+
+    // tables missing columns aren't from a version of OpenLP that can be read
+    songs.map_err(|_| GenericError::BAD_REQUEST)
+}
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use roxmltree::{Document, Node};
+
+use crate::{content::languages, helpers::errors::GenericError};
+
+use super::songs::{Song, SongVerse, TEXT_KEY};
+
+const NAMESPACE: &str = "http://openlyrics.info/namespace/2009/song";
+const VERSION: &str = "0.9";
+
+/// Group content keys for the OpenLyrics properties kept on import and written on export
+const PROPERTY_KEYS: &[(&str, &str)] = &[
+    ("copyright", "copyright"),
+    ("ccliNo", "ccli_number"),
+    ("key", "key"),
+    ("tempo", "tempo"),
+    ("released", "released"),
+];
+pub const AUTHORS_KEY: &str = "authors";
+/// Separates authors when several are stored in one value
+pub const AUTHOR_SEPARATOR: &str = ", ";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.tag_name().name() == name)
+}
+
+fn text_of(node: Node) -> String {
+    node.descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+/// Collects the lyrics of a `lines` element, with `br` and `line` elements as line breaks.
+/// Comments and chords are left out, only the text they surround is kept.
+fn lines_text(node: Node, text: &mut String) {
+    for child in node.children() {
+        if child.is_text() {
+            // line breaks in the XML are only formatting
+            text.push_str(
+                &child
+                    .text()
+                    .unwrap_or_default()
+                    .replace(char::is_whitespace, " "),
+            );
+            continue;
+        }
+        match child.tag_name().name() {
+            "br" => text.push('\n'),
+            "comment" => {}
+            "line" => {
+                lines_text(child, text);
+                text.push('\n');
+            }
+            _ => lines_text(child, text),
+        }
+    }
+}
+
+/// Collapses the spaces left around line breaks and between words
+fn normalize_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+/// Reads an OpenLyrics song.
+/// Verses in several languages become language tagged keys such as `text@de`.
+pub fn parse(xml: &str) -> Result<Song, GenericError> {
+    let document = Document::parse(xml).map_err(|_| GenericError::BAD_REQUEST)?;
+    let root = document.root_element();
+    if root.tag_name().name() != "song" {
+        return Err(GenericError::BAD_REQUEST);
+    }
+
+    let mut song = Song::default();
+
+    if let Some(properties) = child(root, "properties") {
+        song.title = child(properties, "titles")
+            .and_then(|titles| child(titles, "title"))
+            .map(text_of)
+            .unwrap_or_default();
+
+        let authors: Vec<String> = child(properties, "authors")
+            .map(|authors| children(authors, "author").map(text_of).collect())
+            .unwrap_or_default();
+        if !authors.is_empty() {
+            song.metadata
+                .insert(String::from(AUTHORS_KEY), authors.join(AUTHOR_SEPARATOR));
+        }
+
+        for (element, key) in PROPERTY_KEYS {
+            if let Some(value) = child(properties, element).map(text_of) {
+                song.metadata.insert(String::from(*key), value);
+            }
+        }
+
+        song.verse_order = child(properties, "verseOrder")
+            .map(text_of)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+    }
+
+    let verses: Vec<Node> = child(root, "lyrics")
+        .map(|lyrics| children(lyrics, "verse").collect())
+        .unwrap_or_default();
+
+    let mut languages: Vec<&str> = verses
+        .iter()
+        .filter_map(|verse| verse.attribute("lang"))
+        .filter(|language| languages::is_language_tag(language))
+        .collect();
+    languages.sort();
+    languages.dedup();
+    let tag_languages = languages.len() > 1;
+
+    for verse in verses {
+        let Some(name) = verse.attribute("name").map(str::to_lowercase) else {
+            continue;
+        };
+        let key = match verse.attribute("lang") {
+            Some(language) if tag_languages && languages::is_language_tag(language) => {
+                format!("{}{}{}", TEXT_KEY, languages::LANGUAGE_SEPARATOR, language)
+            }
+            _ => String::from(TEXT_KEY),
+        };
+
+        // translations of a verse are separate elements with the same name
+        let index = match song.verses.iter().position(|verse| verse.name == name) {
+            Some(index) => index,
+            None => {
+                song.verses.push(SongVerse {
+                    name,
+                    slides: Vec::new(),
+                });
+                song.verses.len() - 1
+            }
+        };
+        let slides = &mut song.verses[index].slides;
+
+        for (part, lines) in children(verse, "lines").enumerate() {
+            let mut text = String::new();
+            lines_text(lines, &mut text);
+            if slides.len() <= part {
+                slides.push(BTreeMap::new());
+            }
+            slides[part].insert(key.clone(), normalize_lines(&text));
+        }
+    }
+
+    Ok(song)
+}
+
+/// Writes a song as OpenLyrics, with each slide as a `lines` element of its verse
+pub fn write(song: &Song) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<song xmlns=\"{}\" version=\"{}\" createdIn=\"streamsys {}\" modifiedIn=\"streamsys {}\" modifiedDate=\"{}\">\n",
+        NAMESPACE,
+        VERSION,
+        env!("CARGO_PKG_VERSION"),
+        env!("CARGO_PKG_VERSION"),
+        Utc::now().format("%Y-%m-%dT%H:%M:%S")
+    ));
+
+    xml.push_str("  <properties>\n");
+    xml.push_str(&format!(
+        "    <titles>\n      <title>{}</title>\n    </titles>\n",
+        escape(&song.title)
+    ));
+    if let Some(authors) = song.metadata.get(AUTHORS_KEY) {
+        xml.push_str("    <authors>\n");
+        for author in authors
+            .split(AUTHOR_SEPARATOR.trim())
+            .map(str::trim)
+            .filter(|author| !author.is_empty())
+        {
+            xml.push_str(&format!("      <author>{}</author>\n", escape(author)));
+        }
+        xml.push_str("    </authors>\n");
+    }
+    for (element, key) in PROPERTY_KEYS {
+        if let Some(value) = song.metadata.get(*key) {
+            xml.push_str(&format!(
+                "    <{}>{}</{}>\n",
+                element,
+                escape(value),
+                element
+            ));
+        }
+    }
+    if !song.verse_order.is_empty() {
+        xml.push_str(&format!(
+            "    <verseOrder>{}</verseOrder>\n",
+            escape(&song.verse_order.join(" "))
+        ));
+    }
+    xml.push_str("  </properties>\n");
+
+    xml.push_str("  <lyrics>\n");
+    for verse in &song.verses {
+        let mut keys: Vec<&String> = verse
+            .slides
+            .iter()
+            .flat_map(|slide| slide.keys())
+            .filter(|key| languages::split_key(key).0 == TEXT_KEY)
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        for key in keys {
+            let language = match languages::split_key(key).1 {
+                Some(language) => format!(" lang=\"{}\"", escape(language)),
+                None => String::new(),
+            };
+            xml.push_str(&format!(
+                "    <verse name=\"{}\"{}>\n",
+                escape(&verse.name),
+                language
+            ));
+            // parts that aren't translated are left out rather than written empty
+            for text in verse.slides.iter().filter_map(|slide| slide.get(key)) {
+                let lines: Vec<String> = text.lines().map(escape).collect();
+                xml.push_str(&format!("      <lines>{}</lines>\n", lines.join("<br/>")));
+            }
+            xml.push_str("    </verse>\n");
+        }
+    }
+    xml.push_str("  </lyrics>\n");
+    xml.push_str("</song>\n");
+
+    xml
+}
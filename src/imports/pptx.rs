@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use roxmltree::{Document, Node};
+use zip::ZipArchive;
+
+use crate::helpers::errors::GenericError;
+
+use super::model::ImportWarning;
+
+pub const BODY_KEY: &str = "body";
+/// Slide content key for speaker notes, which only operators see. It is kept apart from
+/// other notes so that its rule doesn't change what stage outputs show of them.
+pub const NOTES_KEY: &str = "notes.speaker";
+
+const PRESENTATION_FILE: &str = "ppt/presentation.xml";
+/// Most bytes read from a part of the file holding XML
+const MAX_XML_SIZE: u64 = 16 * 1024 * 1024;
+const CORE_PROPERTIES_FILE: &str = "docProps/core.xml";
+const RELATIONSHIPS_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+/// Placeholder types of slide titles
+const TITLE_PLACEHOLDERS: &[&str] = &["title", "ctrTitle"];
+/// Placeholder types repeated on every slide by its layout, which aren't part of the body
+const IGNORED_PLACEHOLDERS: &[&str] = &["dt", "ftr", "hdr", "sldNum", "sldImg"];
+
+pub struct PptxSlide {
+    /// Position of the slide in the presentation, counting from 1
+    pub number: usize,
+    pub title: String,
+    pub body: String,
+    pub notes: String,
+    /// Indexes of the images shown on the slide in `Pptx::images`
+    pub images: Vec<usize>,
+}
+
+pub struct Pptx {
+    pub title: Option<String>,
+    pub slides: Vec<PptxSlide>,
+    /// File names of images and the paths they are extracted to, each only once however many
+    /// slides show it
+    pub images: Vec<(String, PathBuf)>,
+}
+
+/// A relationship from one part of the file to another, by its id
+struct Relationship {
+    kind: String,
+    target: String,
+    external: bool,
+}
+
+/// Reads a part of the file, or `None` if it is missing or larger than `MAX_XML_SIZE`
+fn read_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let entry = zip.by_name(name).ok()?;
+    let mut data = Vec::new();
+    entry.take(MAX_XML_SIZE + 1).read_to_end(&mut data).ok()?;
+    (data.len() as u64 <= MAX_XML_SIZE).then_some(data)
+}
+
+fn read_text_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Option<String> {
+    read_entry(zip, name).and_then(|data| String::from_utf8(data).ok())
+}
+
+/// Resolves a relationship target against the directory of the part it is from
+fn resolve_target(part: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => part.split('/').collect(),
+    };
+    // the part itself isn't a directory
+    segments.pop();
+    for segment in target.trim_start_matches('/').split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            }
+            "." | "" => {}
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Reads the relationships of a part, which are kept in a `_rels` directory next to it
+fn relationships<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    part: &str,
+) -> HashMap<String, Relationship> {
+    let (directory, file_name) = part.rsplit_once('/').unwrap_or(("", part));
+    let Some(xml) = read_text_entry(zip, &format!("{}/_rels/{}.rels", directory, file_name)) else {
+        return HashMap::new();
+    };
+    let Ok(document) = Document::parse(&xml) else {
+        return HashMap::new();
+    };
+
+    let relationships = document
+        .descendants()
+        .filter(|node| node.has_tag_name("Relationship"))
+        .filter_map(|node| {
+            let id = node.attribute("Id")?;
+            let target = node.attribute("Target")?;
+            let external = node.attribute("TargetMode") == Some("External");
+            Some((
+                id.to_owned(),
+                Relationship {
+                    kind: node
+                        .attribute("Type")
+                        .and_then(|kind| kind.rsplit('/').next())
+                        .unwrap_or_default()
+                        .to_owned(),
+                    target: match external {
+                        true => target.to_owned(),
+                        false => resolve_target(part, target),
+                    },
+                    external,
+                },
+            ))
+        })
+        .collect();
+
+    relationships
+}
+
+/// Text of a text body, with a line per paragraph
+fn text_body(node: Node) -> String {
+    let paragraphs: Vec<String> = node
+        .descendants()
+        .filter(|node| node.has_tag_name("p"))
+        .filter(|node| {
+            node.parent_element()
+                .is_some_and(|parent| parent.has_tag_name("txBody"))
+        })
+        .map(|paragraph| {
+            let mut text = String::new();
+            for node in paragraph.descendants() {
+                if node.has_tag_name("t") {
+                    text.push_str(node.text().unwrap_or_default());
+                } else if node.has_tag_name("br") {
+                    text.push('\n');
+                }
+            }
+            text.trim_end().to_owned()
+        })
+        .collect();
+
+    paragraphs.join("\n").trim().to_owned()
+}
+
+fn placeholder_type<'a>(shape: Node<'a, '_>) -> Option<&'a str> {
+    let placeholder = shape.descendants().find(|node| node.has_tag_name("ph"))?;
+    // placeholders without a type are body text
+    Some(placeholder.attribute("type").unwrap_or("body"))
+}
+
+/// Reads the title and body of a slide from its shapes, in the order they are drawn
+fn slide_text(document: &Document) -> (String, String) {
+    let mut titles = Vec::new();
+    let mut bodies = Vec::new();
+
+    let shapes = document
+        .descendants()
+        .filter(|node| node.has_tag_name("sp"));
+    for shape in shapes {
+        let Some(text_body_node) = shape.children().find(|node| node.has_tag_name("txBody")) else {
+            continue;
+        };
+        let text = text_body(text_body_node);
+        if text.is_empty() {
+            continue;
+        }
+        match placeholder_type(shape) {
+            Some(kind) if TITLE_PLACEHOLDERS.contains(&kind) => titles.push(text),
+            Some(kind) if IGNORED_PLACEHOLDERS.contains(&kind) => {}
+            _ => bodies.push(text),
+        }
+    }
+
+    (titles.join("\n"), bodies.join("\n"))
+}
+
+/// Reads the speaker notes of a notes slide, which are in its body placeholder
+fn notes_text(document: &Document) -> String {
+    let notes: Vec<String> = document
+        .descendants()
+        .filter(|node| node.has_tag_name("sp"))
+        .filter(|shape| placeholder_type(*shape) == Some("body"))
+        .filter_map(|shape| shape.children().find(|node| node.has_tag_name("txBody")))
+        .map(text_body)
+        .filter(|text| !text.is_empty())
+        .collect();
+
+    notes.join("\n")
+}
+
+/// Reads the slides of a PowerPoint presentation in order, with their titles, body text,
+/// speaker notes and images. Hidden slides are left out. Images are extracted into a directory,
+/// leaving out those that would take the images past `max_size` altogether.
+pub fn read(
+    path: &Path,
+    directory: &Path,
+    max_size: u64,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Pptx, GenericError> {
+    let file = File::open(path).map_err(|_| GenericError::BAD_REQUEST)?;
+    let mut zip = ZipArchive::new(file).map_err(|_| GenericError::BAD_REQUEST)?;
+    std::fs::create_dir_all(directory).expect("Failed to create import directory");
+
+    let presentation_xml =
+        read_text_entry(&mut zip, PRESENTATION_FILE).ok_or(GenericError::BAD_REQUEST)?;
+    let presentation = Document::parse(&presentation_xml).map_err(|_| GenericError::BAD_REQUEST)?;
+    let presentation_relationships = relationships(&mut zip, PRESENTATION_FILE);
+
+    let title = read_text_entry(&mut zip, CORE_PROPERTIES_FILE).and_then(|xml| {
+        let document = Document::parse(&xml).ok()?;
+        let title = document
+            .descendants()
+            .find(|node| node.has_tag_name("title"))?
+            .text()?
+            .trim()
+            .to_owned();
+        Some(title).filter(|title| !title.is_empty())
+    });
+
+    let slide_parts: Vec<String> = presentation
+        .descendants()
+        .filter(|node| node.has_tag_name("sldId"))
+        .filter_map(|node| node.attribute((RELATIONSHIPS_NAMESPACE, "id")))
+        .filter_map(|id| presentation_relationships.get(id))
+        .map(|relationship| relationship.target.clone())
+        .collect();
+
+    let mut pptx = Pptx {
+        title,
+        slides: Vec::new(),
+        images: Vec::new(),
+    };
+    // indexes in `pptx.images` by the part they are read from
+    let mut image_indexes: HashMap<String, usize> = HashMap::new();
+    let mut images_size: u64 = 0;
+
+    for (index, part) in slide_parts.iter().enumerate() {
+        let number = index + 1;
+        let source = format!("slide {}", number);
+
+        let Some(document_xml) = read_text_entry(&mut zip, part) else {
+            warnings.push(ImportWarning {
+                source,
+                message: String::from("Slide is missing from the file or too large, slide skipped"),
+            });
+            continue;
+        };
+        let Ok(document) = Document::parse(&document_xml) else {
+            warnings.push(ImportWarning {
+                source,
+                message: String::from("Slide could not be read, slide skipped"),
+            });
+            continue;
+        };
+        if document.root_element().attribute("show") == Some("0") {
+            warnings.push(ImportWarning {
+                source,
+                message: String::from("Slide is hidden, slide skipped"),
+            });
+            continue;
+        }
+
+        let (title, body) = slide_text(&document);
+        let slide_relationships = relationships(&mut zip, part);
+
+        let notes = slide_relationships
+            .values()
+            .find(|relationship| relationship.kind == "notesSlide" && !relationship.external)
+            .and_then(|relationship| read_text_entry(&mut zip, &relationship.target))
+            .and_then(|xml| {
+                Document::parse(&xml)
+                    .ok()
+                    .map(|document| notes_text(&document))
+            })
+            .unwrap_or_default();
+
+        let mut images = Vec::new();
+        let embeds = document
+            .descendants()
+            .filter(|node| node.has_tag_name("blip"))
+            .filter_map(|node| node.attribute((RELATIONSHIPS_NAMESPACE, "embed")));
+        for embed in embeds {
+            let Some(relationship) = slide_relationships.get(embed) else {
+                continue;
+            };
+            if relationship.external {
+                warnings.push(ImportWarning {
+                    source: source.clone(),
+                    message: format!(
+                        "Image \"{}\" is linked rather than embedded, image left out",
+                        relationship.target
+                    ),
+                });
+                continue;
+            }
+
+            let image_index = match image_indexes.get(&relationship.target) {
+                Some(image_index) => *image_index,
+                None => {
+                    let Ok(entry) = zip.by_name(&relationship.target) else {
+                        warnings.push(ImportWarning {
+                            source: source.clone(),
+                            message: format!(
+                                "Image \"{}\" is missing from the file, image left out",
+                                relationship.target
+                            ),
+                        });
+                        continue;
+                    };
+
+                    let image_path = directory.join(pptx.images.len().to_string());
+                    let mut image_file =
+                        File::create(&image_path).expect("Failed to create image file");
+                    let size_left = max_size.saturating_sub(images_size);
+                    let copied = io::copy(&mut entry.take(size_left + 1), &mut image_file);
+                    let message = match copied {
+                        Ok(size) if size <= size_left => {
+                            images_size += size;
+                            None
+                        }
+                        Ok(_) => Some("would take the images past the size limit"),
+                        Err(_) => Some("could not be extracted"),
+                    };
+                    if let Some(message) = message {
+                        let _ = std::fs::remove_file(&image_path);
+                        warnings.push(ImportWarning {
+                            source: source.clone(),
+                            message: format!(
+                                "Image \"{}\" {}, image left out",
+                                relationship.target, message
+                            ),
+                        });
+                        continue;
+                    }
+
+                    let file_name = relationship
+                        .target
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or(&relationship.target)
+                        .to_owned();
+                    pptx.images.push((file_name, image_path));
+                    image_indexes.insert(relationship.target.clone(), pptx.images.len() - 1);
+                    pptx.images.len() - 1
+                }
+            };
+            if !images.contains(&image_index) {
+                images.push(image_index);
+            }
+        }
+
+        pptx.slides.push(PptxSlide {
+            number,
+            title,
+            body,
+            notes,
+            images,
+        });
+    }
+
+    Ok(pptx)
+}
@@ -0,0 +1,433 @@
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use roxmltree::{Document, Node};
+
+use crate::helpers::errors::GenericError;
+
+use super::{
+    model::ImportWarning,
+    songs::{Song, SongVerse, VerseNames, LABEL_KEY, TEXT_KEY},
+    text::{self, HEADING_PREFIX},
+};
+
+/// Slide content key for the label ProPresenter gives a single slide
+pub const SLIDE_LABEL_KEY: &str = "slide_label";
+/// Slide content key for the colour of the ProPresenter group a slide is in, as `#rrggbb`
+pub const GROUP_COLOR_KEY: &str = "group_color";
+
+/// Group content keys for the song details of a document, by attribute
+const DOCUMENT_ATTRIBUTES: &[(&str, &str)] = &[
+    ("CCLIArtistCredits", "artist"),
+    ("CCLIAuthor", "authors"),
+    ("CCLIPublisher", "publisher"),
+    ("CCLICopyrightYear", "copyright_year"),
+    ("CCLISongNumber", "ccli_number"),
+    ("category", "category"),
+];
+/// Elements that refer to media files through their `source` attribute
+const MEDIA_ELEMENTS: &[&str] = &["RVImageElement", "RVVideoElement", "RVAudioElement"];
+/// RTF destinations that hold no text of the slide
+const RTF_SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "info",
+    "pict",
+    "header",
+    "footer",
+    "listtable",
+    "listoverridetable",
+    "expandedcolortbl",
+];
+
+/// Media file a slide refers to, to be found in the media library by file name
+pub struct SlideMedia {
+    pub verse: usize,
+    pub slide: usize,
+    /// File name without its directory
+    pub file_name: String,
+}
+
+pub struct Presentation {
+    pub song: Song,
+    pub media: Vec<SlideMedia>,
+}
+
+/// Characters for the Windows-1252 bytes that differ from Latin-1
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80 => '€',
+        0x85 => '…',
+        0x91 => '‘',
+        0x92 => '’',
+        0x93 => '“',
+        0x94 => '”',
+        0x95 => '•',
+        0x96 => '–',
+        0x97 => '—',
+        0x99 => '™',
+        byte => char::from(byte),
+    }
+}
+
+/// Extracts the text of an RTF document, leaving out formatting, fonts and pictures
+fn rtf_text(rtf: &str) -> String {
+    let bytes = rtf.as_bytes();
+    let mut text = String::new();
+    // whether each open group is skipped
+    let mut skipped = vec![false];
+    // characters standing in for the latest `\u` character, which are left out
+    let mut unicode_fallback = 1;
+    let mut pending_fallback = 0;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let skipping = skipped.last().copied().unwrap_or(false);
+        match bytes[i] {
+            b'{' => {
+                skipped.push(skipping);
+                i += 1;
+            }
+            b'}' => {
+                skipped.pop();
+                i += 1;
+            }
+            b'\r' | b'\n' => i += 1,
+            b'\\' => {
+                i += 1;
+                let Some(&next) = bytes.get(i) else {
+                    break;
+                };
+
+                if !next.is_ascii_alphabetic() {
+                    // symbols may be any character, which may take several bytes
+                    i += rtf[i..].chars().next().map_or(1, char::len_utf8);
+                    match next {
+                        b'\'' => {
+                            // only the hex digits actually there are read
+                            let digits = bytes[i..]
+                                .iter()
+                                .take(2)
+                                .take_while(|byte| byte.is_ascii_hexdigit())
+                                .count();
+                            let byte = match digits {
+                                2 => u8::from_str_radix(&rtf[i..i + 2], 16).ok(),
+                                _ => None,
+                            };
+                            i += digits;
+                            if pending_fallback > 0 {
+                                pending_fallback -= 1;
+                            } else if let (false, Some(byte)) = (skipping, byte) {
+                                text.push(windows_1252(byte));
+                            }
+                        }
+                        b'*' => {
+                            if let Some(group) = skipped.last_mut() {
+                                *group = true;
+                            }
+                        }
+                        b'\\' | b'{' | b'}' if !skipping => text.push(char::from(next)),
+                        b'~' if !skipping => text.push(' '),
+                        b'\r' | b'\n' if !skipping => text.push('\n'),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                let start = i;
+                while bytes.get(i).is_some_and(u8::is_ascii_alphabetic) {
+                    i += 1;
+                }
+                let word = &rtf[start..i];
+                let parameter_start = i;
+                if bytes.get(i) == Some(&b'-') {
+                    i += 1;
+                }
+                while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+                    i += 1;
+                }
+                let parameter: Option<i32> = rtf[parameter_start..i].parse().ok();
+                if bytes.get(i) == Some(&b' ') {
+                    i += 1;
+                }
+
+                if skipping {
+                    continue;
+                }
+                match word {
+                    "par" | "line" => text.push('\n'),
+                    "tab" => text.push('\t'),
+                    "uc" => unicode_fallback = parameter.unwrap_or(1),
+                    "u" => {
+                        // code points past 32767 are written as negative numbers
+                        let code = parameter.map(|code| if code < 0 { code + 65536 } else { code });
+                        if let Some(character) = code.and_then(|code| char::from_u32(code as u32)) {
+                            text.push(character);
+                        }
+                        pending_fallback = unicode_fallback;
+                    }
+                    word if RTF_SKIPPED_DESTINATIONS.contains(&word) => {
+                        if let Some(group) = skipped.last_mut() {
+                            *group = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                let character = rtf[i..].chars().next().unwrap_or_default();
+                i += character.len_utf8();
+                if pending_fallback > 0 {
+                    pending_fallback -= 1;
+                } else if !skipping {
+                    text.push(character);
+                }
+            }
+        }
+    }
+
+    text
+}
+
+fn decode(encoded: &str) -> Option<String> {
+    let bytes = STANDARD
+        .decode(encoded.split_whitespace().collect::<String>())
+        .ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn ivar<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.attribute("rvXMLIvarName") == Some(name))
+}
+
+/// Text of a text element, from its plain text when it has it and otherwise from its RTF
+fn element_text(element: Node) -> Option<String> {
+    let plain_text = ivar(element, "PlainText")
+        .and_then(|node| node.text())
+        .and_then(decode);
+    let text = match plain_text {
+        Some(text) => text,
+        None => {
+            let rtf = ivar(element, "RTFData")
+                .and_then(|node| node.text())
+                .or(element.attribute("RTFData"))
+                .and_then(decode)?;
+            rtf_text(&rtf)
+        }
+    };
+
+    let text = text
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(text.trim().to_owned())
+}
+
+/// Converts a colour of space separated red, green, blue and alpha fractions to `#rrggbb`
+fn color(value: &str) -> Option<String> {
+    let components: Vec<f64> = value
+        .split_whitespace()
+        .map(|component| component.parse().ok())
+        .collect::<Option<_>>()?;
+    if components.len() < 3 {
+        return None;
+    }
+    Some(format!(
+        "#{}",
+        components[..3]
+            .iter()
+            .map(|component| format!("{:02x}", (component.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect::<String>()
+    ))
+}
+
+/// File name of a media source, which is usually a `file://` URL from the computer it was made on
+fn source_file_name(source: &str) -> Option<String> {
+    let decoded = decode_percent(source);
+    let file_name = decoded
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|file_name| !file_name.is_empty())?;
+    Some(file_name.to_owned())
+}
+
+fn decode_percent(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = match (bytes[i], text.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) => u8::from_str_radix(hex, 16).ok(),
+            _ => None,
+        };
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reads a ProPresenter 6 document, with a verse per group named after its label
+/// and the verse order of its first arrangement.
+/// The media its slides refer to is listed to be looked up in the media library.
+pub fn parse(
+    xml: &str,
+    file_name: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> Result<Presentation, GenericError> {
+    let document = Document::parse(xml).map_err(|_| GenericError::BAD_REQUEST)?;
+    let root = document.root_element();
+    if root.tag_name().name() != "RVPresentationDocument" {
+        return Err(GenericError::BAD_REQUEST);
+    }
+
+    let title = match root.attribute("CCLISongTitle").map(str::trim) {
+        Some(title) if !title.is_empty() => title.to_owned(),
+        _ => file_name
+            .rsplit_once('.')
+            .map(|(name, _)| name)
+            .unwrap_or(file_name)
+            .to_owned(),
+    };
+    let mut song = Song {
+        title,
+        ..Default::default()
+    };
+    for (attribute, key) in DOCUMENT_ATTRIBUTES {
+        if let Some(value) = root.attribute(*attribute).map(str::trim) {
+            if !value.is_empty() {
+                song.metadata.insert(String::from(*key), value.to_owned());
+            }
+        }
+    }
+
+    let mut media = Vec::new();
+    let mut names = VerseNames::default();
+    // verse names by group id, for arrangements
+    let mut group_names: BTreeMap<String, String> = BTreeMap::new();
+
+    let groups = root
+        .descendants()
+        .filter(|node| node.has_tag_name("RVSlideGrouping"));
+    for (group_index, group) in groups.enumerate() {
+        let label = group
+            .attribute("name")
+            .unwrap_or_default()
+            .trim()
+            .to_owned();
+        let group_color = group.attribute("color").and_then(color);
+        let name = match text::section_label(&label) {
+            Some((prefix, number)) => names.next(prefix, number),
+            None => names.next(HEADING_PREFIX, None),
+        };
+        let source = format!(
+            "{} / {}",
+            file_name,
+            match label.is_empty() {
+                true => format!("group {}", group_index + 1),
+                false => label.clone(),
+            }
+        );
+
+        let mut slides = Vec::new();
+        let group_slides = group
+            .descendants()
+            .filter(|node| node.has_tag_name("RVDisplaySlide"));
+        for slide in group_slides {
+            if slide.attribute("enabled") == Some("false") {
+                continue;
+            }
+
+            let texts: Vec<String> = slide
+                .descendants()
+                .filter(|node| node.has_tag_name("RVTextElement"))
+                .filter_map(|element| {
+                    let text = element_text(element);
+                    if text.is_none() {
+                        warnings.push(ImportWarning {
+                            source: source.clone(),
+                            message: String::from("Text could not be decoded, text left out"),
+                        });
+                    }
+                    text
+                })
+                .filter(|text| !text.is_empty())
+                .collect();
+
+            let mut content = BTreeMap::new();
+            content.insert(String::from(TEXT_KEY), texts.join("\n"));
+            if !label.is_empty() {
+                content.insert(String::from(LABEL_KEY), label.clone());
+            }
+            if let Some(slide_label) = slide.attribute("label").map(str::trim) {
+                if !slide_label.is_empty() {
+                    content.insert(String::from(SLIDE_LABEL_KEY), slide_label.to_owned());
+                }
+            }
+            if let Some(group_color) = &group_color {
+                content.insert(String::from(GROUP_COLOR_KEY), group_color.clone());
+            }
+
+            let sources = slide
+                .descendants()
+                .filter(|node| MEDIA_ELEMENTS.contains(&node.tag_name().name()))
+                .filter_map(|node| node.attribute("source"))
+                .filter_map(source_file_name);
+            for file_name in sources {
+                media.push(SlideMedia {
+                    verse: song.verses.len(),
+                    slide: slides.len(),
+                    file_name,
+                });
+            }
+
+            slides.push(content);
+        }
+
+        if slides.is_empty() {
+            continue;
+        }
+        if let Some(id) = group.attribute("uuid") {
+            group_names.insert(id.to_owned(), name.clone());
+        }
+        song.verses.push(SongVerse { name, slides });
+    }
+
+    if let Some(arrangement) = root
+        .descendants()
+        .find(|node| node.has_tag_name("RVSongArrangement"))
+    {
+        let group_ids: Vec<&str> = ivar(arrangement, "groupIDs")
+            .map(|array| {
+                array
+                    .children()
+                    .filter(|node| node.is_element())
+                    .filter_map(|node| node.text())
+                    .collect()
+            })
+            .unwrap_or_default();
+        for group_id in group_ids {
+            match group_names.get(group_id.trim()) {
+                Some(name) => song.verse_order.push(name.clone()),
+                None => warnings.push(ImportWarning {
+                    source: file_name.to_owned(),
+                    message: String::from("Arrangement refers to a group that doesn't exist"),
+                }),
+            }
+        }
+    }
+
+    Ok(Presentation { song, media })
+}
@@ -0,0 +1,856 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use axum::body::Bytes;
+use futures::{Stream, StreamExt};
+use rusqlite::{named_params, OptionalExtension};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use uuid::Uuid;
+
+use crate::{
+    bundles::{
+        model::{
+            BundleContent, BundleDeck, BundleDeckSection, BundleDeckSlide, BundleMedia,
+            BundleSlide, BundleSlideGroup, DeckBundle,
+        },
+        service::BundlesService,
+    },
+    config::file::AppConfig,
+    database::Database,
+    helpers::errors::GenericError,
+    jobs::service::JobsService,
+    media::service::MediaService,
+};
+
+use super::{
+    chordpro::{self, ChordProImportOptions},
+    csv::{self, CsvImportError, CsvImportOptions, CsvImportPreview, CsvSlide},
+    images::{self, ImageImportOptions, DURATION_KEY},
+    model::{ImportOptions, ImportReport, ImportWarning, BACKGROUND_KEY, MEDIA_KEY},
+    openlp, openlyrics,
+    pptx::{self, BODY_KEY, NOTES_KEY},
+    propresenter,
+    songs::{self, Song, SongVerse, TITLE_KEY, VERSE_ORDER_KEY},
+    text::{self, TextImportOptions},
+};
+
+/// Number of slides shown when previewing an import
+const PREVIEW_SLIDES: usize = 20;
+/// Name of the deck made by OpenLP imports, which has a section for every song
+const OPENLP_DECK_NAME: &str = "OpenLP";
+/// Name of the deck made by ProPresenter imports of several documents
+const PROPRESENTER_DECK_NAME: &str = "ProPresenter";
+/// Name of the deck made by image imports with nothing better to name it by
+const IMAGES_DECK_NAME: &str = "Slideshow";
+
+pub struct ImportsService {
+    config: AppConfig,
+    db: Database,
+    bundles_service: BundlesService,
+    media_service: MediaService,
+}
+
+impl ImportsService {
+    pub fn new(database: &Database, config: &AppConfig, jobs_service: &JobsService) -> Self {
+        Self {
+            config: config.clone(),
+            db: database.clone(),
+            bundles_service: BundlesService::new(database, config),
+            media_service: MediaService::new(database, config, jobs_service),
+        }
+    }
+
+    /// Saves an uploaded file to a temporary file, failing once it grows past the upload size limit.
+    /// The caller removes the file when done with it.
+    pub async fn save_upload<S, E>(&self, mut stream: S) -> Result<PathBuf, GenericError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+    {
+        let temp_path = self.media_service.temp_path().await;
+        let mut file = fs::File::create(&temp_path)
+            .await
+            .expect("Failed to create import file");
+        let mut size: u64 = 0;
+
+        let mut result = Ok(());
+        while let Some(chunk) = stream.next().await {
+            let Ok(chunk) = chunk else {
+                result = Err(GenericError::BAD_REQUEST);
+                break;
+            };
+            size += chunk.len() as u64;
+            if size > self.config.media_max_upload_size {
+                result = Err(GenericError::PAYLOAD_TOO_LARGE);
+                break;
+            }
+            file.write_all(&chunk)
+                .await
+                .expect("Failed to write import file");
+        }
+        file.flush().await.expect("Failed to write import file");
+        drop(file);
+
+        match result {
+            Ok(()) => Ok(temp_path),
+            Err(err) => {
+                let _ = fs::remove_file(&temp_path).await;
+                Err(err)
+            }
+        }
+    }
+
+    /// Turns each row of a CSV file into a slide, saving them all in one transaction
+    /// if requested and nothing is wrong
+    pub async fn import_csv(
+        &self,
+        path: &Path,
+        options: &CsvImportOptions,
+    ) -> Result<CsvImportPreview, GenericError> {
+        let csv_path = path.to_owned();
+        let delimiter = options.delimiter;
+        let table = tokio::task::spawn_blocking(move || csv::read_csv(&csv_path, delimiter))
+            .await
+            .unwrap()?;
+
+        let mut db = self.db.get();
+
+        if let Some(slide_group_id) = options.slide_group_id {
+            let exists = db
+                .prepare_cached("SELECT 1 FROM \"slide_groups\" WHERE \"id\" = :id;")
+                .unwrap()
+                .exists(named_params! {":id": slide_group_id})
+                .expect("Error occurred checking slide group in database");
+            if !exists {
+                return Err(GenericError::BAD_REQUEST);
+            }
+        }
+        if let Some(slide_type_id) = options.slide_type_id {
+            let exists = db
+                .prepare_cached("SELECT 1 FROM \"slide_types\" WHERE \"id\" = :id;")
+                .unwrap()
+                .exists(named_params! {":id": slide_type_id})
+                .expect("Error occurred checking slide type in database");
+            if !exists {
+                return Err(GenericError::BAD_REQUEST);
+            }
+        }
+
+        let mut errors = Vec::new();
+        let column_index = |column: &str| table.columns.iter().position(|name| name == column);
+
+        let name_index = column_index(&options.name_column);
+        if name_index.is_none() {
+            errors.push(CsvImportError {
+                row: None,
+                message: format!("Name column \"{}\" is not in the file", options.name_column),
+            });
+        }
+        let mut content_columns = Vec::new();
+        for (column, key) in &options.columns {
+            match column_index(column) {
+                Some(_) if key.is_empty() => errors.push(CsvImportError {
+                    row: None,
+                    message: format!("Column \"{}\" is mapped to an empty key", column),
+                }),
+                Some(index) => content_columns.push((index, key)),
+                None => errors.push(CsvImportError {
+                    row: None,
+                    message: format!("Column \"{}\" is not in the file", column),
+                }),
+            }
+        }
+
+        let mut slides = Vec::new();
+        let mut names = HashSet::new();
+        if let Some(name_index) = name_index {
+            for (index, values) in table.rows.iter().enumerate() {
+                let row = index + 2;
+                let name = values[name_index].trim().to_owned();
+
+                if name.is_empty() {
+                    errors.push(CsvImportError {
+                        row: Some(row),
+                        message: String::from("Name is empty"),
+                    });
+                } else if !names.insert(name.clone()) {
+                    errors.push(CsvImportError {
+                        row: Some(row),
+                        message: format!("Name \"{}\" is used by an earlier row", name),
+                    });
+                } else {
+                    let exists = db
+                        .prepare_cached("SELECT 1 FROM \"slides\" WHERE \"slide_group_id\" IS :slide_group_id AND \"name\" = :name;")
+                        .unwrap()
+                        .exists(named_params! {
+                            ":slide_group_id": options.slide_group_id,
+                            ":name": name,
+                        })
+                        .expect("Error occurred checking slide names in database");
+                    if exists {
+                        errors.push(CsvImportError {
+                            row: Some(row),
+                            message: format!(
+                                "A slide named \"{}\" already exists in the group",
+                                name
+                            ),
+                        });
+                    }
+                }
+
+                // empty cells leave the key unset so lower layers still apply
+                let content: BTreeMap<String, String> = content_columns
+                    .iter()
+                    .filter(|(index, _)| !values[*index].is_empty())
+                    .map(|(index, key)| ((*key).clone(), values[*index].clone()))
+                    .collect();
+
+                slides.push(CsvSlide { row, name, content });
+            }
+        }
+
+        let committed = options.commit && errors.is_empty();
+        if committed {
+            let tx = db.transaction().unwrap();
+
+            for slide in &slides {
+                let slide_id = Uuid::new_v4();
+                tx.prepare_cached("INSERT INTO \"slides\" (\"id\", \"slide_group_id\", \"slide_type_id\", \"name\") VALUES (:id, :slide_group_id, :slide_type_id, :name);")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": slide_id,
+                        ":slide_group_id": options.slide_group_id,
+                        ":slide_type_id": options.slide_type_id,
+                        ":name": slide.name,
+                    })
+                    .expect("Error occurred adding slide");
+
+                for (key, value) in &slide.content {
+                    tx.prepare_cached("INSERT INTO \"slide_content\" (\"id\", \"slide_id\", \"key\", \"value\") VALUES (:id, :slide_id, :key, :value);")
+                        .unwrap()
+                        .execute(named_params! {
+                            ":id": Uuid::new_v4(),
+                            ":slide_id": slide_id,
+                            ":key": key,
+                            ":value": value,
+                        })
+                        .expect("Error occurred adding slide content");
+                }
+            }
+
+            tx.commit().expect("Error occurred committing CSV import");
+        }
+
+        let row_count = table.rows.len();
+        slides.truncate(PREVIEW_SLIDES);
+
+        Ok(CsvImportPreview {
+            columns: table.columns,
+            row_count,
+            slides,
+            errors,
+            committed,
+        })
+    }
+
+    /// Describes a file to be added to the media library with an import
+    async fn read_media(path: &Path, name: &str) -> BundleMedia {
+        let mut file = fs::File::open(path)
+            .await
+            .expect("Failed to open media file");
+        let mut hasher = Sha256::new();
+        let mut size: i64 = 0;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .await
+                .expect("Failed to read media file");
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as i64;
+        }
+
+        let mime_type = mime_guess::from_path(name)
+            .first_or_octet_stream()
+            .to_string();
+        let dimensions = if mime_type.starts_with("image/") {
+            let image_path = path.to_owned();
+            tokio::task::spawn_blocking(move || {
+                image::ImageReader::open(image_path)
+                    .ok()?
+                    .with_guessed_format()
+                    .ok()?
+                    .into_dimensions()
+                    .ok()
+            })
+            .await
+            .unwrap()
+        } else {
+            None
+        };
+
+        BundleMedia {
+            id: Uuid::new_v4(),
+            name: name.to_owned(),
+            mime_type,
+            size,
+            hash: hex::encode(hasher.finalize()),
+            width: dimensions.map(|(width, _)| width as i64),
+            height: dimensions.map(|(_, height)| height as i64),
+        }
+    }
+
+    /// Saves an imported deck along with the files of the media it lists, given in the
+    /// same order. Files are copied into the library only when saving.
+    async fn finish_with_media(
+        &self,
+        bundle: DeckBundle,
+        media_files: &[PathBuf],
+        warnings: Vec<ImportWarning>,
+        options: &ImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        if options.commit {
+            for (media, source_path) in bundle.media.iter().zip(media_files) {
+                let path = self.media_service.file_path(&media.hash);
+                if fs::try_exists(&path).await.unwrap_or(false) {
+                    continue;
+                }
+                let temp_path = self.media_service.temp_path().await;
+                fs::copy(source_path, &temp_path)
+                    .await
+                    .expect("Failed to copy media file");
+                fs::create_dir_all(path.parent().unwrap())
+                    .await
+                    .expect("Failed to create media directory");
+                fs::rename(&temp_path, &path)
+                    .await
+                    .expect("Failed to move media file into place");
+            }
+        }
+
+        self.finish(bundle, warnings, options, user_id).await
+    }
+
+    /// Saves an imported deck if requested, otherwise only reports what would be saved
+    async fn finish(
+        &self,
+        bundle: DeckBundle,
+        warnings: Vec<ImportWarning>,
+        options: &ImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let result = match options.commit {
+            true => {
+                let (result, added_media) = self
+                    .bundles_service
+                    .import_deck(bundle.clone(), options.conflict, user_id)
+                    .await?;
+                for media_id in added_media {
+                    self.media_service.queue_variants(media_id, user_id);
+                }
+                Some(result)
+            }
+            false => None,
+        };
+
+        Ok(ImportReport {
+            bundle,
+            warnings,
+            result,
+        })
+    }
+
+    /// Imports an OpenLyrics song as a group with a slide per verse part
+    /// and a deck section following its verse order
+    pub async fn import_openlyrics(
+        &self,
+        path: &Path,
+        options: &ImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let xml = fs::read_to_string(path)
+            .await
+            .map_err(|_| GenericError::BAD_REQUEST)?;
+        let song = openlyrics::parse(&xml)?;
+
+        let deck_name = options.deck_name.clone().unwrap_or(song.title.clone());
+        let mut warnings = Vec::new();
+        let bundle = songs::songs_deck(vec![song], &deck_name, &mut warnings);
+
+        self.finish(bundle, warnings, options, user_id).await
+    }
+
+    /// Reads a slide group back into a song, with verses in the order they are first sung
+    pub fn song(&self, slide_group_id: Uuid) -> Option<Song> {
+        let db = self.db.get();
+
+        let group_name: String = db
+            .prepare_cached("SELECT \"name\" FROM \"slide_groups\" WHERE \"id\" = :id;")
+            .unwrap()
+            .query_row(named_params! {":id": slide_group_id}, |row| row.get("name"))
+            .optional()
+            .expect("Error occurred getting slide group from database")?;
+
+        let mut metadata: BTreeMap<String, String> = db
+            .prepare_cached("SELECT \"key\", \"value\" FROM \"slide_group_content\" WHERE \"slide_group_id\" = :slide_group_id AND \"value\" IS NOT NULL;")
+            .unwrap()
+            .query_map(named_params! {":slide_group_id": slide_group_id}, |row| {
+                Ok((row.get("key")?, row.get("value")?))
+            })
+            .expect("Error occurred getting slide group content from database")
+            .map(|entry| entry.unwrap())
+            .collect();
+
+        let slides: Vec<(Uuid, String)> = db
+            .prepare_cached("SELECT \"id\", \"name\" FROM \"slides\" WHERE \"slide_group_id\" = :slide_group_id ORDER BY \"name\";")
+            .unwrap()
+            .query_map(named_params! {":slide_group_id": slide_group_id}, |row| {
+                Ok((row.get("id")?, row.get("name")?))
+            })
+            .expect("Error occurred getting slides from database")
+            .map(|slide| slide.unwrap())
+            .collect();
+
+        let title = metadata.remove(TITLE_KEY).unwrap_or(group_name);
+        let verse_order: Vec<String> = metadata
+            .remove(VERSE_ORDER_KEY)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+
+        let mut parts = Vec::new();
+        for (slide_id, slide_name) in slides {
+            let content: BTreeMap<String, String> = db
+                .prepare_cached("SELECT \"key\", \"value\" FROM \"slide_content\" WHERE \"slide_id\" = :slide_id AND \"value\" IS NOT NULL;")
+                .unwrap()
+                .query_map(named_params! {":slide_id": slide_id}, |row| {
+                    Ok((row.get("key")?, row.get("value")?))
+                })
+                .expect("Error occurred getting slide content from database")
+                .map(|entry| entry.unwrap())
+                .collect();
+
+            let (verse_name, part) = songs::split_slide_name(&slide_name);
+            parts.push((verse_name.to_owned(), part, content));
+        }
+        parts.sort_by_key(|(_, part, _)| *part);
+
+        let mut verses: Vec<SongVerse> = Vec::new();
+        for (verse_name, _, content) in parts {
+            match verses.iter_mut().find(|verse| verse.name == verse_name) {
+                Some(verse) => verse.slides.push(content),
+                None => verses.push(SongVerse {
+                    name: verse_name,
+                    slides: vec![content],
+                }),
+            }
+        }
+        verses.sort_by_key(|verse| verse.name.clone());
+        verses.sort_by_key(|verse| {
+            verse_order
+                .iter()
+                .position(|name| *name == verse.name)
+                .unwrap_or(verse_order.len())
+        });
+
+        Some(Song {
+            title,
+            metadata,
+            verses,
+            verse_order,
+        })
+    }
+
+    /// Imports a ChordPro song like an OpenLyrics song, with sections named like
+    /// OpenLyrics verses (`v1`, `c1`, `b1`)
+    pub async fn import_chordpro(
+        &self,
+        path: &Path,
+        options: &ChordProImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let text = fs::read_to_string(path)
+            .await
+            .map_err(|_| GenericError::BAD_REQUEST)?;
+
+        let mut warnings = Vec::new();
+        let song = chordpro::parse(&text, options.chords, &mut warnings);
+
+        let deck_name = options
+            .import
+            .deck_name
+            .clone()
+            .unwrap_or(song.title.clone());
+        let bundle = songs::songs_deck(vec![song], &deck_name, &mut warnings);
+
+        self.finish(bundle, warnings, &options.import, user_id)
+            .await
+    }
+
+    /// Imports plain text or Markdown as a group with a slide per paragraph
+    /// and a deck section following its section labels
+    pub async fn import_text(
+        &self,
+        path: &Path,
+        options: &TextImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let text = fs::read_to_string(path)
+            .await
+            .map_err(|_| GenericError::BAD_REQUEST)?;
+
+        let mut warnings = Vec::new();
+        let song = text::parse(&text, options, &mut warnings);
+
+        let deck_name = options
+            .import
+            .deck_name
+            .clone()
+            .unwrap_or(song.title.clone());
+        let bundle = songs::songs_deck(vec![song], &deck_name, &mut warnings);
+
+        self.finish(bundle, warnings, &options.import, user_id)
+            .await
+    }
+
+    /// Imports OpenLP song and custom slide databases as a group per song or custom slide,
+    /// in one deck with a section for each of them
+    pub async fn import_openlp(
+        &self,
+        paths: &[PathBuf],
+        options: &ImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let read_paths = paths.to_vec();
+        let (library, mut warnings) = tokio::task::spawn_blocking(move || {
+            let mut warnings = Vec::new();
+            read_paths
+                .iter()
+                .map(|path| openlp::read_library(path, &mut warnings))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|libraries| {
+                    (
+                        libraries.into_iter().flatten().collect::<Vec<_>>(),
+                        warnings,
+                    )
+                })
+        })
+        .await
+        .unwrap()?;
+
+        let deck_name = options
+            .deck_name
+            .clone()
+            .unwrap_or(String::from(OPENLP_DECK_NAME));
+        let bundle = songs::songs_deck(library, &deck_name, &mut warnings);
+
+        self.finish(bundle, warnings, options, user_id).await
+    }
+
+    /// Finds media by its file name, returning its id and MIME type
+    fn media_by_name(&self, name: &str) -> Option<(Uuid, String)> {
+        let db = self.db.get();
+
+        let media = db
+            .prepare_cached("SELECT \"id\", \"mime_type\" FROM \"media\" WHERE \"name\" = :name ORDER BY \"created_at\" DESC LIMIT 1;")
+            .unwrap()
+            .query_row(named_params! {":name": name}, |row| {
+                Ok((row.get("id")?, row.get("mime_type")?))
+            })
+            .optional()
+            .expect("Error occurred getting media from database");
+
+        media
+    }
+
+    /// Imports ProPresenter 6 documents, given with their file names, as a group per document
+    /// in one deck. Media is found in the media library by file name and left out otherwise.
+    pub async fn import_propresenter(
+        &self,
+        files: &[(String, PathBuf)],
+        options: &ImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let mut warnings = Vec::new();
+        let mut library = Vec::new();
+
+        for (file_name, path) in files {
+            let xml = fs::read_to_string(path)
+                .await
+                .map_err(|_| GenericError::BAD_REQUEST)?;
+            let mut presentation = propresenter::parse(&xml, file_name, &mut warnings)?;
+
+            for media in presentation.media {
+                let Some((media_id, mime_type)) = self.media_by_name(&media.file_name) else {
+                    warnings.push(ImportWarning {
+                        source: format!("{} / {}", file_name, media.file_name),
+                        message: String::from("Media isn't in the media library, media left out"),
+                    });
+                    continue;
+                };
+                let key = match mime_type.starts_with("image/") {
+                    true => BACKGROUND_KEY,
+                    false => MEDIA_KEY,
+                };
+                presentation.song.verses[media.verse].slides[media.slide]
+                    .entry(String::from(key))
+                    .or_insert(MediaService::file_url(media_id));
+            }
+
+            library.push(presentation.song);
+        }
+
+        let deck_name = match (&options.deck_name, library.as_slice()) {
+            (Some(deck_name), _) => deck_name.clone(),
+            (None, [song]) => song.title.clone(),
+            (None, _) => String::from(PROPRESENTER_DECK_NAME),
+        };
+        let bundle = songs::songs_deck(library, &deck_name, &mut warnings);
+
+        self.finish(bundle, warnings, options, user_id).await
+    }
+
+    /// Imports a PowerPoint presentation as a group with a slide per PowerPoint slide and
+    /// a deck of its own. Images are added to the media library, the first image of a slide
+    /// becoming its background.
+    pub async fn import_pptx(
+        &self,
+        path: &Path,
+        file_name: &str,
+        options: &ImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let read_path = path.to_owned();
+        let extract_directory = self.media_service.temp_path().await;
+        let read_directory = extract_directory.clone();
+        let max_size = self.config.media_max_upload_size;
+        let (presentation, mut warnings) = tokio::task::spawn_blocking(move || {
+            let mut warnings = Vec::new();
+            pptx::read(&read_path, &read_directory, max_size, &mut warnings)
+                .map(|presentation| (presentation, warnings))
+        })
+        .await
+        .unwrap()
+        .inspect_err(|_| {
+            let _ = std::fs::remove_dir_all(&extract_directory);
+        })?;
+
+        let title = match presentation.title {
+            Some(title) => title,
+            None => match file_name.rsplit_once('.') {
+                Some((name, _)) if !name.is_empty() => name.to_owned(),
+                _ => String::from("Untitled"),
+            },
+        };
+
+        // images are extracted to be described and, when saving, copied into the library
+        let mut media = Vec::new();
+        let mut media_files = Vec::new();
+        for (name, path) in &presentation.images {
+            media.push(Self::read_media(path, name).await);
+            media_files.push(path.clone());
+        }
+
+        let group_id = Uuid::new_v4();
+        let mut section = BundleDeckSection {
+            name: Some(title.clone()),
+            slide_group_id: Some(group_id),
+            ..Default::default()
+        };
+        let mut slides = Vec::new();
+        for slide in presentation.slides {
+            let mut content: BundleContent = BTreeMap::new();
+            content.insert(String::from(TITLE_KEY), Some(slide.title));
+            content.insert(String::from(BODY_KEY), Some(slide.body));
+            if !slide.notes.is_empty() {
+                content.insert(String::from(NOTES_KEY), Some(slide.notes));
+            }
+            if let Some(image) = slide.images.first() {
+                content.insert(
+                    String::from(BACKGROUND_KEY),
+                    Some(MediaService::file_url(media[*image].id)),
+                );
+            }
+            if slide.images.len() > 1 {
+                warnings.push(ImportWarning {
+                    source: format!("slide {}", slide.number),
+                    message: String::from(
+                        "Slide shows several images, only the first is used as its background",
+                    ),
+                });
+            }
+
+            let slide_id = Uuid::new_v4();
+            slides.push(BundleSlide {
+                id: slide_id,
+                slide_group_id: Some(group_id),
+                slide_type_id: None,
+                name: format!("Slide {}", slide.number),
+                content,
+            });
+            section.slides.push(BundleDeckSlide {
+                slide_id: Some(slide_id),
+                ..Default::default()
+            });
+        }
+
+        let bundle = DeckBundle {
+            slide_types: Vec::new(),
+            slide_groups: vec![BundleSlideGroup {
+                id: group_id,
+                parent_group_id: None,
+                name: title.clone(),
+                content: BTreeMap::from([(String::from(TITLE_KEY), Some(title.clone()))]),
+            }],
+            slides,
+            deck: BundleDeck {
+                name: options.deck_name.clone().unwrap_or(title),
+                content: BTreeMap::new(),
+                sections: vec![section],
+            },
+            media,
+        };
+
+        let report = self
+            .finish_with_media(bundle, &media_files, warnings, options, user_id)
+            .await;
+        let _ = fs::remove_dir_all(&extract_directory).await;
+
+        report
+    }
+
+    /// Imports the images of a zip file, or of a folder in the import directory when no file
+    /// is given, as a slideshow. Each image is added to the media library and shown as the
+    /// background of a slide of its own, in order of file name, advancing after the duration.
+    pub async fn import_images(
+        &self,
+        zip_file: Option<(&str, &Path)>,
+        options: &ImageImportOptions,
+        user_id: Option<Uuid>,
+    ) -> Result<ImportReport, GenericError> {
+        let (source_name, source_path) = match (zip_file, &options.folder) {
+            (Some((file_name, path)), _) => (file_name.to_owned(), path.to_owned()),
+            (None, Some(folder)) => (
+                folder.clone(),
+                images::folder_path(&self.config.import_directory, folder)
+                    .ok_or(GenericError::BAD_REQUEST)?,
+            ),
+            (None, None) => return Err(GenericError::BAD_REQUEST),
+        };
+
+        let extract_directory = self.media_service.temp_path().await;
+        let max_size = self.config.media_max_upload_size;
+        let is_zip = zip_file.is_some();
+        let read_directory = extract_directory.clone();
+        let (mut files, mut warnings) = tokio::task::spawn_blocking(move || {
+            let mut warnings = Vec::new();
+            let files = match is_zip {
+                true => images::extract_zip(&source_path, &read_directory, max_size, &mut warnings),
+                false => images::read_folder(&source_path, &mut warnings),
+            };
+            files.map(|files| (files, warnings))
+        })
+        .await
+        .unwrap()
+        .inspect_err(|_| {
+            let _ = std::fs::remove_dir_all(&extract_directory);
+        })?;
+        files.sort_by(|a, b| images::compare_names(&a.name, &b.name));
+
+        let mut media = Vec::new();
+        let mut media_files = Vec::new();
+        for file in files {
+            let bundle_media = Self::read_media(&file.path, &file.name).await;
+            if bundle_media.width.is_none() {
+                warnings.push(ImportWarning {
+                    source: file.name,
+                    message: String::from("Image could not be read, image skipped"),
+                });
+                continue;
+            }
+            media.push(bundle_media);
+            media_files.push(file.path);
+        }
+        if media.is_empty() {
+            let _ = fs::remove_dir_all(&extract_directory).await;
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        let title = match Path::new(&source_name).file_stem() {
+            Some(stem) if !stem.is_empty() && stem != "." => stem.to_string_lossy().into_owned(),
+            _ => String::from(IMAGES_DECK_NAME),
+        };
+        let title = options.import.deck_name.clone().unwrap_or(title);
+
+        let group_id = Uuid::new_v4();
+        let mut section = BundleDeckSection {
+            name: Some(title.clone()),
+            slide_group_id: Some(group_id),
+            ..Default::default()
+        };
+        let mut slides = Vec::new();
+        for image in &media {
+            let name = match image.name.rsplit_once('.') {
+                Some((stem, _)) if !stem.is_empty() => stem.to_owned(),
+                _ => image.name.clone(),
+            };
+            let content: BundleContent = BTreeMap::from([
+                (
+                    String::from(BACKGROUND_KEY),
+                    Some(MediaService::file_url(image.id)),
+                ),
+                (
+                    String::from(DURATION_KEY),
+                    Some(options.duration.to_string()),
+                ),
+            ]);
+
+            let slide_id = Uuid::new_v4();
+            slides.push(BundleSlide {
+                id: slide_id,
+                slide_group_id: Some(group_id),
+                slide_type_id: None,
+                name,
+                content,
+            });
+            section.slides.push(BundleDeckSlide {
+                slide_id: Some(slide_id),
+                ..Default::default()
+            });
+        }
+
+        let bundle = DeckBundle {
+            slide_types: Vec::new(),
+            slide_groups: vec![BundleSlideGroup {
+                id: group_id,
+                parent_group_id: None,
+                name: title.clone(),
+                content: BTreeMap::new(),
+            }],
+            slides,
+            deck: BundleDeck {
+                name: title,
+                content: BTreeMap::new(),
+                sections: vec![section],
+            },
+            media,
+        };
+
+        let report = self
+            .finish_with_media(bundle, &media_files, warnings, &options.import, user_id)
+            .await;
+        let _ = fs::remove_dir_all(&extract_directory).await;
+
+        report
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::bundles::model::{
+    BundleContent, BundleDeck, BundleDeckSection, BundleDeckSlide, BundleSlide, BundleSlideGroup,
+    DeckBundle,
+};
+
+use super::model::ImportWarning;
+
+pub const TITLE_KEY: &str = "title";
+pub const TEXT_KEY: &str = "text";
+/// Slide content key for the label a section was given, such as "Verse 2"
+pub const LABEL_KEY: &str = "label";
+/// Group content key holding the verse names in the order they are sung, separated by spaces
+pub const VERSE_ORDER_KEY: &str = "verse_order";
+
+/// Separates a verse name from the number of its part in slide names, as in `v1.2`
+const PART_SEPARATOR: char = '.';
+
+/// A song read by one of the song importers
+#[derive(Clone, Default)]
+pub struct Song {
+    pub title: String,
+    /// Stored as group content alongside the title, such as `authors` or `ccli_number`
+    pub metadata: BTreeMap<String, String>,
+    pub verses: Vec<SongVerse>,
+    /// Verse names in the order they are sung, every verse once in order when empty
+    pub verse_order: Vec<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct SongVerse {
+    /// Short name such as `v1` or `c` which the verse order refers to
+    pub name: String,
+    /// Content of each slide of the verse, with at least the `text` key
+    pub slides: Vec<BTreeMap<String, String>>,
+}
+
+/// Hands out verse names made of a prefix and a number, such as `v2`, without repeating any
+#[derive(Default)]
+pub struct VerseNames {
+    names: HashSet<String>,
+    counts: HashMap<String, usize>,
+}
+
+impl VerseNames {
+    /// Names a verse with the number it was given when that is still free,
+    /// otherwise with the next free number for its prefix
+    pub fn next(&mut self, prefix: &str, number: Option<usize>) -> String {
+        let name = match number.map(|number| format!("{}{}", prefix, number)) {
+            Some(name) if !self.names.contains(&name) => name,
+            _ => {
+                let count = self.counts.entry(prefix.to_owned()).or_default();
+                loop {
+                    *count += 1;
+                    let name = format!("{}{}", prefix, count);
+                    if !self.names.contains(&name) {
+                        break name;
+                    }
+                }
+            }
+        };
+        self.names.insert(name.clone());
+        name
+    }
+}
+
+/// Name of the slide showing a part of a verse, the first part being named like the verse
+pub fn slide_name(verse_name: &str, part: usize) -> String {
+    match part {
+        0 => verse_name.to_owned(),
+        part => format!("{}{}{}", verse_name, PART_SEPARATOR, part + 1),
+    }
+}
+
+/// Splits a slide name made by `slide_name` into the verse name and the index of the part
+pub fn split_slide_name(slide_name: &str) -> (&str, usize) {
+    match slide_name.rsplit_once(PART_SEPARATOR) {
+        Some((verse_name, part)) => match part.parse::<usize>() {
+            Ok(part) if part > 1 => (verse_name, part - 1),
+            _ => (slide_name, 0),
+        },
+        None => (slide_name, 0),
+    }
+}
+
+/// Turns songs into a deck with a group of slides per song and a section per song
+/// that follows its verse order
+pub fn songs_deck(
+    songs: Vec<Song>,
+    deck_name: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> DeckBundle {
+    let mut bundle = DeckBundle {
+        deck: BundleDeck {
+            name: deck_name.to_owned(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    // songs with the same title in one import would collide before any conflict policy applies
+    let mut group_names = HashSet::new();
+
+    for song in songs {
+        let title = match song.title.trim() {
+            "" => String::from("Untitled"),
+            title => title.to_owned(),
+        };
+        let group_name = (1..)
+            .map(|number| match number {
+                1 => title.clone(),
+                number => format!("{} ({})", title, number),
+            })
+            .find(|name| !group_names.contains(name))
+            .unwrap();
+        group_names.insert(group_name.clone());
+
+        let mut content: BundleContent = song
+            .metadata
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (key.clone(), Some(value.clone())))
+            .collect();
+        content.insert(String::from(TITLE_KEY), Some(title.clone()));
+        if !song.verse_order.is_empty() {
+            content.insert(
+                String::from(VERSE_ORDER_KEY),
+                Some(song.verse_order.join(" ")),
+            );
+        }
+
+        let group_id = Uuid::new_v4();
+        bundle.slide_groups.push(BundleSlideGroup {
+            id: group_id,
+            parent_group_id: None,
+            name: group_name.clone(),
+            content,
+        });
+
+        let mut verse_slides: BTreeMap<&str, Vec<Uuid>> = BTreeMap::new();
+        for verse in &song.verses {
+            if verse.name.is_empty() || verse_slides.contains_key(verse.name.as_str()) {
+                warnings.push(ImportWarning {
+                    source: format!("{} / {}", title, verse.name),
+                    message: String::from("Verse name is empty or used twice, verse skipped"),
+                });
+                continue;
+            }
+
+            let mut slide_ids = Vec::new();
+            for (part, slide_content) in verse.slides.iter().enumerate() {
+                let slide_id = Uuid::new_v4();
+                bundle.slides.push(BundleSlide {
+                    id: slide_id,
+                    slide_group_id: Some(group_id),
+                    slide_type_id: None,
+                    name: slide_name(&verse.name, part),
+                    content: slide_content
+                        .iter()
+                        .map(|(key, value)| (key.clone(), Some(value.clone())))
+                        .collect(),
+                });
+                slide_ids.push(slide_id);
+            }
+            verse_slides.insert(&verse.name, slide_ids);
+        }
+
+        let order: Vec<&str> = match song.verse_order.is_empty() {
+            true => song
+                .verses
+                .iter()
+                .map(|verse| verse.name.as_str())
+                .collect(),
+            false => song.verse_order.iter().map(String::as_str).collect(),
+        };
+        let mut section = BundleDeckSection {
+            name: Some(group_name),
+            slide_group_id: Some(group_id),
+            ..Default::default()
+        };
+        let mut missing = HashSet::new();
+        for verse_name in order {
+            match verse_slides.get(verse_name) {
+                Some(slide_ids) => {
+                    section
+                        .slides
+                        .extend(slide_ids.iter().map(|slide_id| BundleDeckSlide {
+                            slide_id: Some(*slide_id),
+                            ..Default::default()
+                        }))
+                }
+                None if missing.insert(verse_name) => warnings.push(ImportWarning {
+                    source: format!("{} / {}", title, verse_name),
+                    message: String::from("Verse order refers to a verse that doesn't exist"),
+                }),
+                None => {}
+            }
+        }
+        bundle.deck.sections.push(section);
+    }
+
+    bundle
+}
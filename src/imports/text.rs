@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    model::{ImportOptions, ImportWarning},
+    songs::{Song, SongVerse, VerseNames, LABEL_KEY, TEXT_KEY, TITLE_KEY},
+};
+
+/// Section labels recognised in lyrics, by lowercase name and the prefix of their verse names
+const SECTION_LABELS: &[(&str, &str)] = &[
+    ("verse", "v"),
+    ("chorus", "c"),
+    ("refrain", "c"),
+    ("pre-chorus", "p"),
+    ("prechorus", "p"),
+    ("bridge", "b"),
+    ("tag", "t"),
+    ("intro", "i"),
+    ("outro", "o"),
+    ("ending", "e"),
+];
+/// Prefix of the verse names of paragraphs outside of any section
+const PARAGRAPH_PREFIX: &str = "v";
+/// Prefix of the verse names of sections started by headings that aren't section labels
+pub const HEADING_PREFIX: &str = "s";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TextImportOptions {
+    #[serde(flatten)]
+    pub import: ImportOptions,
+    /// Most lines on a slide, longer paragraphs being split over several slides
+    pub max_lines: Option<usize>,
+    /// Most characters on a slide, longer paragraphs being split over several slides
+    pub max_characters: Option<usize>,
+}
+
+struct Section {
+    prefix: &'static str,
+    /// Number given in a label such as "Verse 2"
+    number: Option<usize>,
+    label: Option<String>,
+    /// Heading shown as the title of each slide of the section
+    heading: Option<String>,
+    paragraphs: Vec<Vec<String>>,
+    line: usize,
+}
+
+/// Reads a label such as "Verse 2", "[Chorus]" or "Bridge:" into its verse name prefix and number
+pub fn section_label(text: &str) -> Option<(&'static str, Option<usize>)> {
+    let text = text.trim().trim_end_matches(':');
+    let text = text
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+        .or_else(|| text.strip_prefix('(')?.strip_suffix(')'))
+        .unwrap_or(text)
+        .trim()
+        .to_lowercase();
+
+    let (name, number) = match text.rsplit_once(' ') {
+        Some((name, number)) => match number.parse::<usize>() {
+            Ok(number) => (name.trim(), Some(number)),
+            Err(_) => (text.as_str(), None),
+        },
+        None => (text.as_str(), None),
+    };
+    let name = name.replace(' ', "-");
+
+    SECTION_LABELS
+        .iter()
+        .find(|(label, _)| *label == name)
+        .map(|(_, prefix)| (*prefix, number))
+}
+
+/// Reads a Markdown heading into its level and text
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    match (level, line[level..].strip_prefix(' ')) {
+        (1..=6, Some(text)) => Some((level, text.trim().trim_end_matches('#').trim())),
+        _ => None,
+    }
+}
+
+/// Whether a line is a Markdown horizontal rule, which separates slides like a blank line
+fn is_rule(line: &str) -> bool {
+    let line: String = line.split_whitespace().collect();
+    line.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|rule| line.chars().all(|c| c == *rule))
+}
+
+/// Wraps a line at words so that it fits on a slide, words longer than a slide are left whole
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    if line.chars().count() <= width {
+        return vec![line.to_owned()];
+    }
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Splits a paragraph over as many slides as it needs to keep within the limits
+fn split_paragraph(
+    lines: &[String],
+    max_lines: Option<usize>,
+    max_characters: Option<usize>,
+) -> Vec<String> {
+    let max_lines = max_lines.unwrap_or(usize::MAX).max(1);
+    let max_characters = max_characters.unwrap_or(usize::MAX).max(1);
+
+    let mut slides = Vec::new();
+    let mut slide: Vec<String> = Vec::new();
+    let mut characters: usize = 0;
+    for line in lines.iter().flat_map(|line| wrap(line, max_characters)) {
+        let length = line.chars().count();
+        // line breaks count towards the characters of a slide
+        if !slide.is_empty()
+            && (slide.len() >= max_lines || characters.saturating_add(1 + length) > max_characters)
+        {
+            slides.push(slide.join("\n"));
+            slide.clear();
+            characters = 0;
+        }
+        characters += length + usize::from(!slide.is_empty());
+        slide.push(line);
+    }
+    if !slide.is_empty() {
+        slides.push(slide.join("\n"));
+    }
+    slides
+}
+
+/// Ends the paragraph being read, as part of the current section or as a section of its own
+fn end_paragraph(
+    paragraph: &mut Vec<String>,
+    current: &mut Option<Section>,
+    sections: &mut Vec<Section>,
+    line: usize,
+) {
+    if paragraph.is_empty() {
+        return;
+    }
+    let lines = std::mem::take(paragraph);
+    match current {
+        Some(section) => section.paragraphs.push(lines),
+        None => sections.push(Section {
+            prefix: PARAGRAPH_PREFIX,
+            number: None,
+            label: None,
+            heading: None,
+            paragraphs: vec![lines],
+            line,
+        }),
+    }
+}
+
+/// Reads plain text or Markdown as a song.
+/// Slides are split at blank lines, horizontal rules and headings, and a heading or line
+/// that is only a section label starts a section. A label with no text after it repeats
+/// the section it names, and a first level heading before anything else is the title.
+pub fn parse(text: &str, options: &TextImportOptions, warnings: &mut Vec<ImportWarning>) -> Song {
+    let mut song = Song::default();
+    let mut sections: Vec<Section> = Vec::new();
+    let mut current: Option<Section> = None;
+    let mut paragraph: Vec<String> = Vec::new();
+
+    for (number, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim_end().replace("**", "").replace("__", "");
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || is_rule(trimmed) {
+            end_paragraph(&mut paragraph, &mut current, &mut sections, number);
+            continue;
+        }
+
+        let heading = heading(trimmed);
+        let label = section_label(heading.map(|(_, text)| text).unwrap_or(trimmed));
+        if heading.is_none() && label.is_none() {
+            paragraph.push(line);
+            continue;
+        }
+
+        end_paragraph(&mut paragraph, &mut current, &mut sections, number);
+        if let Some((1, text)) = heading {
+            if song.title.is_empty() && sections.is_empty() && current.is_none() {
+                song.title = text.to_owned();
+                continue;
+            }
+        }
+
+        let label_text = heading.map(|(_, text)| text).unwrap_or(trimmed);
+        sections.extend(current.take());
+        current = Some(match label {
+            Some((prefix, number_in_label)) => Section {
+                prefix,
+                number: number_in_label,
+                label: Some(label_text.trim_end_matches(':').to_owned()),
+                heading: None,
+                paragraphs: Vec::new(),
+                line: number,
+            },
+            None => Section {
+                prefix: HEADING_PREFIX,
+                number: None,
+                label: Some(label_text.to_owned()),
+                heading: Some(label_text.to_owned()),
+                paragraphs: Vec::new(),
+                line: number,
+            },
+        });
+    }
+    end_paragraph(
+        &mut paragraph,
+        &mut current,
+        &mut sections,
+        text.lines().count(),
+    );
+    sections.extend(current.take());
+
+    let mut names = VerseNames::default();
+    // verses by their lowercase label, for labels that repeat a section
+    let mut labelled: HashMap<String, usize> = HashMap::new();
+
+    for section in sections {
+        let label_key = section.label.as_deref().map(str::to_lowercase);
+
+        // a label alone repeats the section it names, or the latest of its kind without a number
+        if section.paragraphs.is_empty() && section.heading.is_none() {
+            let repeated = label_key
+                .as_ref()
+                .and_then(|label| labelled.get(label))
+                .or_else(|| match section.number {
+                    Some(_) => None,
+                    None => labelled
+                        .values()
+                        .filter(|index| song.verses[**index].name.starts_with(section.prefix))
+                        .max(),
+                });
+            match repeated {
+                Some(index) => song.verse_order.push(song.verses[*index].name.clone()),
+                None => warnings.push(ImportWarning {
+                    source: format!("line {}", section.line + 1),
+                    message: format!(
+                        "Section \"{}\" has no text and repeats no earlier section",
+                        section.label.unwrap_or_default()
+                    ),
+                }),
+            }
+            continue;
+        }
+
+        let name = names.next(section.prefix, section.number);
+
+        // headings without text are title slides
+        let paragraphs = match section.paragraphs.is_empty() {
+            true => vec![Vec::new()],
+            false => section.paragraphs,
+        };
+        let mut slides = Vec::new();
+        for paragraph in paragraphs {
+            let texts = match paragraph.is_empty() {
+                true => vec![String::new()],
+                false => split_paragraph(&paragraph, options.max_lines, options.max_characters),
+            };
+            for text in texts {
+                let mut content = BTreeMap::new();
+                content.insert(String::from(TEXT_KEY), text);
+                if let Some(label) = &section.label {
+                    content.insert(String::from(LABEL_KEY), label.clone());
+                }
+                if let Some(heading) = &section.heading {
+                    content.insert(String::from(TITLE_KEY), heading.clone());
+                }
+                slides.push(content);
+            }
+        }
+
+        if let Some(label) = label_key {
+            labelled.insert(label, song.verses.len());
+        }
+        song.verse_order.push(name.clone());
+        song.verses.push(SongVerse { name, slides });
+    }
+
+    song
+}
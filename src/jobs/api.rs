@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        Path, Query, State, WebSocketUpgrade,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+};
+
+use super::db::{DbJob, JobStatus};
+
+/// Users allowed to see and manage jobs
+const JOB_PERMISSIONS: i64 = UserPermission::SYSTEM_ADMIN | UserPermission::SETUP;
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/", get(list_jobs))
+        .route("/events", get(events_handler))
+        .route("/:job_id", get(get_job))
+        .route("/:job_id/cancel", post(cancel_job))
+        .route("/:job_id/retry", post(retry_job))
+}
+
+#[derive(Deserialize)]
+pub struct ListJobsQuery {
+    pub status: Option<JobStatus>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+/// Most jobs listed at once
+const MAX_LIMIT: i64 = 1000;
+
+fn default_limit() -> i64 {
+    100
+}
+
+pub async fn list_jobs(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<ListJobsQuery>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, JOB_PERMISSIONS) else {
+        return AuthToken::failure_response();
+    };
+
+    let jobs = state
+        .jobs_service
+        .list(query.status, query.limit.clamp(1, MAX_LIMIT));
+
+    Json(jobs).into_response()
+}
+
+pub async fn get_job(
+    State(state): State<Arc<AppServices>>,
+    Path(job_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, JOB_PERMISSIONS) else {
+        return AuthToken::failure_response();
+    };
+
+    match state.jobs_service.get(job_id) {
+        Some(job) => Json(job).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn cancel_job(
+    State(state): State<Arc<AppServices>>,
+    Path(job_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, JOB_PERMISSIONS) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.jobs_service.cancel(job_id);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "job_cancel",
+        json!({
+            "job_id": job_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(job) => Json(job).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn retry_job(
+    State(state): State<Arc<AppServices>>,
+    Path(job_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, JOB_PERMISSIONS) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.jobs_service.retry(job_id);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "job_retry",
+        json!({
+            "job_id": job_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(job) => Json(job).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JobsRequest {
+    Authenticate { auth_token: String },
+    Ping { ping: String },
+    Pong { pong: String },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JobsResponse {
+    AuthResult { auth: bool },
+    Job { job: DbJob },
+    Ping { ping: String },
+    Pong { pong: String },
+}
+
+pub async fn events_handler(
+    State(state): State<Arc<AppServices>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(|socket| events_websocket_handler(socket, state))
+}
+
+/// Sends every job change to the client once it has authenticated
+pub async fn events_websocket_handler(mut socket: WebSocket, state: Arc<AppServices>) {
+    let mut updates = state.jobs_service.subscribe();
+    let mut authorized = false;
+
+    loop {
+        let response = tokio::select! {
+            msg = socket.recv() => {
+                let Some(Ok(msg)) = msg else {
+                    break;
+                };
+                let Message::Text(msg) = msg else {
+                    continue;
+                };
+                let Ok(request) = serde_json::from_str::<JobsRequest>(&msg) else {
+                    continue;
+                };
+
+                match request {
+                    JobsRequest::Authenticate { auth_token } => {
+                        authorized = state
+                            .auth_service
+                            .authorize(&auth_token, JOB_PERMISSIONS)
+                            .is_some();
+                        JobsResponse::AuthResult { auth: authorized }
+                    }
+                    JobsRequest::Ping { ping } => JobsResponse::Pong { pong: ping },
+                    JobsRequest::Pong { pong: _ } => continue,
+                }
+            },
+            job = updates.recv() => match job {
+                Ok(job) if authorized => JobsResponse::Job { job },
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            _ = state.shutdown_token.cancelled() => break,
+        };
+
+        let response_json = serde_json::to_string(&response).unwrap();
+        if socket.send(Message::Text(response_json)).await.is_err() {
+            break;
+        }
+    }
+}
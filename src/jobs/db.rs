@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+    Row, ToSql,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Kind of work a job does, which decides how its payload is interpreted
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobType {
+    /// Generates the thumbnail and display variants of an image
+    MediaVariants,
+    /// Backs up the database
+    DatabaseBackup,
+    /// Saves slides from files uploaded to an importer
+    Import,
+    /// Imports a deck bundle
+    BundleImport,
+    /// Writes a deck bundle for download
+    BundleExport,
+}
+impl JobType {
+    /// Whether a running job can be cancelled. Jobs that save to the database from blocking
+    /// work can't be stopped partway through, so they run to the end once started.
+    pub fn is_cancellable_while_running(self) -> bool {
+        matches!(self, Self::MediaVariants | Self::BundleExport)
+    }
+}
+impl ToSql for JobType {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Self::MediaVariants => "media_variants",
+            Self::DatabaseBackup => "database_backup",
+            Self::Import => "import",
+            Self::BundleImport => "bundle_import",
+            Self::BundleExport => "bundle_export",
+        }
+        .into())
+    }
+}
+impl FromSql for JobType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("media_variants") => Ok(Self::MediaVariants),
+            Ok("database_backup") => Ok(Self::DatabaseBackup),
+            Ok("import") => Ok(Self::Import),
+            Ok("bundle_import") => Ok(Self::BundleImport),
+            Ok("bundle_export") => Ok(Self::BundleExport),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for a worker, possibly to be retried after a failure
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+impl ToSql for JobStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+        .into())
+    }
+}
+impl FromSql for JobStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("queued") => Ok(Self::Queued),
+            Ok("running") => Ok(Self::Running),
+            Ok("succeeded") => Ok(Self::Succeeded),
+            Ok("failed") => Ok(Self::Failed),
+            Ok("cancelled") => Ok(Self::Cancelled),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbJob {
+    pub id: Uuid,
+    pub job_type: JobType,
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    /// Fraction of the work done, from 0 to 1
+    pub progress: f64,
+    pub progress_message: Option<String>,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    /// Time the job may be picked up at, later than creation when retrying
+    pub run_after: DateTime<Utc>,
+    pub result: Option<serde_json::Value>,
+    /// Error of the last failed attempt
+    pub error: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+impl DbJob {
+    pub const TABLE_NAME: &'static str = "jobs";
+
+    pub const COLUMNS_SQL: &'static str =
+        "\"id\", \"job_type\", \"payload_json\", \"status\", \"progress\", \"progress_message\", \"attempts\", \"max_attempts\", \"run_after\", \"result_json\", \"error\", \"created_by\", \"created_at\", \"started_at\", \"finished_at\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            id: row
+                .get("id")
+                .expect("Failed to get value from database row"),
+            job_type: row
+                .get("job_type")
+                .expect("Failed to get value from database row"),
+            payload: serde_json::from_str(
+                &row.get::<_, String>("payload_json")
+                    .expect("Failed to get value from database row"),
+            )
+            .expect("Error parsing JSON from job payload"),
+            status: row
+                .get("status")
+                .expect("Failed to get value from database row"),
+            progress: row
+                .get("progress")
+                .expect("Failed to get value from database row"),
+            progress_message: row
+                .get("progress_message")
+                .expect("Failed to get value from database row"),
+            attempts: row
+                .get("attempts")
+                .expect("Failed to get value from database row"),
+            max_attempts: row
+                .get("max_attempts")
+                .expect("Failed to get value from database row"),
+            run_after: row
+                .get("run_after")
+                .expect("Failed to get value from database row"),
+            result: row
+                .get::<_, Option<String>>("result_json")
+                .expect("Failed to get value from database row")
+                .map(|result| {
+                    serde_json::from_str(&result).expect("Error parsing JSON from job result")
+                }),
+            error: row
+                .get("error")
+                .expect("Failed to get value from database row"),
+            created_by: row
+                .get("created_by")
+                .expect("Failed to get value from database row"),
+            created_at: row
+                .get("created_at")
+                .expect("Failed to get value from database row"),
+            started_at: row
+                .get("started_at")
+                .expect("Failed to get value from database row"),
+            finished_at: row
+                .get("finished_at")
+                .expect("Failed to get value from database row"),
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chrono::{TimeDelta, Utc};
+use rusqlite::{named_params, OptionalExtension};
+use serde::de::DeserializeOwned;
+use tokio::{
+    fs,
+    sync::{broadcast, Notify},
+};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{config::file::AppConfig, database::Database, helpers::errors::GenericError};
+
+use super::db::{DbJob, JobStatus, JobType};
+
+/// Number of job updates buffered for slow subscribers
+const JOB_UPDATES_CAPACITY: usize = 256;
+
+/// Persistent queue of background jobs.
+/// Clones share the same queue, so services can enqueue jobs themselves.
+#[derive(Clone)]
+pub struct JobsService {
+    config: AppConfig,
+    db: Database,
+    /// Wakes a waiting worker when a job is queued
+    queued: Arc<Notify>,
+    /// Every change to a job
+    updates: broadcast::Sender<DbJob>,
+    /// Cancellation tokens of running jobs
+    running: Arc<Mutex<HashMap<Uuid, CancellationToken>>>,
+}
+
+impl JobsService {
+    pub fn new(database: &Database, config: &AppConfig) -> Self {
+        Self {
+            config: config.clone(),
+            db: database.clone(),
+            queued: Arc::new(Notify::new()),
+            updates: broadcast::channel(JOB_UPDATES_CAPACITY).0,
+            running: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<DbJob> {
+        let db = self.db.get();
+
+        let job_result: Option<DbJob> = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"jobs\" WHERE \"id\" = :id;",
+                DbJob::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_row(named_params! {":id": id}, |row| Ok(DbJob::from_row(row)))
+            .optional()
+            .expect("Error occurred getting job by id from database");
+
+        job_result
+    }
+
+    /// Lists jobs, newest first
+    pub fn list(&self, status: Option<JobStatus>, limit: i64) -> Vec<DbJob> {
+        let db = self.db.get();
+
+        let jobs = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"jobs\" WHERE :status IS NULL OR \"status\" = :status ORDER BY \"created_at\" DESC LIMIT :limit;",
+                DbJob::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_map(named_params! {":status": status, ":limit": limit}, |row| {
+                Ok(DbJob::from_row(row))
+            })
+            .expect("Error occurred getting jobs from database")
+            .map(|job| job.unwrap())
+            .collect();
+
+        jobs
+    }
+
+    /// Subscribes to changes of all jobs
+    pub fn subscribe(&self) -> broadcast::Receiver<DbJob> {
+        self.updates.subscribe()
+    }
+
+    /// Sends the current state of a job to subscribers
+    fn notify(&self, id: Uuid) -> Option<DbJob> {
+        let job = self.get(id)?;
+        let _ = self.updates.send(job.clone());
+        Some(job)
+    }
+
+    /// Queues a job to be run by the next free worker
+    pub fn enqueue(
+        &self,
+        job_type: JobType,
+        payload: serde_json::Value,
+        created_by: Option<Uuid>,
+    ) -> Uuid {
+        self.enqueue_as(Uuid::new_v4(), job_type, payload, created_by)
+    }
+
+    /// Queues a job under an id chosen beforehand, so its files can be kept before it runs
+    pub fn enqueue_as(
+        &self,
+        job_id: Uuid,
+        job_type: JobType,
+        payload: serde_json::Value,
+        created_by: Option<Uuid>,
+    ) -> Uuid {
+        let now = Utc::now();
+
+        let db = self.db.get();
+        db.prepare_cached("INSERT INTO \"jobs\" (\"id\", \"job_type\", \"payload_json\", \"status\", \"max_attempts\", \"run_after\", \"created_by\", \"created_at\") VALUES (:id, :job_type, :payload_json, :status, :max_attempts, :run_after, :created_by, :created_at);")
+            .unwrap()
+            .execute(named_params! {
+                ":id": job_id,
+                ":job_type": job_type,
+                ":payload_json": payload.to_string(),
+                ":status": JobStatus::Queued,
+                ":max_attempts": self.config.job_max_attempts,
+                ":run_after": now,
+                ":created_by": created_by,
+                ":created_at": now,
+            })
+            .expect("Error occurred adding job to database");
+
+        self.notify(job_id);
+        self.queued.notify_one();
+
+        job_id
+    }
+
+    /// Directory of the files of a job, such as uploads to import or an export to download.
+    /// The files are kept as long as the job, so it can be retried.
+    pub fn files_directory(&self, job_id: Uuid) -> PathBuf {
+        Path::new(&self.config.job_directory).join(job_id.to_string())
+    }
+
+    /// Moves files into the directory of a job, returning their new paths
+    pub async fn keep_files(&self, job_id: Uuid, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let directory = self.files_directory(job_id);
+        fs::create_dir_all(&directory)
+            .await
+            .expect("Failed to create job directory");
+
+        let mut kept = Vec::new();
+        for path in paths {
+            let kept_path = directory.join(path.file_name().unwrap());
+            // the job directory may be on another file system than the upload
+            if fs::rename(path, &kept_path).await.is_err() {
+                fs::copy(path, &kept_path)
+                    .await
+                    .expect("Failed to copy file to job directory");
+                let _ = fs::remove_file(path).await;
+            }
+            kept.push(kept_path);
+        }
+        kept
+    }
+
+    /// Waits until a job is queued
+    pub async fn wait_for_jobs(&self) {
+        self.queued.notified().await;
+    }
+
+    /// Requeues jobs that were running when the server stopped
+    pub fn recover(&self) {
+        let db = self.db.get();
+        db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :queued WHERE \"status\" = :running;")
+            .unwrap()
+            .execute(named_params! {
+                ":queued": JobStatus::Queued,
+                ":running": JobStatus::Running,
+            })
+            .expect("Error occurred recovering interrupted jobs");
+    }
+
+    /// Takes the next due job off the queue and marks it as running
+    pub fn claim_next(&self) -> Option<JobContext> {
+        let now = Utc::now();
+
+        let db = self.db.get();
+        let job_result: Option<DbJob> = db
+            .prepare_cached(&format!(
+                "UPDATE \"jobs\" SET \"status\" = :running, \"attempts\" = \"attempts\" + 1, \"progress\" = 0, \"progress_message\" = NULL, \"started_at\" = :now WHERE \"id\" = (SELECT \"id\" FROM \"jobs\" WHERE \"status\" = :queued AND \"run_after\" <= :now ORDER BY \"run_after\" LIMIT 1) RETURNING {};",
+                DbJob::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_row(
+                named_params! {
+                    ":running": JobStatus::Running,
+                    ":queued": JobStatus::Queued,
+                    ":now": now,
+                },
+                |row| Ok(DbJob::from_row(row)),
+            )
+            .optional()
+            .expect("Error occurred claiming job from database");
+
+        let job = job_result?;
+        let _ = self.updates.send(job.clone());
+
+        let cancel_token = CancellationToken::new();
+        self.running
+            .lock()
+            .unwrap()
+            .insert(job.id, cancel_token.clone());
+
+        Some(JobContext {
+            job,
+            cancel_token,
+            retry: Arc::new(AtomicBool::new(true)),
+            jobs: self.clone(),
+        })
+    }
+
+    /// Records the progress of a running job
+    pub fn set_progress(&self, id: Uuid, progress: f64, message: Option<&str>) {
+        let db = self.db.get();
+        db.prepare_cached("UPDATE \"jobs\" SET \"progress\" = :progress, \"progress_message\" = :progress_message WHERE \"id\" = :id AND \"status\" = :running;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": id,
+                ":progress": progress.clamp(0.0, 1.0),
+                ":progress_message": message,
+                ":running": JobStatus::Running,
+            })
+            .expect("Error occurred updating job progress");
+        drop(db);
+
+        self.notify(id);
+    }
+
+    /// Records the outcome of a run, retrying failures with exponential backoff unless the job
+    /// said they would fail again
+    pub fn finish(&self, context: JobContext, result: Result<serde_json::Value, String>) {
+        self.running.lock().unwrap().remove(&context.job.id);
+        let job = &context.job;
+        let now = Utc::now();
+
+        let db = self.db.get();
+        match result {
+            Err(_) if context.cancel_token.is_cancelled() => {
+                db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :status, \"finished_at\" = :now WHERE \"id\" = :id;")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": job.id,
+                        ":status": JobStatus::Cancelled,
+                        ":now": now,
+                    })
+                    .expect("Error occurred updating job");
+            }
+            Ok(result) => {
+                db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :status, \"progress\" = 1, \"result_json\" = :result_json, \"error\" = NULL, \"finished_at\" = :now WHERE \"id\" = :id;")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": job.id,
+                        ":status": JobStatus::Succeeded,
+                        ":result_json": result.to_string(),
+                        ":now": now,
+                    })
+                    .expect("Error occurred updating job");
+            }
+            Err(error)
+                if job.attempts < job.max_attempts && context.retry.load(Ordering::Relaxed) =>
+            {
+                let exponent = u32::try_from(job.attempts - 1).unwrap_or(0).min(16);
+                let delay = self.config.job_retry_delay.saturating_mul(1 << exponent);
+                let run_after = now + TimeDelta::seconds(i64::try_from(delay).unwrap_or(i64::MAX));
+
+                db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :status, \"error\" = :error, \"run_after\" = :run_after WHERE \"id\" = :id;")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": job.id,
+                        ":status": JobStatus::Queued,
+                        ":error": error,
+                        ":run_after": run_after,
+                    })
+                    .expect("Error occurred updating job");
+            }
+            Err(error) => {
+                db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :status, \"error\" = :error, \"finished_at\" = :now WHERE \"id\" = :id;")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": job.id,
+                        ":status": JobStatus::Failed,
+                        ":error": error,
+                        ":now": now,
+                    })
+                    .expect("Error occurred updating job");
+            }
+        }
+        drop(db);
+
+        self.notify(job.id);
+    }
+
+    /// Puts a job interrupted by shutdown back into the queue without counting the attempt
+    pub fn interrupt(&self, context: JobContext) {
+        self.running.lock().unwrap().remove(&context.job.id);
+
+        let db = self.db.get();
+        db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :status, \"attempts\" = \"attempts\" - 1 WHERE \"id\" = :id;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": context.job.id,
+                ":status": JobStatus::Queued,
+            })
+            .expect("Error occurred updating job");
+    }
+
+    /// Cancels a queued job, or asks a running job to stop if it can be stopped partway through
+    pub fn cancel(&self, id: Uuid) -> Result<DbJob, GenericError> {
+        let job = self.get(id).ok_or(GenericError::NOT_FOUND)?;
+
+        match job.status {
+            JobStatus::Queued => {
+                let db = self.db.get();
+                db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :status, \"finished_at\" = :now WHERE \"id\" = :id AND \"status\" = :queued;")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": id,
+                        ":status": JobStatus::Cancelled,
+                        ":queued": JobStatus::Queued,
+                        ":now": Utc::now(),
+                    })
+                    .expect("Error occurred cancelling job");
+            }
+            JobStatus::Running if !job.job_type.is_cancellable_while_running() => {
+                return Err(GenericError::CONFLICT);
+            }
+            JobStatus::Running => {
+                // the worker records the cancellation once the job stops
+                if let Some(cancel_token) = self.running.lock().unwrap().get(&id) {
+                    cancel_token.cancel();
+                }
+            }
+            _ => return Err(GenericError::CONFLICT),
+        }
+
+        self.notify(id).ok_or(GenericError::NOT_FOUND)
+    }
+
+    /// Queues a failed or cancelled job again with a fresh set of attempts
+    pub fn retry(&self, id: Uuid) -> Result<DbJob, GenericError> {
+        let job = self.get(id).ok_or(GenericError::NOT_FOUND)?;
+        if !matches!(job.status, JobStatus::Failed | JobStatus::Cancelled) {
+            return Err(GenericError::CONFLICT);
+        }
+
+        let db = self.db.get();
+        db.prepare_cached("UPDATE \"jobs\" SET \"status\" = :status, \"attempts\" = 0, \"progress\" = 0, \"progress_message\" = NULL, \"error\" = NULL, \"run_after\" = :now, \"finished_at\" = NULL WHERE \"id\" = :id;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": id,
+                ":status": JobStatus::Queued,
+                ":now": Utc::now(),
+            })
+            .expect("Error occurred retrying job");
+        drop(db);
+
+        self.queued.notify_one();
+        self.notify(id).ok_or(GenericError::NOT_FOUND)
+    }
+
+    /// Deletes finished jobs older than the configured maximum age
+    pub fn prune(&self) {
+        let max_age =
+            TimeDelta::seconds(i64::try_from(self.config.job_max_age).unwrap_or(i64::MAX));
+        let Some(cutoff) = Utc::now().checked_sub_signed(max_age) else {
+            return;
+        };
+
+        let db = self.db.get();
+        let pruned: Vec<Uuid> = db
+            .prepare_cached("DELETE FROM \"jobs\" WHERE \"status\" IN (:succeeded, :failed, :cancelled) AND \"finished_at\" < :cutoff RETURNING \"id\";")
+            .unwrap()
+            .query_map(
+                named_params! {
+                    ":succeeded": JobStatus::Succeeded,
+                    ":failed": JobStatus::Failed,
+                    ":cancelled": JobStatus::Cancelled,
+                    ":cutoff": cutoff,
+                },
+                |row| row.get("id"),
+            )
+            .expect("Error occurred pruning jobs")
+            .map(|id| id.unwrap())
+            .collect();
+        drop(db);
+
+        for job_id in pruned {
+            let _ = std::fs::remove_dir_all(self.files_directory(job_id));
+        }
+    }
+}
+
+/// A job claimed by a worker, passed to the code doing its work
+#[derive(Clone)]
+pub struct JobContext {
+    pub job: DbJob,
+    /// Cancelled when the job is cancelled while running
+    pub cancel_token: CancellationToken,
+    /// Whether a failure of this run is retried
+    retry: Arc<AtomicBool>,
+    jobs: JobsService,
+}
+
+impl JobContext {
+    /// Parses the payload of the job
+    pub fn payload<T: DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_value(self.job.payload.clone())
+            .map_err(|err| format!("Invalid job payload: {}", err))
+    }
+
+    /// Reports progress as a fraction from 0 to 1
+    pub fn progress(&self, progress: f64, message: Option<&str>) {
+        self.jobs.set_progress(self.job.id, progress, message);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_token.is_cancelled()
+    }
+
+    /// Fails the job for good if this run fails, for failures that would happen the same way
+    /// again such as invalid input
+    pub fn skip_retries(&self) {
+        self.retry.store(false, Ordering::Relaxed);
+    }
+}
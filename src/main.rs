@@ -2,16 +2,32 @@ pub mod api;
 pub mod app;
 pub mod audit;
 pub mod auth;
+pub mod backups;
+pub mod bundles;
 pub mod config;
 pub mod content;
 pub mod database;
+pub mod display_outputs;
 pub mod helpers;
+pub mod imports;
+pub mod jobs;
+pub mod media;
+pub mod previews;
+pub mod run_sheets;
+pub mod schedule;
 pub mod state;
 pub mod tasks;
 pub mod users;
 
+use std::{path::PathBuf, process};
+
 use app::App;
+use audit::AuditService;
 use config::file::AppConfig;
+use database::Database;
+use imports::{model::ImportOptions, service::ImportsService};
+use jobs::service::JobsService;
+use serde_json::json;
 use tokio::signal;
 use tokio_util::sync::CancellationToken;
 
@@ -23,7 +39,24 @@ const CONFIG_FILE: &str = "./config.json";
 pub async fn main() {
     let config = AppConfig::load(CONFIG_FILE).await;
 
-    let app = App::build(&config).await;
+    // `--check` reports pending database migrations without changing anything
+    if std::env::args().skip(1).any(|arg| arg == "--check") {
+        check_database(&config);
+    }
+
+    // `--import-openlp <file>...` imports OpenLP song and custom slide databases and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--import-openlp") {
+        import_openlp(&config, &args[index + 1..]).await;
+    }
+
+    let app = match App::build(&config).await {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
 
     app.services.audit_service.log(None, "startup");
 
@@ -41,6 +74,88 @@ pub async fn main() {
     app.services.audit_service.log(None, "shutdown");
 }
 
+/// Dry-runs database migrations on a copy of the database and exits
+fn check_database(config: &AppConfig) -> ! {
+    match Database::check(config) {
+        Ok(check) => {
+            println!(
+                "Database version {}, latest version {}",
+                check.current_version, check.latest_version
+            );
+            if check.pending.is_empty() {
+                println!("No migrations pending");
+            }
+            for (version, name) in check.pending {
+                println!("Pending migration {}: {}", version, name);
+            }
+            process::exit(0);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Imports OpenLP databases into the database and exits, listing anything skipped
+async fn import_openlp(config: &AppConfig, files: &[String]) -> ! {
+    if files.is_empty() {
+        eprintln!("No OpenLP databases given");
+        process::exit(1);
+    }
+
+    let database = match Database::new(config) {
+        Ok(database) => database,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+    let jobs_service = JobsService::new(&database, config);
+    let imports_service = ImportsService::new(&database, config, &jobs_service);
+    let audit_service = AuditService::new(&database);
+
+    let paths: Vec<PathBuf> = files.iter().map(PathBuf::from).collect();
+    let options = ImportOptions {
+        commit: true,
+        ..Default::default()
+    };
+    let result = imports_service.import_openlp(&paths, &options, None).await;
+
+    audit_service.log_data(
+        None,
+        "import_openlp",
+        json!({
+            "files": files,
+            "slide_deck_id": result
+                .as_ref()
+                .ok()
+                .and_then(|report| report.result.as_ref())
+                .map(|result| result.slide_deck_id),
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(report) => {
+            for warning in &report.warnings {
+                eprintln!("Skipped {}: {}", warning.source, warning.message);
+            }
+            if let Some(result) = report.result {
+                println!(
+                    "Imported {} groups and {} slides into deck {}",
+                    result.slide_groups.created, result.slides.created, result.slide_deck_id
+                );
+            }
+            process::exit(0);
+        }
+        Err(_) => {
+            eprintln!("Error occurred importing OpenLP databases");
+            process::exit(1);
+        }
+    }
+}
+
 async fn shutdown_signal(shutdown_token: CancellationToken) {
     let ctrl_c = async {
         signal::ctrl_c().await.unwrap();
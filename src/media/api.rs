@@ -0,0 +1,530 @@
+use std::{io::SeekFrom, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+};
+
+use super::{db::DbMediaFolder, service::MediaUpdate, variants::MediaVariant};
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/", get(list_media))
+        .route(
+            "/",
+            post(upload_multipart).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/upload",
+            post(upload_stream).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/folders", get(list_folders))
+        .route("/folders", post(create_folder))
+        .route("/folders/:folder_id", put(update_folder))
+        .route("/folders/:folder_id", delete(delete_folder))
+        .route("/:media_id", get(get_media))
+        .route("/:media_id", put(update_media))
+        .route("/:media_id", delete(delete_media))
+        .route("/:media_id/usages", get(get_usages))
+        .route("/:media_id/file", get(get_file))
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    pub folder_id: Option<Uuid>,
+}
+
+pub async fn list_media(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<ListQuery>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let media = state.media_service.list(query.folder_id);
+
+    Json(media).into_response()
+}
+
+/// Uploads every file field of a multipart form into the folder given by a preceding `folder_id` field
+pub async fn upload_multipart(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let mut folder_id = None;
+    let mut media_ids = Vec::new();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        };
+
+        if field.name() == Some("folder_id") {
+            let Ok(Ok(id)) = field.text().await.map(|text| text.parse::<Uuid>()) else {
+                return StatusCode::BAD_REQUEST.into_response();
+            };
+            folder_id = Some(id);
+            continue;
+        }
+
+        let Some(name) = field.file_name().map(String::from) else {
+            continue;
+        };
+        let mime_type = field.content_type().map(String::from);
+
+        let result = state
+            .media_service
+            .store(
+                field,
+                &name,
+                mime_type.as_deref(),
+                folder_id,
+                Some(current_user.id),
+            )
+            .await;
+
+        state.audit_service.log_data(
+            Some(current_user.id),
+            "media_upload",
+            json!({
+                "media_id": result.as_ref().ok(),
+                "name": name,
+                "success": result.is_ok()
+            }),
+        );
+
+        match result {
+            Ok(id) => media_ids.push(id),
+            Err(err) => return err.to_status_code().into_response(),
+        }
+    }
+
+    Json(media_ids).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    pub name: String,
+    pub folder_id: Option<Uuid>,
+}
+
+/// Uploads a single file sent as the raw request body
+pub async fn upload_stream(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<UploadQuery>,
+    token: AuthToken,
+    headers: HeaderMap,
+    body: Body,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let mime_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok());
+
+    let result = state
+        .media_service
+        .store(
+            body.into_data_stream(),
+            &query.name,
+            mime_type,
+            query.folder_id,
+            Some(current_user.id),
+        )
+        .await;
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "media_upload",
+        json!({
+            "media_id": result.as_ref().ok(),
+            "name": query.name,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(id) => Json(id).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn get_media(
+    State(state): State<Arc<AppServices>>,
+    Path(media_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.media_service.get(media_id);
+
+    match result {
+        Some(media) => Json(media).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn update_media(
+    State(state): State<Arc<AppServices>>,
+    Path(media_id): Path<Uuid>,
+    token: AuthToken,
+    Json(request): Json<MediaUpdate>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.media_service.update(media_id, &request);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "media_update",
+        json!({
+            "media_id": media_id,
+            "name": request.name,
+            "folder_id": request.folder_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn delete_media(
+    State(state): State<Arc<AppServices>>,
+    Path(media_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.media_service.delete(media_id).await;
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "media_delete",
+        json!({
+            "media_id": media_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Lists content values referring to a media item
+pub async fn get_usages(
+    State(state): State<Arc<AppServices>>,
+    Path(media_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let usages = state.media_service.usages(media_id);
+
+    Json(usages).into_response()
+}
+
+pub async fn list_folders(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let folders = state.media_service.list_folders();
+
+    Json(folders).into_response()
+}
+
+pub async fn create_folder(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    Json(request): Json<DbMediaFolder>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.media_service.create_folder(&request);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "media_folder_create",
+        json!({
+            "folder_id": result.as_ref().ok(),
+            "name": request.name,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(id) => Json(id).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn update_folder(
+    State(state): State<Arc<AppServices>>,
+    Path(folder_id): Path<Uuid>,
+    token: AuthToken,
+    Json(request): Json<DbMediaFolder>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let mut folder = request.clone();
+    folder.id = Some(folder_id);
+
+    let result = state.media_service.update_folder(&folder);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "media_folder_update",
+        json!({
+            "folder_id": folder_id,
+            "name": folder.name,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn delete_folder(
+    State(state): State<Arc<AppServices>>,
+    Path(folder_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.media_service.delete_folder(folder_id);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "media_folder_delete",
+        json!({
+            "folder_id": folder_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Parses a single byte range, returning `None` for ranges that can't be satisfied
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    let range = range.strip_prefix("bytes=")?.trim();
+    // multiple ranges are not supported, and empty files have no bytes to give
+    if range.contains(',') || size == 0 {
+        return None;
+    }
+    let (start, end) = range.split_once('-')?;
+
+    let (start, end) = if start.is_empty() {
+        // suffix range of the last bytes
+        let length: u64 = end.parse().ok()?;
+        if length == 0 {
+            return None;
+        }
+        (size.saturating_sub(length), size - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            size - 1
+        } else {
+            std::cmp::min(end.parse().ok()?, size - 1)
+        };
+        (start, end)
+    };
+
+    if start > end || start >= size {
+        return None;
+    }
+    Some((start, end))
+}
+
+#[derive(Deserialize)]
+pub struct FileQuery {
+    /// Scaled down copy of an image to serve instead of the original
+    pub variant: Option<MediaVariant>,
+}
+
+/// Serves a media file, supporting range requests and conditional requests by strong ETag.
+/// Displays load media without logging in, so this is not authenticated.
+pub async fn get_file(
+    State(state): State<Arc<AppServices>>,
+    Path(media_id): Path<Uuid>,
+    Query(query): Query<FileQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(media) = state.media_service.get(media_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // variants are only made of images larger than the variant, otherwise the original is served
+    let variant = match query.variant {
+        Some(variant) => state
+            .media_service
+            .variant_file(&media, variant)
+            .await
+            .map(|path| (variant, path)),
+        None => None,
+    };
+
+    let (path, etag, mime_type) = match variant {
+        Some((variant, path)) => {
+            let mime_type = mime_guess::from_path(&path)
+                .first_or_octet_stream()
+                .to_string();
+            let etag = format!("\"{}-{}\"", media.hash, variant.name());
+            (path, etag, mime_type)
+        }
+        None => (
+            state.media_service.file_path(&media.hash),
+            format!("\"{}\"", media.hash),
+            media.mime_type.clone(),
+        ),
+    };
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&format!("max-age={}", state.config.http_caching_max_age)).unwrap(),
+    );
+
+    let etag_matches = |header_name| {
+        headers
+            .get(header_name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|tag| tag.trim() == etag || tag.trim() == "*")
+            })
+    };
+
+    if etag_matches(header::IF_NONE_MATCH) {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
+
+    // ranges only apply if the client still has the same file
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .filter(|_| !headers.contains_key(header::IF_RANGE) || etag_matches(header::IF_RANGE));
+
+    let Ok(mut file) = fs::File::open(&path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let size = file
+        .metadata()
+        .await
+        .expect("Failed to read media file metadata")
+        .len();
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&mime_type)
+            .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    );
+
+    match range {
+        Some(range) => {
+            let Some((start, end)) = parse_range(range, size) else {
+                response_headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes */{}", size)).unwrap(),
+                );
+                return (StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response();
+            };
+
+            file.seek(SeekFrom::Start(start))
+                .await
+                .expect("Failed to seek media file");
+            let length = end - start + 1;
+
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, size)).unwrap(),
+            );
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+
+            (
+                StatusCode::PARTIAL_CONTENT,
+                response_headers,
+                Body::from_stream(ReaderStream::new(file.take(length))),
+            )
+                .into_response()
+        }
+        None => {
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+
+            (
+                StatusCode::OK,
+                response_headers,
+                Body::from_stream(ReaderStream::new(file)),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use rusqlite::Row;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbMedia {
+    pub id: Uuid,
+    pub folder_id: Option<Uuid>,
+    pub name: String,
+    pub mime_type: String,
+    pub size: i64,
+    /// Hex encoded SHA-256 of the file, which is also its name on disk
+    pub hash: String,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub uploaded_by: Option<Uuid>,
+}
+impl DbMedia {
+    pub const TABLE_NAME: &'static str = "media";
+
+    pub const COLUMNS_SQL: &'static str =
+        "\"id\", \"folder_id\", \"name\", \"mime_type\", \"size\", \"hash\", \"width\", \"height\", \"created_at\", \"uploaded_by\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            id: row
+                .get("id")
+                .expect("Failed to get value from database row"),
+            folder_id: row
+                .get("folder_id")
+                .expect("Failed to get value from database row"),
+            name: row
+                .get("name")
+                .expect("Failed to get value from database row"),
+            mime_type: row
+                .get("mime_type")
+                .expect("Failed to get value from database row"),
+            size: row
+                .get("size")
+                .expect("Failed to get value from database row"),
+            hash: row
+                .get("hash")
+                .expect("Failed to get value from database row"),
+            width: row
+                .get("width")
+                .expect("Failed to get value from database row"),
+            height: row
+                .get("height")
+                .expect("Failed to get value from database row"),
+            created_at: row
+                .get("created_at")
+                .expect("Failed to get value from database row"),
+            uploaded_by: row
+                .get("uploaded_by")
+                .expect("Failed to get value from database row"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbMediaFolder {
+    pub id: Option<Uuid>,
+    pub parent_folder_id: Option<Uuid>,
+    pub name: String,
+}
+impl DbMediaFolder {
+    pub const TABLE_NAME: &'static str = "media_folders";
+
+    pub const COLUMNS_SQL: &'static str = "\"id\", \"parent_folder_id\", \"name\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            id: row
+                .get("id")
+                .expect("Failed to get value from database row"),
+            parent_folder_id: row
+                .get("parent_folder_id")
+                .expect("Failed to get value from database row"),
+            name: row
+                .get("name")
+                .expect("Failed to get value from database row"),
+        }
+    }
+}
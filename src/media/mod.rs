@@ -0,0 +1,4 @@
+pub mod api;
+pub mod db;
+pub mod service;
+pub mod variants;
@@ -0,0 +1,522 @@
+use std::path::{Path, PathBuf};
+
+use axum::body::Bytes;
+use chrono::Utc;
+use futures::{Stream, StreamExt};
+use rusqlite::{named_params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::{
+    config::file::AppConfig,
+    content::db::SlideContent,
+    database::Database,
+    helpers::errors::GenericError,
+    jobs::{db::JobType, service::JobsService},
+};
+
+use super::{
+    db::{DbMedia, DbMediaFolder},
+    variants::MediaVariant,
+};
+
+/// Changes to the name or folder of a media item
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MediaUpdate {
+    pub name: String,
+    pub folder_id: Option<Uuid>,
+}
+
+pub struct MediaService {
+    config: AppConfig,
+    db: Database,
+    jobs_service: JobsService,
+}
+
+impl MediaService {
+    pub fn new(database: &Database, config: &AppConfig, jobs_service: &JobsService) -> Self {
+        Self {
+            config: config.clone(),
+            db: database.clone(),
+            jobs_service: jobs_service.clone(),
+        }
+    }
+
+    /// Location of a file by its hash, spread over subdirectories by the first two characters
+    pub fn file_path(&self, hash: &str) -> PathBuf {
+        Path::new(&self.config.media_directory)
+            .join(&hash[..2])
+            .join(hash)
+    }
+
+    /// URL media is served from, used when content refers to media by id
+    pub fn file_url(media_id: Uuid) -> String {
+        format!("/api/media/{}/file", media_id)
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<DbMedia> {
+        let db = self.db.get();
+
+        let media_result = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"{}\" WHERE \"id\" = :id;",
+                DbMedia::COLUMNS_SQL,
+                DbMedia::TABLE_NAME
+            ))
+            .unwrap()
+            .query_row(named_params! {":id": id}, |row| Ok(DbMedia::from_row(row)))
+            .optional()
+            .expect("Error occurred getting media from database");
+
+        media_result
+    }
+
+    /// Lists media in a folder, `None` being the top level
+    pub fn list(&self, folder_id: Option<Uuid>) -> Vec<DbMedia> {
+        let db = self.db.get();
+
+        let media = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"{}\" WHERE \"folder_id\" IS :folder_id ORDER BY \"name\";",
+                DbMedia::COLUMNS_SQL,
+                DbMedia::TABLE_NAME
+            ))
+            .unwrap()
+            .query_map(named_params! {":folder_id": folder_id}, |row| {
+                Ok(DbMedia::from_row(row))
+            })
+            .expect("Error occurred listing media from database")
+            .map(|media| media.unwrap())
+            .collect();
+
+        media
+    }
+
+    /// Stores an uploaded file, failing once it grows past the upload size limit
+    pub async fn store<S, E>(
+        &self,
+        mut stream: S,
+        name: &str,
+        mime_type: Option<&str>,
+        folder_id: Option<Uuid>,
+        uploaded_by: Option<Uuid>,
+    ) -> Result<Uuid, GenericError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+    {
+        if name.is_empty() {
+            return Err(GenericError::BAD_REQUEST);
+        }
+        if folder_id.is_some_and(|folder_id| self.get_folder(folder_id).is_none()) {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        // files are written to a temporary name until their hash is known
+        let temp_path = self.temp_path().await;
+
+        let mut file = fs::File::create(&temp_path)
+            .await
+            .expect("Failed to create media file");
+        let mut hasher = Sha256::new();
+        let mut size: u64 = 0;
+
+        let mut result = Ok(());
+        while let Some(chunk) = stream.next().await {
+            let Ok(chunk) = chunk else {
+                result = Err(GenericError::BAD_REQUEST);
+                break;
+            };
+            size += chunk.len() as u64;
+            if size > self.config.media_max_upload_size {
+                result = Err(GenericError::PAYLOAD_TOO_LARGE);
+                break;
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk)
+                .await
+                .expect("Failed to write media file");
+        }
+        file.flush().await.expect("Failed to write media file");
+        drop(file);
+
+        if let Err(err) = result {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(err);
+        }
+
+        let hash = hex::encode(hasher.finalize());
+        let path = self.file_path(&hash);
+
+        // identical files are only stored once
+        if fs::try_exists(&path).await.unwrap_or(false) {
+            let _ = fs::remove_file(&temp_path).await;
+        } else {
+            fs::create_dir_all(path.parent().unwrap())
+                .await
+                .expect("Failed to create media directory");
+            fs::rename(&temp_path, &path)
+                .await
+                .expect("Failed to move media file into place");
+        }
+
+        // generic types sent by clients that don't know better are guessed from the name instead
+        let mime_type = match mime_type {
+            Some(mime_type)
+                if !matches!(
+                    mime_type,
+                    "application/octet-stream" | "application/x-www-form-urlencoded"
+                ) =>
+            {
+                mime_type.to_owned()
+            }
+            _ => mime_guess::from_path(name)
+                .first_or_octet_stream()
+                .to_string(),
+        };
+
+        let dimensions = if mime_type.starts_with("image/") {
+            let image_path = path.clone();
+            tokio::task::spawn_blocking(move || {
+                image::ImageReader::open(image_path)
+                    .ok()?
+                    .with_guessed_format()
+                    .ok()?
+                    .into_dimensions()
+                    .ok()
+            })
+            .await
+            .unwrap()
+        } else {
+            None
+        };
+
+        let media_id = Uuid::new_v4();
+
+        let db = self.db.get();
+        db.prepare_cached("INSERT INTO \"media\" (\"id\", \"folder_id\", \"name\", \"mime_type\", \"size\", \"hash\", \"width\", \"height\", \"created_at\", \"uploaded_by\") VALUES (:id, :folder_id, :name, :mime_type, :size, :hash, :width, :height, :created_at, :uploaded_by);")
+            .unwrap()
+            .execute(named_params! {
+                ":id": media_id,
+                ":folder_id": folder_id,
+                ":name": name,
+                ":mime_type": mime_type,
+                ":size": size,
+                ":hash": hash,
+                ":width": dimensions.map(|(width, _)| width),
+                ":height": dimensions.map(|(_, height)| height),
+                ":created_at": Utc::now(),
+                ":uploaded_by": uploaded_by,
+            })
+            .expect("Error occurred adding media to database");
+
+        if mime_type.starts_with("image/") {
+            self.queue_variants(media_id, uploaded_by);
+        }
+
+        Ok(media_id)
+    }
+
+    /// Gets a unique path for a temporary file in the media directory,
+    /// from where it can be moved into place
+    pub async fn temp_path(&self) -> PathBuf {
+        let temp_directory = Path::new(&self.config.media_directory).join("tmp");
+        fs::create_dir_all(&temp_directory)
+            .await
+            .expect("Failed to create media directory");
+        temp_directory.join(Uuid::new_v4().to_string())
+    }
+
+    /// Queues generating the variants of an image in the background so adding it returns quickly
+    pub fn queue_variants(&self, media_id: Uuid, created_by: Option<Uuid>) {
+        self.jobs_service.enqueue(
+            JobType::MediaVariants,
+            json!({ "media_id": media_id }),
+            created_by,
+        );
+    }
+
+    /// Generates all missing variants of an image
+    pub async fn generate_variants(&self, media_id: Uuid) {
+        if let Some(media) = self.get(media_id) {
+            self.generate(&media, &MediaVariant::ALL).await;
+        }
+    }
+
+    /// Gets the file of a variant, generating it if it is missing.
+    /// Returns `None` if the original should be served instead.
+    pub async fn variant_file(&self, media: &DbMedia, variant: MediaVariant) -> Option<PathBuf> {
+        if !media.mime_type.starts_with("image/") {
+            return None;
+        }
+
+        // images already within the variant size are served as they are, without decoding them
+        if let (Some(width), Some(height)) = (media.width, media.height) {
+            if variant.fits(width as u32, height as u32, &self.config) {
+                return None;
+            }
+        }
+
+        let original = self.file_path(&media.hash);
+        if let Some(path) = variant.existing_path(&original) {
+            return Some(path);
+        }
+
+        self.generate(media, &[variant]).await.into_iter().next()
+    }
+
+    /// Generates variants that don't exist yet, returning the paths of those that apply
+    async fn generate(&self, media: &DbMedia, variants: &[MediaVariant]) -> Vec<PathBuf> {
+        let original = self.file_path(&media.hash);
+        let variants = variants.to_vec();
+        let config = self.config.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let missing: Vec<_> = variants
+                .iter()
+                .filter(|variant| variant.existing_path(&original).is_none())
+                .collect();
+            let mut paths: Vec<_> = variants
+                .iter()
+                .filter_map(|variant| variant.existing_path(&original))
+                .collect();
+            if missing.is_empty() {
+                return paths;
+            }
+
+            let Some(image) = image::ImageReader::open(&original)
+                .ok()
+                .and_then(|reader| reader.with_guessed_format().ok())
+                .and_then(|reader| reader.decode().ok())
+            else {
+                return paths;
+            };
+
+            for variant in missing {
+                let Some((resized, format)) = variant.generate(&image, &config) else {
+                    continue;
+                };
+
+                // written under a temporary name as requests may generate the same variant
+                let path = variant.path(&original, format);
+                let temp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+                if resized.save_with_format(&temp_path, format).is_ok()
+                    && std::fs::rename(&temp_path, &path).is_ok()
+                {
+                    paths.push(path);
+                } else {
+                    let _ = std::fs::remove_file(&temp_path);
+                }
+            }
+
+            paths
+        })
+        .await
+        .unwrap()
+    }
+
+    pub fn update(&self, id: Uuid, update: &MediaUpdate) -> Result<(), GenericError> {
+        if update.name.is_empty() {
+            return Err(GenericError::BAD_REQUEST);
+        }
+        if update
+            .folder_id
+            .is_some_and(|folder_id| self.get_folder(folder_id).is_none())
+        {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        let db = self.db.get();
+
+        let rows = db
+            .prepare_cached(
+                "UPDATE \"media\" SET \"name\" = :name, \"folder_id\" = :folder_id WHERE \"id\" = :id;",
+            )
+            .unwrap()
+            .execute(named_params! {
+                ":id": id,
+                ":name": update.name,
+                ":folder_id": update.folder_id,
+            })
+            .expect("Error occurred updating media");
+
+        if rows == 0 {
+            return Err(GenericError::NOT_FOUND);
+        }
+        Ok(())
+    }
+
+    /// Lists content values referring to a media item
+    pub fn usages(&self, id: Uuid) -> Vec<SlideContent> {
+        let db = self.db.get();
+
+        let usages = db
+            .prepare_cached(&format!(
+                "SELECT * FROM ({}) WHERE instr(\"value\", :media_id) > 0;",
+                SlideContent::UNION_SELECT
+            ))
+            .unwrap()
+            .query_map(named_params! {":media_id": id.to_string()}, |row| {
+                Ok(SlideContent::from_row(row))
+            })
+            .expect("Error occurred finding media usages in database")
+            .map(|usage| usage.unwrap())
+            .collect();
+
+        usages
+    }
+
+    /// Deletes a media item that is not used by any content,
+    /// removing the file once no other media item shares it
+    pub async fn delete(&self, id: Uuid) -> Result<(), GenericError> {
+        let Some(media) = self.get(id) else {
+            return Err(GenericError::NOT_FOUND);
+        };
+        if !self.usages(id).is_empty() {
+            return Err(GenericError::CONFLICT);
+        }
+
+        let shared = {
+            let db = self.db.get();
+
+            db.prepare_cached("DELETE FROM \"media\" WHERE \"id\" = :id;")
+                .unwrap()
+                .execute(named_params! {":id": id})
+                .expect("Error occurred deleting media");
+
+            let shared = db
+                .prepare_cached("SELECT 1 FROM \"media\" WHERE \"hash\" = :hash;")
+                .unwrap()
+                .exists(named_params! {":hash": media.hash})
+                .expect("Error occurred checking media files in database");
+            shared
+        };
+
+        if !shared {
+            let original = self.file_path(&media.hash);
+            for variant in MediaVariant::ALL {
+                if let Some(path) = variant.existing_path(&original) {
+                    let _ = fs::remove_file(path).await;
+                }
+            }
+            let _ = fs::remove_file(original).await;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_folder(&self, id: Uuid) -> Option<DbMediaFolder> {
+        let db = self.db.get();
+
+        let folder_result = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"{}\" WHERE \"id\" = :id;",
+                DbMediaFolder::COLUMNS_SQL,
+                DbMediaFolder::TABLE_NAME
+            ))
+            .unwrap()
+            .query_row(named_params! {":id": id}, |row| {
+                Ok(DbMediaFolder::from_row(row))
+            })
+            .optional()
+            .expect("Error occurred getting media folder from database");
+
+        folder_result
+    }
+
+    pub fn list_folders(&self) -> Vec<DbMediaFolder> {
+        let db = self.db.get();
+
+        let folders = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"{}\" ORDER BY \"name\";",
+                DbMediaFolder::COLUMNS_SQL,
+                DbMediaFolder::TABLE_NAME
+            ))
+            .unwrap()
+            .query_map(named_params! {}, |row| Ok(DbMediaFolder::from_row(row)))
+            .expect("Error occurred listing media folders from database")
+            .map(|folder| folder.unwrap())
+            .collect();
+
+        folders
+    }
+
+    pub fn create_folder(&self, folder: &DbMediaFolder) -> Result<Uuid, GenericError> {
+        let id = Uuid::new_v4();
+
+        let db = self.db.get();
+
+        let success = db
+            .prepare_cached("INSERT INTO \"media_folders\" (\"id\", \"parent_folder_id\", \"name\") VALUES (:id, :parent_folder_id, :name);")
+            .unwrap()
+            .execute(named_params! {
+                ":id": id,
+                ":parent_folder_id": folder.parent_folder_id,
+                ":name": folder.name,
+            })
+            .is_ok();
+
+        if success {
+            Ok(id)
+        } else {
+            Err(GenericError::BAD_REQUEST)
+        }
+    }
+
+    pub fn update_folder(&self, folder: &DbMediaFolder) -> Result<(), GenericError> {
+        let Some(id) = folder.id else {
+            return Err(GenericError::BAD_REQUEST);
+        };
+        if self.get_folder(id).is_none() {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        // folders can't be moved into themselves
+        let mut parent_id = folder.parent_folder_id;
+        while let Some(ancestor_id) = parent_id {
+            if ancestor_id == id {
+                return Err(GenericError::BAD_REQUEST);
+            }
+            parent_id = self
+                .get_folder(ancestor_id)
+                .and_then(|ancestor| ancestor.parent_folder_id);
+        }
+
+        let db = self.db.get();
+
+        let success = db
+            .prepare_cached("UPDATE \"media_folders\" SET \"parent_folder_id\" = :parent_folder_id, \"name\" = :name WHERE \"id\" = :id;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": id,
+                ":parent_folder_id": folder.parent_folder_id,
+                ":name": folder.name,
+            })
+            .is_ok();
+
+        if success {
+            Ok(())
+        } else {
+            Err(GenericError::BAD_REQUEST)
+        }
+    }
+
+    /// Deletes a folder and its subfolders, moving their media to the top level
+    pub fn delete_folder(&self, id: Uuid) -> Result<(), GenericError> {
+        let db = self.db.get();
+
+        let rows = db
+            .prepare_cached("DELETE FROM \"media_folders\" WHERE \"id\" = :id;")
+            .unwrap()
+            .execute(named_params! {":id": id})
+            .expect("Error occurred deleting media folder");
+
+        if rows == 0 {
+            return Err(GenericError::NOT_FOUND);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::config::file::AppConfig;
+
+/// Smaller copies of image media for previews and displays
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaVariant {
+    Thumbnail,
+    Display,
+}
+
+impl MediaVariant {
+    pub const ALL: [MediaVariant; 2] = [Self::Thumbnail, Self::Display];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Thumbnail => "thumbnail",
+            Self::Display => "display",
+        }
+    }
+
+    /// Box the variant is scaled down to fit in
+    pub fn max_size(&self, config: &AppConfig) -> (u32, u32) {
+        match self {
+            Self::Thumbnail => (config.media_thumbnail_size, config.media_thumbnail_size),
+            Self::Display => (config.media_display_width, config.media_display_height),
+        }
+    }
+
+    /// Location of the variant next to the original, images with transparency being kept as PNG
+    pub fn path(&self, original: &Path, format: ImageFormat) -> PathBuf {
+        let extension = match format {
+            ImageFormat::Png => "png",
+            _ => "jpg",
+        };
+        let mut file_name = original.file_name().unwrap().to_owned();
+        file_name.push(format!(".{}.{}", self.name(), extension));
+        original.with_file_name(file_name)
+    }
+
+    /// Finds an already generated variant
+    pub fn existing_path(&self, original: &Path) -> Option<PathBuf> {
+        [ImageFormat::Jpeg, ImageFormat::Png]
+            .into_iter()
+            .map(|format| self.path(original, format))
+            .find(|path| path.exists())
+    }
+
+    /// Whether an image of the given size already fits the variant, so the original can be used
+    pub fn fits(&self, width: u32, height: u32, config: &AppConfig) -> bool {
+        let (max_width, max_height) = self.max_size(config);
+        width <= max_width && height <= max_height
+    }
+
+    /// Scales an image down to the variant size, returning `None` if it already fits
+    pub fn generate(
+        &self,
+        image: &DynamicImage,
+        config: &AppConfig,
+    ) -> Option<(DynamicImage, ImageFormat)> {
+        if self.fits(image.width(), image.height(), config) {
+            return None;
+        }
+
+        let (max_width, max_height) = self.max_size(config);
+
+        let resized = image.resize(max_width, max_height, FilterType::Triangle);
+        if resized.color().has_alpha() {
+            Some((resized, ImageFormat::Png))
+        } else {
+            Some((
+                DynamicImage::ImageRgb8(resized.into_rgb8()),
+                ImageFormat::Jpeg,
+            ))
+        }
+    }
+}
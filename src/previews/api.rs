@@ -0,0 +1,157 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+    content::db::ContentVisibility,
+    database::display_outputs::OutputKind,
+    media::variants::MediaVariant,
+    state::models::DisplayState,
+};
+
+use super::service::{PreviewFormat, PreviewKey, PreviewsService};
+
+const MAX_PREVIEW_SIZE: u32 = 3840;
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new().route("/slides/:slide_deck_slide_id", get(get_slide_preview))
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    /// Display output to preview the slide as, using its languages and visibility
+    pub output_id: Option<Uuid>,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default)]
+    pub format: PreviewFormat,
+}
+
+fn default_width() -> u32 {
+    480
+}
+
+fn default_height() -> u32 {
+    270
+}
+
+/// Renders a deck slide as it would appear on a display output
+pub async fn get_slide_preview(
+    State(state): State<Arc<AppServices>>,
+    Path(slide_deck_slide_id): Path<Uuid>,
+    Query(query): Query<PreviewQuery>,
+    headers: HeaderMap,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    if !(1..=MAX_PREVIEW_SIZE).contains(&query.width)
+        || !(1..=MAX_PREVIEW_SIZE).contains(&query.height)
+    {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let output = match query.output_id {
+        Some(output_id) => match state.display_outputs_service.get(output_id) {
+            Some(output) => Some(output),
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+        None => None,
+    };
+
+    let Some(slide) = state
+        .content_service
+        .resolve(slide_deck_slide_id, query.output_id)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // previews show what the audience (or the stage) of the output would see
+    let access = match output {
+        Some(output) if output.kind == OutputKind::Stage => ContentVisibility::Stage,
+        _ => ContentVisibility::Public,
+    };
+    let mut display = DisplayState {
+        content: state.visibility_service.filter(&slide.content, access),
+        slide_type_id: slide.slide_type_id,
+        rendered: None,
+    };
+    state.template_service.render_display(&mut display);
+
+    let markup = display.rendered.as_deref().unwrap_or_default();
+    let mut media_files = HashMap::new();
+    for media_id in display
+        .content
+        .values()
+        .flat_map(|value| PreviewsService::referenced_media(value))
+        .chain(PreviewsService::referenced_media(markup))
+    {
+        if media_files.contains_key(&media_id) {
+            continue;
+        }
+        let Some(media) = state.media_service.get(media_id) else {
+            continue;
+        };
+        let path = match state
+            .media_service
+            .variant_file(&media, MediaVariant::Display)
+            .await
+        {
+            Some(path) => path,
+            None => state.media_service.file_path(&media.hash),
+        };
+        media_files.insert(media_id, path);
+    }
+
+    let key = PreviewKey {
+        slide_deck_slide_id,
+        display_output_id: query.output_id,
+        width: query.width,
+        height: query.height,
+        format: query.format,
+    };
+    let preview = match state
+        .previews_service
+        .render(key, &display, media_files)
+        .await
+    {
+        Ok(preview) => preview,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    let etag = format!("\"{}\"", preview.hash);
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    let etag_matches = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if etag_matches {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(query.format.mime_type()),
+    );
+
+    (StatusCode::OK, response_headers, preview.data).into_response()
+}
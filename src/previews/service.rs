@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use resvg::{
+    tiny_skia,
+    usvg::{self, fontdb, ImageHrefResolver, ImageKind},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::{config::file::AppConfig, helpers::errors::GenericError, state::models::DisplayState};
+
+use super::svg;
+
+const MEDIA_URL_PREFIX: &str = "/api/media/";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl PreviewFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Svg => "image/svg+xml",
+        }
+    }
+}
+
+/// A slide preview as requested, used to find previews that became outdated
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PreviewKey {
+    pub slide_deck_slide_id: Uuid,
+    pub display_output_id: Option<Uuid>,
+    pub width: u32,
+    pub height: u32,
+    pub format: PreviewFormat,
+}
+
+pub struct Preview {
+    /// Hash of everything the preview was drawn from
+    pub hash: String,
+    pub data: Vec<u8>,
+}
+
+pub struct PreviewsService {
+    config: AppConfig,
+    fontdb: Arc<fontdb::Database>,
+    /// Hash of the latest preview of each slide, so older ones can be removed
+    latest: Mutex<HashMap<PreviewKey, String>>,
+}
+
+impl PreviewsService {
+    pub fn new(config: &AppConfig) -> Self {
+        let mut fontdb = fontdb::Database::new();
+        fontdb.load_system_fonts();
+
+        // the generic family defaults to Arial, which most servers don't have
+        let sans_serif = fontdb::Query {
+            families: &[fontdb::Family::SansSerif],
+            ..Default::default()
+        };
+        if fontdb.query(&sans_serif).is_none() {
+            let families: Vec<String> = fontdb
+                .faces()
+                .filter_map(|face| face.families.first())
+                .map(|(family, _)| family.clone())
+                .collect();
+            let fallback = families
+                .iter()
+                .find(|family| family.ends_with("Sans"))
+                .or(families.first())
+                .cloned();
+            if let Some(fallback) = fallback {
+                fontdb.set_sans_serif_family(fallback);
+            }
+        }
+
+        Self {
+            config: config.clone(),
+            fontdb: Arc::new(fontdb),
+            latest: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gets the media ids referred to by URL in markup
+    pub fn referenced_media(markup: &str) -> Vec<Uuid> {
+        markup
+            .match_indices(MEDIA_URL_PREFIX)
+            .filter_map(|(index, _)| {
+                markup
+                    .get(index + MEDIA_URL_PREFIX.len()..)
+                    .and_then(|rest| rest.get(..36))
+                    .and_then(|id| id.parse().ok())
+            })
+            .collect()
+    }
+
+    fn cache_path(&self, hash: &str, format: PreviewFormat) -> PathBuf {
+        Path::new(&self.config.preview_cache_directory)
+            .join(&hash[..2])
+            .join(format!("{}.{}", hash, format.extension()))
+    }
+
+    /// Renders a slide, reusing the cached preview if nothing it is drawn from has changed.
+    /// Media files referenced by the slide are looked up in `media_files`.
+    pub async fn render(
+        &self,
+        key: PreviewKey,
+        display: &DisplayState,
+        media_files: HashMap<Uuid, PathBuf>,
+    ) -> Result<Preview, GenericError> {
+        let markup = svg::slide_svg(display, key.width, key.height);
+
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{}:{}x{}:",
+            key.format.extension(),
+            key.width,
+            key.height
+        ));
+        hasher.update(&markup);
+        let hash = hex::encode(hasher.finalize());
+
+        let path = self.cache_path(&hash, key.format);
+
+        let data = match fs::read(&path).await {
+            Ok(data) => data,
+            Err(_) => {
+                let data = match key.format {
+                    PreviewFormat::Svg => markup.into_bytes(),
+                    PreviewFormat::Png => {
+                        let fontdb = self.fontdb.clone();
+                        tokio::task::spawn_blocking(move || {
+                            Self::rasterize(&markup, key.width, key.height, fontdb, media_files)
+                        })
+                        .await
+                        .unwrap()
+                        .ok_or(GenericError::BAD_REQUEST)?
+                    }
+                };
+
+                fs::create_dir_all(path.parent().unwrap())
+                    .await
+                    .expect("Failed to create preview cache directory");
+                fs::write(&path, &data)
+                    .await
+                    .expect("Failed to write preview to cache");
+
+                data
+            }
+        };
+
+        // previews of content that has since changed won't be requested again
+        let previous = self.latest.lock().unwrap().insert(key, hash.clone());
+        if let Some(previous) = previous.filter(|previous| *previous != hash) {
+            let _ = fs::remove_file(self.cache_path(&previous, key.format)).await;
+        }
+
+        Ok(Preview { hash, data })
+    }
+
+    /// Draws SVG markup into a PNG, fitting it into the size
+    fn rasterize(
+        markup: &str,
+        width: u32,
+        height: u32,
+        fontdb: Arc<fontdb::Database>,
+        media_files: HashMap<Uuid, PathBuf>,
+    ) -> Option<Vec<u8>> {
+        // only media files can be loaded, never arbitrary paths on the server
+        let resolve_string = move |href: &str, _: &usvg::Options| {
+            let media_id = Self::referenced_media(href).into_iter().next()?;
+            let data = std::fs::read(media_files.get(&media_id)?).ok()?;
+            match image::guess_format(&data).ok()? {
+                image::ImageFormat::Png => Some(ImageKind::PNG(Arc::new(data))),
+                image::ImageFormat::Jpeg => Some(ImageKind::JPEG(Arc::new(data))),
+                image::ImageFormat::Gif => Some(ImageKind::GIF(Arc::new(data))),
+                image::ImageFormat::WebP => Some(ImageKind::WEBP(Arc::new(data))),
+                _ => None,
+            }
+        };
+
+        let options = usvg::Options {
+            fontdb,
+            image_href_resolver: ImageHrefResolver {
+                resolve_string: Box::new(resolve_string),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let tree = usvg::Tree::from_str(markup, &options).ok()?;
+
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+        let tree_size = tree.size();
+        let scale = f32::min(
+            width as f32 / tree_size.width(),
+            height as f32 / tree_size.height(),
+        );
+        let transform = tiny_skia::Transform::from_translate(
+            (width as f32 - tree_size.width() * scale) / 2.0,
+            (height as f32 - tree_size.height() * scale) / 2.0,
+        )
+        .pre_scale(scale, scale);
+
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+        pixmap.encode_png().ok()
+    }
+}
@@ -0,0 +1,99 @@
+use crate::state::models::DisplayState;
+
+/// Size of the canvas slides are laid out on, scaled to the requested size
+pub const CANVAS_WIDTH: u32 = 1920;
+pub const CANVAS_HEIGHT: u32 = 1080;
+
+const TITLE_FONT_SIZE: u32 = 72;
+const BODY_FONT_SIZE: u32 = 64;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Draws a slide as SVG.
+///
+/// Slides whose template renders SVG are used as they are, other slides get a plain layout of
+/// their `background` colour or media URL, `title`, and `body` (or `text`) lines.
+pub fn slide_svg(display: &DisplayState, width: u32, height: u32) -> String {
+    if let Some(rendered) = display
+        .rendered
+        .as_ref()
+        .map(|rendered| rendered.trim_start())
+        .filter(|rendered| rendered.starts_with("<svg"))
+    {
+        return rendered.to_owned();
+    }
+
+    let content = &display.content;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        width, height, CANVAS_WIDTH, CANVAS_HEIGHT
+    );
+
+    let background = content.get("background").map(|value| value.trim());
+    match background {
+        Some(background) if background.starts_with('/') => {
+            svg.push_str(&format!(
+                "<rect width=\"100%\" height=\"100%\" fill=\"black\"/><image width=\"{}\" height=\"{}\" preserveAspectRatio=\"xMidYMid slice\" xlink:href=\"{}\"/>",
+                CANVAS_WIDTH,
+                CANVAS_HEIGHT,
+                escape(background)
+            ));
+        }
+        Some(background) if !background.is_empty() => {
+            svg.push_str(&format!(
+                "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+                escape(background)
+            ));
+        }
+        _ => svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"black\"/>"),
+    }
+
+    let color = content
+        .get("color")
+        .map(|color| escape(color))
+        .unwrap_or(String::from("white"));
+
+    if let Some(title) = content.get("title").filter(|title| !title.is_empty()) {
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" font-weight=\"bold\" fill=\"{}\" text-anchor=\"middle\">{}</text>",
+            CANVAS_WIDTH / 2,
+            TITLE_FONT_SIZE * 2,
+            TITLE_FONT_SIZE,
+            color,
+            escape(title)
+        ));
+    }
+
+    // body lines are centred on the canvas
+    let body = content.get("body").or_else(|| content.get("text"));
+    if let Some(body) = body {
+        let lines: Vec<&str> = body.lines().collect();
+        let line_height = BODY_FONT_SIZE * 5 / 4;
+        let first_line_y = (CANVAS_HEIGHT as i64 - (lines.len() as i64 - 1) * line_height as i64)
+            / 2
+            + BODY_FONT_SIZE as i64 / 3;
+
+        svg.push_str(&format!(
+            "<text font-family=\"sans-serif\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\">",
+            BODY_FONT_SIZE, color
+        ));
+        for (index, line) in lines.iter().enumerate() {
+            svg.push_str(&format!(
+                "<tspan x=\"{}\" y=\"{}\">{}</tspan>",
+                CANVAS_WIDTH / 2,
+                first_line_y + index as i64 * line_height as i64,
+                escape(line)
+            ));
+        }
+        svg.push_str("</text>");
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
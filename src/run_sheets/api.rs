@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+};
+
+use super::service::RunSheet;
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/", get(list_run_sheets))
+        .route("/", post(create_run_sheet))
+        .route("/:run_sheet_id", get(get_run_sheet))
+        .route("/:run_sheet_id", put(update_run_sheet))
+        .route("/:run_sheet_id", delete(delete_run_sheet))
+        .route("/:run_sheet_id/live", get(get_live))
+        .route("/:run_sheet_id/current", put(set_current))
+        .route("/:run_sheet_id/advance", post(advance))
+        .route("/:run_sheet_id/back", post(back))
+        .route("/:run_sheet_id/reset", post(reset))
+}
+
+pub async fn list_run_sheets(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let run_sheets = state.run_sheets_service.list();
+
+    Json(run_sheets).into_response()
+}
+
+pub async fn get_run_sheet(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) =
+        token.authorize(&state, UserPermission::SETUP | UserPermission::OPERATION)
+    else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.run_sheets_service.get(run_sheet_id);
+
+    match result {
+        Some(run_sheet) => Json(run_sheet).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn create_run_sheet(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    Json(request): Json<RunSheet>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.run_sheets_service.create(&request);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "run_sheet_create",
+        json!({
+            "run_sheet_id": result.as_ref().ok(),
+            "name": request.name,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(id) => Json(id).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn update_run_sheet(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+    token: AuthToken,
+    Json(request): Json<RunSheet>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let mut run_sheet = request.clone();
+    run_sheet.id = Some(run_sheet_id);
+
+    let result = state.run_sheets_service.update(&run_sheet);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "run_sheet_update",
+        json!({
+            "run_sheet_id": run_sheet_id,
+            "name": run_sheet.name,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn delete_run_sheet(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::SETUP) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.run_sheets_service.delete(run_sheet_id);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "run_sheet_delete",
+        json!({
+            "run_sheet_id": run_sheet_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Gets the now, next and after items of a run sheet
+pub async fn get_live(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let result = state.run_sheets_service.get_live(run_sheet_id);
+
+    match result {
+        Some(live) => Json(live).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn set_current(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+    token: AuthToken,
+    Json(request): Json<Option<Uuid>>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::OPERATION) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.run_sheets_service.set_current(run_sheet_id, request);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "run_sheet_set_current",
+        json!({
+            "run_sheet_id": run_sheet_id,
+            "item_id": request,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn reset(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::OPERATION) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.run_sheets_service.reset(run_sheet_id);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "run_sheet_reset",
+        json!({
+            "run_sheet_id": run_sheet_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn advance(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    step(&state, run_sheet_id, &token, true)
+}
+
+pub async fn back(
+    State(state): State<Arc<AppServices>>,
+    Path(run_sheet_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    step(&state, run_sheet_id, &token, false)
+}
+
+fn step(
+    state: &AppServices,
+    run_sheet_id: Uuid,
+    token: &AuthToken,
+    forward: bool,
+) -> axum::response::Response {
+    let Ok(Some(current_user)) = token.authorize(state, UserPermission::OPERATION) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.run_sheets_service.step(run_sheet_id, forward);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        if forward {
+            "run_sheet_advance"
+        } else {
+            "run_sheet_back"
+        },
+        json!({
+            "run_sheet_id": run_sheet_id,
+            "item_id": result.as_ref().ok(),
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(item_id) => Json(item_id).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::db::{DbRunSheet, DbRunSheetItem};
+
+/// Timing of a run sheet against its plan. Durations are in seconds.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShowClock {
+    /// Server time the clock was computed at
+    pub timestamp: DateTime<Utc>,
+    /// Actual start of the first item
+    pub started_at: Option<DateTime<Utc>>,
+    pub current_item_started_at: Option<DateTime<Utc>>,
+    /// Time left of the current item's planned duration, negative once it runs over
+    pub current_item_remaining: Option<i64>,
+    /// How far the show is behind plan, negative when running short
+    pub over_under: i64,
+    /// End of the show if remaining items run to plan
+    pub projected_end: Option<DateTime<Utc>>,
+    pub hard_out: Option<DateTime<Utc>>,
+    /// Time between the projected end and the hard out, negative when the show would overrun
+    pub hard_out_margin: Option<i64>,
+    pub items: Vec<ItemTiming>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ItemTiming {
+    pub item_id: Uuid,
+    /// Start according to plan, counting from the actual start of the show
+    pub planned_start: Option<DateTime<Utc>>,
+    pub actual_start: Option<DateTime<Utc>>,
+    /// Latest start that still hits the hard out if everything from this item on runs to plan
+    pub backtimed_start: Option<DateTime<Utc>>,
+}
+
+/// Planned duration of an item, or `None` if it is too long to compute with
+fn planned(item: &DbRunSheetItem) -> Option<TimeDelta> {
+    TimeDelta::try_seconds(item.planned_duration)
+}
+
+/// Total planned duration of items, or `None` if it is too long to compute with
+fn planned_total(items: &[DbRunSheetItem]) -> Option<TimeDelta> {
+    items.iter().try_fold(TimeDelta::zero(), |total, item| {
+        total.checked_add(&planned(item)?)
+    })
+}
+
+impl ShowClock {
+    /// Computes the clock of a run sheet. Times that can't be computed, such as from durations
+    /// too long to add up, are left out rather than failing.
+    pub fn compute(
+        run_sheet: &DbRunSheet,
+        items: &[DbRunSheetItem],
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        let current_index = run_sheet
+            .current_item_id
+            .and_then(|current| items.iter().position(|item| item.id == current));
+
+        let first_started = items.iter().position(|item| item.started_at.is_some());
+        let started_at = first_started.and_then(|index| items[index].started_at);
+
+        // the plan is anchored on the first item that went live, even if earlier ones were skipped
+        let planned_before_first = planned_total(&items[..first_started.unwrap_or(0)]);
+
+        // planned and backtimed starts are running sums from the front and back respectively
+        let mut planned_start = started_at
+            .zip(planned_before_first)
+            .and_then(|(start, before)| start.checked_sub_signed(before));
+        let mut remaining_after = planned_total(items);
+        let item_timings = items
+            .iter()
+            .map(|item| {
+                let timing = ItemTiming {
+                    item_id: item.id,
+                    planned_start,
+                    actual_start: item.started_at,
+                    backtimed_start: run_sheet
+                        .hard_out
+                        .zip(remaining_after)
+                        .and_then(|(hard_out, remaining)| hard_out.checked_sub_signed(remaining)),
+                };
+                planned_start = planned_start
+                    .zip(planned(item))
+                    .and_then(|(start, duration)| start.checked_add_signed(duration));
+                remaining_after = remaining_after
+                    .zip(planned(item))
+                    .and_then(|(remaining, duration)| remaining.checked_sub(&duration));
+                timing
+            })
+            .collect::<Vec<_>>();
+
+        let current = current_index.map(|index| (&items[index], &item_timings[index]));
+
+        let current_item_started_at = current.and_then(|(item, _)| item.started_at);
+
+        // time left of an item against its plan, negative once it runs over
+        let time_left = |item: &DbRunSheetItem, started_at: DateTime<Utc>| {
+            planned(item)?.checked_sub(&(timestamp - started_at))
+        };
+
+        let current_item_remaining = current.and_then(|(item, _)| {
+            time_left(item, item.started_at?).map(|remaining| remaining.num_seconds())
+        });
+
+        let over_under = current
+            .and_then(|(item, timing)| {
+                let actual_start = item.started_at?;
+                let late_start = actual_start - timing.planned_start?;
+                let overrun = std::cmp::max(TimeDelta::zero(), -time_left(item, actual_start)?);
+                late_start.checked_add(&overrun)
+            })
+            .map_or(0, |over_under| over_under.num_seconds());
+
+        let projected_end = current_index.and_then(|index| {
+            let item = &items[index];
+            let current_left = std::cmp::max(TimeDelta::zero(), time_left(item, item.started_at?)?);
+            let rest = planned_total(&items[index + 1..])?;
+            timestamp
+                .checked_add_signed(current_left)?
+                .checked_add_signed(rest)
+        });
+
+        let hard_out_margin = match (run_sheet.hard_out, projected_end) {
+            (Some(hard_out), Some(projected_end)) => Some((hard_out - projected_end).num_seconds()),
+            _ => None,
+        };
+
+        Self {
+            timestamp,
+            started_at,
+            current_item_started_at,
+            current_item_remaining,
+            over_under,
+            projected_end,
+            hard_out: run_sheet.hard_out,
+            hard_out_margin,
+            items: item_timings,
+        }
+    }
+}
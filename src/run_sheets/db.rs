@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use rusqlite::Row;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbRunSheet {
+    pub id: Uuid,
+    pub name: String,
+    pub display_output_id: Option<Uuid>,
+    pub current_item_id: Option<Uuid>,
+    pub hard_out: Option<DateTime<Utc>>,
+}
+impl DbRunSheet {
+    pub const TABLE_NAME: &'static str = "run_sheets";
+
+    pub const COLUMNS_SQL: &'static str =
+        "\"id\", \"name\", \"display_output_id\", \"current_item_id\", \"hard_out\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            id: row
+                .get("id")
+                .expect("Failed to get value from database row"),
+            name: row
+                .get("name")
+                .expect("Failed to get value from database row"),
+            display_output_id: row
+                .get("display_output_id")
+                .expect("Failed to get value from database row"),
+            current_item_id: row
+                .get("current_item_id")
+                .expect("Failed to get value from database row"),
+            hard_out: row
+                .get("hard_out")
+                .expect("Failed to get value from database row"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbRunSheetItem {
+    pub id: Uuid,
+    pub run_sheet_id: Uuid,
+    pub order: i64,
+    pub name: String,
+    pub slide_deck_id: Option<Uuid>,
+    pub planned_duration: i64,
+    pub owner: String,
+    pub notes: String,
+    pub started_at: Option<DateTime<Utc>>,
+}
+impl DbRunSheetItem {
+    pub const TABLE_NAME: &'static str = "run_sheet_items";
+
+    pub const COLUMNS_SQL: &'static str =
+        "\"id\", \"run_sheet_id\", \"order\", \"name\", \"slide_deck_id\", \"planned_duration\", \"owner\", \"notes\", \"started_at\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            id: row
+                .get("id")
+                .expect("Failed to get value from database row"),
+            run_sheet_id: row
+                .get("run_sheet_id")
+                .expect("Failed to get value from database row"),
+            order: row
+                .get("order")
+                .expect("Failed to get value from database row"),
+            name: row
+                .get("name")
+                .expect("Failed to get value from database row"),
+            slide_deck_id: row
+                .get("slide_deck_id")
+                .expect("Failed to get value from database row"),
+            planned_duration: row
+                .get("planned_duration")
+                .expect("Failed to get value from database row"),
+            owner: row
+                .get("owner")
+                .expect("Failed to get value from database row"),
+            notes: row
+                .get("notes")
+                .expect("Failed to get value from database row"),
+            started_at: row
+                .get("started_at")
+                .expect("Failed to get value from database row"),
+        }
+    }
+}
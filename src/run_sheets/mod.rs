@@ -0,0 +1,4 @@
+pub mod api;
+pub mod clock;
+pub mod db;
+pub mod service;
@@ -0,0 +1,550 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{named_params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::{database::Database, helpers::errors::GenericError};
+
+use super::{
+    clock::ShowClock,
+    db::{DbRunSheet, DbRunSheetItem},
+};
+
+/// Longest planned duration of an item in seconds
+const MAX_PLANNED_DURATION: i64 = 60 * 60 * 24 * 7;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunSheet {
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub display_output_id: Option<Uuid>,
+    pub current_item_id: Option<Uuid>,
+    /// Time the show has to end by
+    pub hard_out: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub items: Vec<RunSheetItem>,
+}
+impl RunSheet {
+    pub fn from_db_run_sheet(run_sheet: &DbRunSheet, items: &[DbRunSheetItem]) -> Self {
+        Self {
+            id: Some(run_sheet.id),
+            name: run_sheet.name.clone(),
+            display_output_id: run_sheet.display_output_id,
+            current_item_id: run_sheet.current_item_id,
+            hard_out: run_sheet.hard_out,
+            items: items.iter().map(RunSheetItem::from_db_item).collect(),
+        }
+    }
+}
+
+/// Run sheet entry, either a slide deck or a free-text item
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunSheetItem {
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub slide_deck_id: Option<Uuid>,
+    /// Planned duration in seconds
+    pub planned_duration: i64,
+    pub owner: String,
+    pub notes: String,
+    /// Time the item last went live
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+}
+impl RunSheetItem {
+    pub fn from_db_item(item: &DbRunSheetItem) -> Self {
+        Self {
+            id: Some(item.id),
+            name: item.name.clone(),
+            slide_deck_id: item.slide_deck_id,
+            planned_duration: item.planned_duration,
+            owner: item.owner.clone(),
+            notes: item.notes.clone(),
+            started_at: item.started_at,
+        }
+    }
+}
+
+/// Now, next and after items of a run sheet
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunSheetLive {
+    pub run_sheet_id: Uuid,
+    pub name: String,
+    pub now: Option<RunSheetItem>,
+    pub next: Option<RunSheetItem>,
+    pub after: Option<RunSheetItem>,
+    pub clock: ShowClock,
+}
+impl RunSheetLive {
+    /// Removes the owners and notes of items, which are only for operators
+    pub fn without_operator_fields(mut self) -> Self {
+        for item in [&mut self.now, &mut self.next, &mut self.after]
+            .into_iter()
+            .flatten()
+        {
+            item.owner.clear();
+            item.notes.clear();
+        }
+        self
+    }
+}
+
+pub struct RunSheetsService {
+    db: Database,
+    /// Live feeds by run sheet id, only created once subscribed to
+    live: Mutex<HashMap<Uuid, watch::Sender<Option<RunSheetLive>>>>,
+}
+
+impl RunSheetsService {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            db: database.clone(),
+            live: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get_run_sheet_by_id(&self, id: Uuid) -> Option<DbRunSheet> {
+        let db = self.db.get();
+
+        let run_sheet_result: Option<DbRunSheet> = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"run_sheets\" WHERE \"id\" = :id;",
+                DbRunSheet::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_row(named_params! {":id": id}, |row| {
+                Ok(DbRunSheet::from_row(row))
+            })
+            .optional()
+            .expect("Error occurred getting run sheet by id from database");
+
+        run_sheet_result
+    }
+
+    pub fn get_items(&self, run_sheet_id: Uuid) -> Vec<DbRunSheetItem> {
+        let db = self.db.get();
+
+        let items = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"run_sheet_items\" WHERE \"run_sheet_id\" = :run_sheet_id ORDER BY \"order\";",
+                DbRunSheetItem::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_map(named_params! {":run_sheet_id": run_sheet_id}, |row| {
+                Ok(DbRunSheetItem::from_row(row))
+            })
+            .expect("Error occurred getting run sheet items from database")
+            .map(|item| item.unwrap())
+            .collect();
+
+        items
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<RunSheet> {
+        self.get_run_sheet_by_id(id)
+            .map(|run_sheet| RunSheet::from_db_run_sheet(&run_sheet, &self.get_items(run_sheet.id)))
+    }
+
+    pub fn list(&self) -> Vec<RunSheet> {
+        let db = self.db.get();
+
+        let run_sheets: Vec<DbRunSheet> = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"run_sheets\" ORDER BY \"name\";",
+                DbRunSheet::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_map(named_params! {}, |row| Ok(DbRunSheet::from_row(row)))
+            .expect("Error occurred getting all run sheets from database")
+            .map(|run_sheet| run_sheet.unwrap())
+            .collect();
+
+        run_sheets
+            .iter()
+            .map(|run_sheet| RunSheet::from_db_run_sheet(run_sheet, &self.get_items(run_sheet.id)))
+            .collect()
+    }
+
+    pub fn create(&self, run_sheet: &RunSheet) -> Result<Uuid, GenericError> {
+        let run_sheet_id = Uuid::new_v4();
+
+        let mut db = self.db.get();
+        let tx = db.transaction().unwrap();
+
+        let success = tx
+            .prepare_cached("INSERT INTO \"run_sheets\" (\"id\", \"name\", \"display_output_id\", \"hard_out\") VALUES (:id, :name, :display_output_id, :hard_out);")
+            .unwrap()
+            .execute(named_params! {
+                ":id": run_sheet_id,
+                ":name": run_sheet.name,
+                ":display_output_id": run_sheet.display_output_id,
+                ":hard_out": run_sheet.hard_out,
+            })
+            .is_ok();
+
+        if !success || !Self::save_items(&tx, run_sheet_id, &run_sheet.items) {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        tx.commit()
+            .expect("Error occurred committing run sheet creation");
+
+        Ok(run_sheet_id)
+    }
+
+    pub fn update(&self, run_sheet: &RunSheet) -> Result<Uuid, GenericError> {
+        let Some(run_sheet_id) = run_sheet.id else {
+            return Err(GenericError::BAD_REQUEST);
+        };
+        if self.get_run_sheet_by_id(run_sheet_id).is_none() {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        {
+            let mut db = self.db.get();
+            let tx = db.transaction().unwrap();
+
+            let success = tx
+                .prepare_cached("UPDATE \"run_sheets\" SET \"name\" = :name, \"display_output_id\" = :display_output_id, \"hard_out\" = :hard_out WHERE \"id\" = :id;")
+                .unwrap()
+                .execute(named_params! {
+                    ":id": run_sheet_id,
+                    ":name": run_sheet.name,
+                    ":display_output_id": run_sheet.display_output_id,
+                    ":hard_out": run_sheet.hard_out,
+                })
+                .is_ok();
+
+            if !success || !Self::save_items(&tx, run_sheet_id, &run_sheet.items) {
+                return Err(GenericError::BAD_REQUEST);
+            }
+
+            tx.commit()
+                .expect("Error occurred committing run sheet update");
+        }
+
+        self.notify(run_sheet_id);
+
+        Ok(run_sheet_id)
+    }
+
+    /// Replaces the items of a run sheet, keeping the ids of existing items
+    fn save_items(tx: &rusqlite::Transaction, run_sheet_id: Uuid, items: &[RunSheetItem]) -> bool {
+        let existing_ids: Vec<Uuid> = tx
+            .prepare_cached(
+                "SELECT \"id\" FROM \"run_sheet_items\" WHERE \"run_sheet_id\" = :run_sheet_id;",
+            )
+            .unwrap()
+            .query_map(named_params! {":run_sheet_id": run_sheet_id}, |row| {
+                row.get("id")
+            })
+            .expect("Error occurred getting run sheet item ids from database")
+            .map(|id| id.unwrap())
+            .collect();
+
+        let mut kept_ids = Vec::new();
+
+        for (order, item) in items.iter().enumerate() {
+            if !(0..=MAX_PLANNED_DURATION).contains(&item.planned_duration) {
+                return false;
+            }
+
+            let params = named_params! {
+                ":id": item.id.unwrap_or_else(Uuid::new_v4),
+                ":run_sheet_id": run_sheet_id,
+                ":order": order as i64,
+                ":name": item.name,
+                ":slide_deck_id": item.slide_deck_id,
+                ":planned_duration": item.planned_duration,
+                ":owner": item.owner,
+                ":notes": item.notes,
+            };
+
+            let result = match item.id {
+                Some(id) if existing_ids.contains(&id) => {
+                    kept_ids.push(id);
+                    tx.prepare_cached("UPDATE \"run_sheet_items\" SET \"order\" = :order, \"name\" = :name, \"slide_deck_id\" = :slide_deck_id, \"planned_duration\" = :planned_duration, \"owner\" = :owner, \"notes\" = :notes WHERE \"id\" = :id AND \"run_sheet_id\" = :run_sheet_id;")
+                        .unwrap()
+                        .execute(params)
+                }
+                Some(_) => return false,
+                None => tx.prepare_cached("INSERT INTO \"run_sheet_items\" (\"id\", \"run_sheet_id\", \"order\", \"name\", \"slide_deck_id\", \"planned_duration\", \"owner\", \"notes\") VALUES (:id, :run_sheet_id, :order, :name, :slide_deck_id, :planned_duration, :owner, :notes);")
+                    .unwrap()
+                    .execute(params),
+            };
+
+            if result.is_err() {
+                return false;
+            }
+        }
+
+        for id in existing_ids.iter().filter(|id| !kept_ids.contains(id)) {
+            tx.prepare_cached("DELETE FROM \"run_sheet_items\" WHERE \"id\" = :id;")
+                .unwrap()
+                .execute(named_params! {":id": id})
+                .expect("Error occurred deleting run sheet item");
+        }
+
+        true
+    }
+
+    pub fn delete(&self, run_sheet_id: Uuid) -> Result<(), GenericError> {
+        let Some(run_sheet) = self.get_run_sheet_by_id(run_sheet_id) else {
+            return Err(GenericError::NOT_FOUND);
+        };
+
+        let db = self.db.get();
+
+        let success = db
+            .prepare_cached("DELETE FROM \"run_sheets\" WHERE \"id\" = :id;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": run_sheet.id,
+            })
+            .is_ok();
+
+        if success {
+            self.notify(run_sheet_id);
+            Ok(())
+        } else {
+            Err(GenericError::BAD_REQUEST)
+        }
+    }
+
+    /// Sets the current item of a run sheet, `None` meaning the run sheet isn't live.
+    /// The item's start time is recorded and later items are marked as not yet started.
+    pub fn set_current(
+        &self,
+        run_sheet_id: Uuid,
+        item_id: Option<Uuid>,
+    ) -> Result<(), GenericError> {
+        if self.get_run_sheet_by_id(run_sheet_id).is_none() {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        let item = match item_id {
+            Some(item_id) => {
+                let Some(item) = self
+                    .get_items(run_sheet_id)
+                    .into_iter()
+                    .find(|item| item.id == item_id)
+                else {
+                    return Err(GenericError::BAD_REQUEST);
+                };
+                Some(item)
+            }
+            None => None,
+        };
+
+        {
+            let mut db = self.db.get();
+            let tx = db.transaction().unwrap();
+
+            tx.prepare_cached(
+                "UPDATE \"run_sheets\" SET \"current_item_id\" = :current_item_id WHERE \"id\" = :id;",
+            )
+            .unwrap()
+            .execute(named_params! {":id": run_sheet_id, ":current_item_id": item_id})
+            .expect("Error occurred setting current run sheet item");
+
+            if let Some(item) = item {
+                tx.prepare_cached("UPDATE \"run_sheet_items\" SET \"started_at\" = CASE WHEN \"id\" = :id THEN :now ELSE NULL END WHERE \"run_sheet_id\" = :run_sheet_id AND \"order\" >= :order;")
+                    .unwrap()
+                    .execute(named_params! {
+                        ":id": item.id,
+                        ":run_sheet_id": run_sheet_id,
+                        ":order": item.order,
+                        ":now": Utc::now(),
+                    })
+                    .expect("Error occurred recording run sheet item start time");
+            }
+
+            tx.commit()
+                .expect("Error occurred committing current run sheet item");
+        }
+
+        self.notify(run_sheet_id);
+
+        Ok(())
+    }
+
+    /// Takes a run sheet off air and clears all recorded start times
+    pub fn reset(&self, run_sheet_id: Uuid) -> Result<(), GenericError> {
+        if self.get_run_sheet_by_id(run_sheet_id).is_none() {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        {
+            let mut db = self.db.get();
+            let tx = db.transaction().unwrap();
+
+            tx.prepare_cached(
+                "UPDATE \"run_sheets\" SET \"current_item_id\" = NULL WHERE \"id\" = :id;",
+            )
+            .unwrap()
+            .execute(named_params! {":id": run_sheet_id})
+            .expect("Error occurred resetting run sheet");
+
+            tx.prepare_cached("UPDATE \"run_sheet_items\" SET \"started_at\" = NULL WHERE \"run_sheet_id\" = :run_sheet_id;")
+                .unwrap()
+                .execute(named_params! {":run_sheet_id": run_sheet_id})
+                .expect("Error occurred clearing run sheet item start times");
+
+            tx.commit()
+                .expect("Error occurred committing run sheet reset");
+        }
+
+        self.notify(run_sheet_id);
+
+        Ok(())
+    }
+
+    /// Moves the current item forward or back by one, returning the new current item.
+    /// Moving forward from the last item ends the run sheet.
+    pub fn step(&self, run_sheet_id: Uuid, forward: bool) -> Result<Option<Uuid>, GenericError> {
+        let Some(run_sheet) = self.get_run_sheet_by_id(run_sheet_id) else {
+            return Err(GenericError::NOT_FOUND);
+        };
+        let items = self.get_items(run_sheet_id);
+
+        let current_index = run_sheet
+            .current_item_id
+            .and_then(|current| items.iter().position(|item| item.id == current));
+
+        let new_index = match (current_index, forward) {
+            (Some(index), true) => Some(index + 1),
+            (Some(index), false) => Some(index.saturating_sub(1)),
+            (None, true) => Some(0),
+            (None, false) => items.len().checked_sub(1),
+        };
+        let item_id = new_index.and_then(|index| items.get(index).map(|item| item.id));
+
+        self.set_current(run_sheet_id, item_id)?;
+
+        Ok(item_id)
+    }
+
+    /// Advances live run sheets following an output when the output moves on to the slide deck
+    /// of a later item
+    pub fn follow_deck(&self, display_output_id: Option<Uuid>, slide_deck_id: Uuid) {
+        let run_sheets: rusqlite::Result<Vec<DbRunSheet>> = {
+            let db = self.db.get();
+            let run_sheets = db
+                .prepare_cached(&format!(
+                    "SELECT {} FROM \"run_sheets\" WHERE \"display_output_id\" IS :display_output_id AND \"current_item_id\" IS NOT NULL;",
+                    DbRunSheet::COLUMNS_SQL
+                ))
+                .and_then(|mut stmt| {
+                    stmt.query_map(named_params! {":display_output_id": display_output_id}, |row| {
+                        Ok(DbRunSheet::from_row(row))
+                    })?
+                    .collect()
+                });
+            run_sheets
+        };
+        // this runs in the background with no one to report to, so a change that can't be
+        // followed is skipped and later changes are still followed
+        let Ok(run_sheets) = run_sheets else {
+            return;
+        };
+
+        for run_sheet in run_sheets {
+            let items = self.get_items(run_sheet.id);
+            let Some(current_index) = items
+                .iter()
+                .position(|item| Some(item.id) == run_sheet.current_item_id)
+            else {
+                continue;
+            };
+
+            if items[current_index].slide_deck_id == Some(slide_deck_id) {
+                continue;
+            }
+
+            if let Some(item) = items[current_index + 1..]
+                .iter()
+                .find(|item| item.slide_deck_id == Some(slide_deck_id))
+            {
+                let _ = self.set_current(run_sheet.id, Some(item.id));
+            }
+        }
+    }
+
+    /// Gets the now, next and after items of a run sheet
+    pub fn get_live(&self, run_sheet_id: Uuid) -> Option<RunSheetLive> {
+        let run_sheet = self.get_run_sheet_by_id(run_sheet_id)?;
+        let items = self.get_items(run_sheet_id);
+
+        let current_index = run_sheet
+            .current_item_id
+            .and_then(|current| items.iter().position(|item| item.id == current));
+
+        let item_at = |offset: usize| {
+            current_index
+                .and_then(|index| items.get(index + offset))
+                .map(RunSheetItem::from_db_item)
+        };
+
+        Some(RunSheetLive {
+            run_sheet_id,
+            name: run_sheet.name.clone(),
+            now: item_at(0),
+            // the first item is up next before the run sheet goes live
+            next: match current_index {
+                Some(_) => item_at(1),
+                None => items.first().map(RunSheetItem::from_db_item),
+            },
+            after: match current_index {
+                Some(_) => item_at(2),
+                None => items.get(1).map(RunSheetItem::from_db_item),
+            },
+            clock: ShowClock::compute(&run_sheet, &items, Utc::now()),
+        })
+    }
+
+    /// Subscribes to the live feed of a run sheet, if it exists
+    pub fn subscribe(&self, run_sheet_id: Uuid) -> Option<watch::Receiver<Option<RunSheetLive>>> {
+        let mut live = self.live.lock().unwrap();
+
+        if let Some(sender) = live.get(&run_sheet_id) {
+            return Some(sender.subscribe());
+        }
+        let run_sheet = self.get_live(run_sheet_id)?;
+        let receiver = live
+            .entry(run_sheet_id)
+            .or_insert_with(|| watch::Sender::new(Some(run_sheet)))
+            .subscribe();
+
+        Some(receiver)
+    }
+
+    /// Republishes all subscribed run sheets that are live so their show clocks keep running,
+    /// dropping the feeds nobody is subscribed to any more
+    pub fn refresh_live(&self) {
+        let mut live = self.live.lock().unwrap();
+
+        live.retain(|_, sender| sender.receiver_count() > 0);
+
+        for (run_sheet_id, sender) in live.iter() {
+            let is_live = sender
+                .borrow()
+                .as_ref()
+                .is_some_and(|live| live.now.is_some());
+            if is_live && sender.receiver_count() > 0 {
+                sender.send_replace(self.get_live(*run_sheet_id));
+            }
+        }
+    }
+
+    /// Publishes the current live state of a run sheet to subscribers
+    fn notify(&self, run_sheet_id: Uuid) {
+        let live = self.live.lock().unwrap();
+
+        if let Some(sender) = live.get(&run_sheet_id) {
+            sender.send_replace(self.get_live(run_sheet_id));
+        }
+    }
+}
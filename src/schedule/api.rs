@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    auth::{db::UserPermission, extractor::AuthToken},
+};
+
+use super::service::ScheduledCue;
+
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/", get(list_cues))
+        .route("/", post(create_cue))
+        .route("/:cue_id", get(get_cue))
+        .route("/:cue_id", delete(cancel_cue))
+}
+
+pub async fn list_cues(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, UserPermission::OPERATION) else {
+        return AuthToken::failure_response();
+    };
+
+    let cues = state.schedule_service.list();
+
+    Json(cues).into_response()
+}
+
+pub async fn get_cue(
+    State(state): State<Arc<AppServices>>,
+    Path(cue_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(_current_user)) = token.authorize(&state, UserPermission::OPERATION) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.schedule_service.get(cue_id);
+
+    match result {
+        Some(cue) => Json(cue).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn create_cue(
+    State(state): State<Arc<AppServices>>,
+    token: AuthToken,
+    Json(request): Json<ScheduledCue>,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::OPERATION) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state
+        .schedule_service
+        .create(&request, Some(current_user.id));
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "scheduled_cue_create",
+        json!({
+            "cue_id": result.as_ref().ok(),
+            "name": request.name,
+            "display_output_id": request.display_output_id,
+            "execute_at": request.execute_at,
+            "timezone": request.timezone,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(id) => Json(id).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+pub async fn cancel_cue(
+    State(state): State<Arc<AppServices>>,
+    Path(cue_id): Path<Uuid>,
+    token: AuthToken,
+) -> impl IntoResponse {
+    let Ok(Some(current_user)) = token.authorize(&state, UserPermission::OPERATION) else {
+        return AuthToken::failure_response();
+    };
+
+    let result = state.schedule_service.cancel(cue_id);
+
+    state.audit_service.log_data(
+        Some(current_user.id),
+        "scheduled_cue_cancel",
+        json!({
+            "cue_id": cue_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    match result {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+    Row, ToSql,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::state::models::CurrentState;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CueStatus {
+    Pending,
+    Executed,
+    Skipped,
+    Cancelled,
+}
+impl ToSql for CueStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Self::Pending => "pending",
+            Self::Executed => "executed",
+            Self::Skipped => "skipped",
+            Self::Cancelled => "cancelled",
+        }
+        .into())
+    }
+}
+impl FromSql for CueStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("pending") => Ok(Self::Pending),
+            Ok("executed") => Ok(Self::Executed),
+            Ok("skipped") => Ok(Self::Skipped),
+            Ok("cancelled") => Ok(Self::Cancelled),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// State change performed when a cue fires
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CueAction {
+    /// Replaces the state of the output
+    SetState { state: CurrentState },
+    /// Resets the output to an empty state
+    Clear,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DbScheduledCue {
+    pub id: Uuid,
+    pub name: String,
+    pub display_output_id: Option<Uuid>,
+    pub execute_at: DateTime<Utc>,
+    pub timezone: String,
+    pub action: CueAction,
+    pub status: CueStatus,
+    pub created_by: Option<Uuid>,
+    pub executed_at: Option<DateTime<Utc>>,
+}
+impl DbScheduledCue {
+    pub const TABLE_NAME: &'static str = "scheduled_cues";
+
+    pub const COLUMNS_SQL: &'static str =
+        "\"id\", \"name\", \"display_output_id\", \"execute_at\", \"timezone\", \"action_json\", \"status\", \"created_by\", \"executed_at\"";
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            id: row
+                .get("id")
+                .expect("Failed to get value from database row"),
+            name: row
+                .get("name")
+                .expect("Failed to get value from database row"),
+            display_output_id: row
+                .get("display_output_id")
+                .expect("Failed to get value from database row"),
+            execute_at: row
+                .get("execute_at")
+                .expect("Failed to get value from database row"),
+            timezone: row
+                .get("timezone")
+                .expect("Failed to get value from database row"),
+            action: serde_json::from_str(
+                &row.get::<_, String>("action_json")
+                    .expect("Failed to get value from database row"),
+            )
+            .expect("Error parsing JSON from scheduled cue action"),
+            status: row
+                .get("status")
+                .expect("Failed to get value from database row"),
+            created_by: row
+                .get("created_by")
+                .expect("Failed to get value from database row"),
+            executed_at: row
+                .get("executed_at")
+                .expect("Failed to get value from database row"),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use chrono::{DateTime, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::{named_params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    audit::AuditService,
+    config::file::AppConfig,
+    database::{display_outputs::OutputKind, Database},
+    display_outputs::service::DisplayOutputsService,
+    helpers::errors::GenericError,
+    state::{models::CurrentState, service::StateService},
+};
+
+use super::db::{CueAction, CueStatus, DbScheduledCue};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduledCue {
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub display_output_id: Option<Uuid>,
+    /// Wall clock time in `timezone` at which the cue fires
+    pub execute_at: NaiveDateTime,
+    /// IANA timezone name, defaults to the configured timezone
+    pub timezone: Option<String>,
+    pub action: CueAction,
+    pub execute_at_utc: Option<DateTime<Utc>>,
+    pub status: Option<CueStatus>,
+    pub created_by: Option<Uuid>,
+    pub executed_at: Option<DateTime<Utc>>,
+}
+impl ScheduledCue {
+    pub fn from_db_cue(cue: &DbScheduledCue) -> Self {
+        let execute_at = match cue.timezone.parse::<Tz>() {
+            Ok(tz) => cue.execute_at.with_timezone(&tz).naive_local(),
+            Err(_) => cue.execute_at.naive_utc(),
+        };
+
+        Self {
+            id: Some(cue.id),
+            name: cue.name.clone(),
+            display_output_id: cue.display_output_id,
+            execute_at,
+            timezone: Some(cue.timezone.clone()),
+            action: cue.action.clone(),
+            execute_at_utc: Some(cue.execute_at),
+            status: Some(cue.status),
+            created_by: cue.created_by,
+            executed_at: cue.executed_at,
+        }
+    }
+}
+
+pub struct ScheduleService {
+    config: AppConfig,
+    db: Database,
+    audit_service: AuditService,
+    display_outputs_service: DisplayOutputsService,
+}
+
+impl ScheduleService {
+    pub fn new(database: &Database, config: &AppConfig) -> Self {
+        Self {
+            config: config.clone(),
+            audit_service: AuditService::new(database),
+            display_outputs_service: DisplayOutputsService::new(database),
+            db: database.clone(),
+        }
+    }
+
+    pub fn get_cue_by_id(&self, id: Uuid) -> Option<DbScheduledCue> {
+        let db = self.db.get();
+
+        let cue_result: Option<DbScheduledCue> = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"scheduled_cues\" WHERE \"id\" = :id;",
+                DbScheduledCue::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_row(named_params! {":id": id}, |row| {
+                Ok(DbScheduledCue::from_row(row))
+            })
+            .optional()
+            .expect("Error occurred getting scheduled cue by id from database");
+
+        cue_result
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<ScheduledCue> {
+        self.get_cue_by_id(id)
+            .map(|cue| ScheduledCue::from_db_cue(&cue))
+    }
+
+    pub fn list(&self) -> Vec<ScheduledCue> {
+        let db = self.db.get();
+
+        let cues = db
+            .prepare_cached(&format!(
+                "SELECT {} FROM \"scheduled_cues\" ORDER BY \"execute_at\";",
+                DbScheduledCue::COLUMNS_SQL
+            ))
+            .unwrap()
+            .query_map(named_params! {}, |row| Ok(DbScheduledCue::from_row(row)))
+            .expect("Error occurred getting all scheduled cues from database")
+            .map(|db_cue| ScheduledCue::from_db_cue(&db_cue.unwrap()))
+            .collect();
+
+        cues
+    }
+
+    pub fn create(&self, cue: &ScheduledCue, user_id: Option<Uuid>) -> Result<Uuid, GenericError> {
+        let cue_id = Uuid::new_v4();
+
+        if self.is_stage_output(cue.display_output_id) {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        let timezone = cue
+            .timezone
+            .clone()
+            .unwrap_or_else(|| self.config.default_timezone.clone());
+        let Ok(tz) = timezone.parse::<Tz>() else {
+            return Err(GenericError::BAD_REQUEST);
+        };
+
+        // times skipped by daylight saving changes can't be scheduled
+        let Some(execute_at) = tz.from_local_datetime(&cue.execute_at).earliest() else {
+            return Err(GenericError::BAD_REQUEST);
+        };
+
+        let action_json =
+            serde_json::to_string(&cue.action).expect("Error stringifying JSON for cue action");
+
+        let db = self.db.get();
+        let success = db.prepare_cached("INSERT INTO \"scheduled_cues\" (\"id\", \"name\", \"display_output_id\", \"execute_at\", \"timezone\", \"action_json\", \"status\", \"created_by\") VALUES (:id, :name, :display_output_id, :execute_at, :timezone, :action_json, :status, :created_by);")
+            .unwrap()
+            .execute(named_params! {
+                ":id": cue_id,
+                ":name": cue.name,
+                ":display_output_id": cue.display_output_id,
+                ":execute_at": execute_at.with_timezone(&Utc),
+                ":timezone": tz.name(),
+                ":action_json": action_json,
+                ":status": CueStatus::Pending,
+                ":created_by": user_id,
+            })
+            .is_ok();
+
+        if success {
+            Ok(cue_id)
+        } else {
+            Err(GenericError::BAD_REQUEST)
+        }
+    }
+
+    /// Cancels a cue that hasn't fired yet
+    pub fn cancel(&self, cue_id: Uuid) -> Result<(), GenericError> {
+        let Some(cue) = self.get_cue_by_id(cue_id) else {
+            return Err(GenericError::NOT_FOUND);
+        };
+
+        if self.set_status(cue.id, CueStatus::Pending, CueStatus::Cancelled, None) {
+            Ok(())
+        } else {
+            Err(GenericError::CONFLICT)
+        }
+    }
+
+    /// Stage outputs follow their target output, so cues can't set their state
+    fn is_stage_output(&self, output_id: Option<Uuid>) -> bool {
+        output_id
+            .and_then(|id| self.display_outputs_service.get(id))
+            .is_some_and(|output| output.kind == OutputKind::Stage)
+    }
+
+    /// Moves a cue from one status to another, returning false if it no longer has the first
+    fn set_status(
+        &self,
+        cue_id: Uuid,
+        from: CueStatus,
+        status: CueStatus,
+        executed_at: Option<DateTime<Utc>>,
+    ) -> bool {
+        let db = self.db.get();
+
+        let updated = db.prepare_cached("UPDATE \"scheduled_cues\" SET \"status\" = :status, \"executed_at\" = :executed_at WHERE \"id\" = :id AND \"status\" = :from;")
+            .unwrap()
+            .execute(named_params! {
+                ":id": cue_id,
+                ":status": status,
+                ":executed_at": executed_at,
+                ":from": from,
+            })
+            .expect("Error occurred updating scheduled cue status");
+
+        updated == 1
+    }
+
+    /// Fires all cues that are due, skipping those missed by more than the configured delay
+    /// (i.e. while the server was down). A cue acts for the user who created it, so it is
+    /// skipped if another user controls its output by then, as it is if the output has become
+    /// a stage output.
+    pub fn run_due(&self, state_service: &StateService) {
+        let now = Utc::now();
+        let max_delay = TimeDelta::seconds(self.config.scheduled_cue_max_delay as i64);
+
+        let due: Vec<DbScheduledCue> = {
+            let db = self.db.get();
+            let cues = db
+                .prepare_cached(&format!(
+                    "SELECT {} FROM \"scheduled_cues\" WHERE \"status\" = :pending AND \"execute_at\" <= :now ORDER BY \"execute_at\";",
+                    DbScheduledCue::COLUMNS_SQL
+                ))
+                .unwrap()
+                .query_map(
+                    named_params! {":pending": CueStatus::Pending, ":now": now},
+                    |row| Ok(DbScheduledCue::from_row(row)),
+                )
+                .expect("Error occurred getting due scheduled cues from database")
+                .map(|db_cue| db_cue.unwrap())
+                .collect();
+            cues
+        };
+
+        for cue in due {
+            let skip_reason = if now - cue.execute_at > max_delay {
+                Some("missed")
+            } else if self.is_stage_output(cue.display_output_id) {
+                Some("stage_output")
+            } else {
+                None
+            };
+            if let Some(reason) = skip_reason {
+                if self.set_status(cue.id, CueStatus::Pending, CueStatus::Skipped, None) {
+                    self.log_skipped(&cue, reason);
+                }
+                continue;
+            }
+
+            // only fire cues which haven't been cancelled in the meantime
+            if !self.set_status(cue.id, CueStatus::Pending, CueStatus::Executed, Some(now)) {
+                continue;
+            }
+
+            let state = match cue.action {
+                CueAction::SetState { ref state } => state.clone(),
+                CueAction::Clear => CurrentState::default(),
+            };
+            if state_service
+                .set_state_for(cue.display_output_id, cue.created_by, state)
+                .is_err()
+            {
+                self.set_status(cue.id, CueStatus::Executed, CueStatus::Skipped, None);
+                self.log_skipped(&cue, "locked");
+                continue;
+            }
+
+            self.audit_service.log_data(
+                None,
+                "scheduled_cue_executed",
+                json!({
+                    "cue_id": cue.id,
+                    "name": cue.name,
+                    "execute_at": cue.execute_at,
+                    "delay_ms": (now - cue.execute_at).num_milliseconds(),
+                }),
+            );
+        }
+    }
+
+    fn log_skipped(&self, cue: &DbScheduledCue, reason: &str) {
+        self.audit_service.log_data(
+            None,
+            "scheduled_cue_skipped",
+            json!({
+                "cue_id": cue.id,
+                "name": cue.name,
+                "execute_at": cue.execute_at,
+                "reason": reason,
+            }),
+        );
+    }
+}
@@ -11,11 +11,19 @@ use axum::{
 };
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use tokio::sync::{mpsc, watch};
+use serde_json::json;
+use tokio::sync::{broadcast::error::RecvError, mpsc, watch};
+use uuid::Uuid;
 
-use crate::{app::AppServices, auth::db::UserPermission};
+use crate::{
+    app::AppServices, auth::db::UserPermission, content::db::ContentVisibility,
+    database::display_outputs::OutputKind, run_sheets::service::RunSheetLive,
+};
 
-use super::models::CurrentState;
+use super::{
+    models::{ControlLock, CurrentState},
+    service::OutputChannel,
+};
 
 pub fn route() -> Router<Arc<AppServices>> {
     Router::new().route("/", get(handler))
@@ -26,18 +34,129 @@ pub fn route() -> Router<Arc<AppServices>> {
 pub enum StateRequest {
     Get { get: bool },
     Authenticate { auth_token: String },
+    Subscribe { subscribe: SubscribeRequest },
     Set { state: CurrentState },
+    Control { control: ControlRequest },
     Ping { ping: String },
     Pong { pong: String },
 }
 
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SubscribeRequest {
+    pub output_id: Option<Uuid>,
+    #[serde(default)]
+    pub run_sheet_id: Option<Uuid>,
+    /// Access key of a stage output, needed to be sent its stage content
+    #[serde(default)]
+    pub access_key: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlRequest {
+    Take,
+    Release,
+    RequestHandover,
+    GrantHandover,
+    ForceRelease,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum StateResponse {
-    AuthResult { auth: bool },
-    State { state: CurrentState },
-    Ping { ping: String },
-    Pong { pong: String },
+    AuthResult {
+        auth: bool,
+    },
+    State {
+        state: CurrentState,
+    },
+    Lock {
+        lock: Option<ControlLock>,
+    },
+    RunSheet {
+        run_sheet: Option<Box<RunSheetLive>>,
+    },
+    Rejected {
+        rejected: String,
+    },
+    /// Sent before everything is sent again, clients should also reload anything else they show
+    Resync {
+        resync: bool,
+    },
+    Ping {
+        ping: String,
+    },
+    Pong {
+        pong: String,
+    },
+}
+
+/// Subscription of the watch task, with the content the client may receive
+#[derive(Clone, Default)]
+struct Subscription {
+    request: SubscribeRequest,
+    access: ContentVisibility,
+}
+
+/// Operators see all content, stage outputs given their access key stage content,
+/// everyone else public content
+fn client_access(
+    state: &AppServices,
+    subscribe: &SubscribeRequest,
+    auth_token: Option<&String>,
+) -> ContentVisibility {
+    let is_operator = auth_token.is_some_and(|auth_token| {
+        state
+            .auth_service
+            .authorize(auth_token, UserPermission::OPERATION)
+            .is_some()
+    });
+    if is_operator {
+        return ContentVisibility::Operator;
+    }
+
+    let is_stage = subscribe
+        .output_id
+        .and_then(|id| state.display_outputs_service.get(id))
+        .is_some_and(|output| {
+            output.kind == OutputKind::Stage
+                && !output.access_key.is_empty()
+                && subscribe.access_key.as_ref() == Some(&output.access_key)
+        });
+    if is_stage {
+        ContentVisibility::Stage
+    } else {
+        ContentVisibility::Public
+    }
+}
+
+/// Prepares a state for a client, removing content it may not see and rendering templates
+fn client_state(
+    state: &AppServices,
+    current: &CurrentState,
+    access: ContentVisibility,
+) -> CurrentState {
+    let mut client_state = state.visibility_service.filter_state(current, access);
+
+    state
+        .template_service
+        .render_display(&mut client_state.display);
+    if let Some(ref mut stage) = client_state.stage {
+        state.template_service.render_display(&mut stage.current);
+        if let Some(ref mut next) = stage.next {
+            state.template_service.render_display(next);
+        }
+    }
+
+    client_state
+}
+
+/// Prepares a run sheet for a client, removing item details only operators may see
+fn client_run_sheet(run_sheet: RunSheetLive, access: ContentVisibility) -> Box<RunSheetLive> {
+    Box::new(match access {
+        ContentVisibility::Operator => run_sheet,
+        _ => run_sheet.without_operator_fields(),
+    })
 }
 
 pub async fn handler(State(state): State<Arc<AppServices>>, ws: WebSocketUpgrade) -> Response {
@@ -47,11 +166,17 @@ pub async fn handler(State(state): State<Arc<AppServices>>, ws: WebSocketUpgrade
 pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
     let mut client_auth_token = None;
 
+    // identifies the control locks this connection holds, which are released when it closes
+    let connection_id = Uuid::new_v4();
+
     let (mut ws_send, mut ws_recv) = socket.split();
 
     // send a message to this queue to send it to the client
     let (queue_send, mut queue_recv) = mpsc::channel::<String>(1);
 
+    // output and run sheet the client is subscribed to (the default output until changed)
+    let (subscription_send, mut subscription_recv) = watch::channel(Subscription::default());
+
     // sends messages to the client from the message queue
     let mut send_task = tokio::spawn(async move {
         while let Some(response) = queue_recv.recv().await {
@@ -61,11 +186,14 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
 
     let r_state = state.clone();
     let r_queue_send = queue_send.clone();
+    let r_release_state = state.clone();
 
     // handles incoming requests from the client
     let mut recv_task = tokio::spawn(async move {
-        let mut watch_recv = r_state.state_service.watch_recv.clone();
-        let watch_send = r_state.state_service.watch_send.clone();
+        let mut output_id = None;
+        let mut output = r_state.state_service.output(output_id);
+        let mut run_sheet_id = None;
+        let mut access = ContentVisibility::default();
 
         async fn send_response(
             response: &StateResponse,
@@ -79,12 +207,15 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
         }
 
         async fn send_current_state(
-            watch_recv: &mut watch::Receiver<CurrentState>,
+            state: &AppServices,
+            output: &OutputChannel,
+            access: ContentVisibility,
             queue_send: &mpsc::Sender<String>,
         ) -> Result<(), ()> {
-            let state = watch_recv.borrow_and_update().clone();
-            let response = StateResponse::State { state };
-            send_response(&response, queue_send).await
+            let state = client_state(state, &output.state.borrow(), access);
+            send_response(&StateResponse::State { state }, queue_send).await?;
+            let lock = output.lock.borrow().clone();
+            send_response(&StateResponse::Lock { lock }, queue_send).await
         }
 
         while let Some(Ok(msg)) = ws_recv.next().await {
@@ -96,12 +227,29 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
                     match request {
                         StateRequest::Get { get: _ } => {
                             // respond with current state
-                            if send_current_state(&mut watch_recv, &r_queue_send)
+                            if send_current_state(&r_state, &output, access, &r_queue_send)
                                 .await
                                 .is_err()
                             {
                                 return;
                             }
+
+                            // respond with current run sheet items if subscribed
+                            if let Some(run_sheet_id) = run_sheet_id {
+                                let run_sheet = r_state
+                                    .run_sheets_service
+                                    .get_live(run_sheet_id)
+                                    .map(|run_sheet| client_run_sheet(run_sheet, access));
+                                if send_response(
+                                    &StateResponse::RunSheet { run_sheet },
+                                    &r_queue_send,
+                                )
+                                .await
+                                .is_err()
+                                {
+                                    return;
+                                }
+                            }
                         }
 
                         StateRequest::Authenticate { auth_token } => {
@@ -123,26 +271,127 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
                             if send_result.is_err() {
                                 return;
                             }
+
+                            // resend content the client may now see
+                            let request = subscription_send.borrow().request.clone();
+                            let new_access =
+                                client_access(&r_state, &request, client_auth_token.as_ref());
+                            if new_access != access {
+                                access = new_access;
+                                if subscription_send
+                                    .send(Subscription { request, access })
+                                    .is_err()
+                                {
+                                    return;
+                                }
+                            }
+                        }
+
+                        StateRequest::Subscribe { subscribe } => {
+                            // only outputs and run sheets that exist have channels
+                            let exists = subscribe
+                                .output_id
+                                .is_none_or(|id| r_state.display_outputs_service.get(id).is_some())
+                                && subscribe.run_sheet_id.is_none_or(|id| {
+                                    r_state.run_sheets_service.get_run_sheet_by_id(id).is_some()
+                                });
+                            if !exists {
+                                if send_response(
+                                    &StateResponse::Rejected {
+                                        rejected: String::from(
+                                            "Output or run sheet does not exist",
+                                        ),
+                                    },
+                                    &r_queue_send,
+                                )
+                                .await
+                                .is_err()
+                                {
+                                    return;
+                                }
+                                continue;
+                            }
+
+                            // switch output (watch task sends the new output's state)
+                            output_id = subscribe.output_id;
+                            output = r_state.state_service.output(output_id);
+                            run_sheet_id = subscribe.run_sheet_id;
+                            access =
+                                client_access(&r_state, &subscribe, client_auth_token.as_ref());
+                            if subscription_send
+                                .send(Subscription {
+                                    request: subscribe,
+                                    access,
+                                })
+                                .is_err()
+                            {
+                                return;
+                            }
                         }
 
                         StateRequest::Set { state } => {
                             // check permissions
-                            let can_set_state = if let Some(ref auth_token) = client_auth_token {
+                            let user = client_auth_token.as_ref().and_then(|auth_token| {
                                 r_state
                                     .auth_service
                                     .authorize(auth_token, UserPermission::OPERATION)
-                                    .is_some()
-                            } else {
-                                false
-                            };
+                            });
 
-                            if can_set_state {
-                                // set state (will trigger response)
-                                if watch_send.send(state).is_err() {
+                            let Some(user) = user else {
+                                // respond with auth failure
+                                if send_response(
+                                    &StateResponse::AuthResult { auth: false },
+                                    &r_queue_send,
+                                )
+                                .await
+                                .is_err()
+                                {
                                     return;
                                 }
+                                continue;
+                            };
+
+                            // stage outputs are driven by their target output
+                            let is_stage = output_id
+                                .and_then(|id| r_state.display_outputs_service.get(id))
+                                .is_some_and(|output| output.kind == OutputKind::Stage);
+
+                            // set state if not controlled by someone else (will trigger response)
+                            let rejection = if is_stage {
+                                Some(String::from("Stage outputs follow their target output"))
                             } else {
-                                // respond with current state
+                                r_state
+                                    .state_service
+                                    .set_state_controlled(output_id, &user, state)
+                                    .err()
+                                    .map(|err| err.to_string())
+                            };
+                            if let Some(rejected) = rejection {
+                                if send_response(
+                                    &StateResponse::Rejected { rejected },
+                                    &r_queue_send,
+                                )
+                                .await
+                                .is_err()
+                                {
+                                    return;
+                                }
+                            }
+                        }
+
+                        StateRequest::Control { control } => {
+                            // force releasing is reserved for admins, everything else for operators
+                            let required_permissions = match control {
+                                ControlRequest::ForceRelease => UserPermission::SYSTEM_ADMIN,
+                                _ => UserPermission::OPERATION,
+                            };
+                            let user = client_auth_token.as_ref().and_then(|auth_token| {
+                                r_state
+                                    .auth_service
+                                    .authorize(auth_token, required_permissions)
+                            });
+
+                            let Some(user) = user else {
                                 if send_response(
                                     &StateResponse::AuthResult { auth: false },
                                     &r_queue_send,
@@ -152,6 +401,55 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
                                 {
                                     return;
                                 }
+                                continue;
+                            };
+
+                            // update lock (will trigger response)
+                            let state_service = &r_state.state_service;
+                            let (action, result) = match control {
+                                ControlRequest::Take => (
+                                    "state_control_take",
+                                    state_service.take_control(output_id, &user, connection_id),
+                                ),
+                                ControlRequest::Release => (
+                                    "state_control_release",
+                                    state_service.release_control(output_id, &user),
+                                ),
+                                ControlRequest::RequestHandover => (
+                                    "state_control_handover_request",
+                                    state_service.request_handover(output_id, &user, connection_id),
+                                ),
+                                ControlRequest::GrantHandover => (
+                                    "state_control_handover_grant",
+                                    state_service.grant_handover(output_id, &user).map(|_| ()),
+                                ),
+                                ControlRequest::ForceRelease => {
+                                    state_service.force_release(output_id);
+                                    ("state_control_force_release", Ok(()))
+                                }
+                            };
+
+                            r_state.audit_service.log_data(
+                                Some(user.id),
+                                action,
+                                json!({
+                                    "output_id": output_id,
+                                    "success": result.is_ok(),
+                                }),
+                            );
+
+                            if let Err(err) = result {
+                                if send_response(
+                                    &StateResponse::Rejected {
+                                        rejected: err.to_string(),
+                                    },
+                                    &r_queue_send,
+                                )
+                                .await
+                                .is_err()
+                                {
+                                    return;
+                                }
                             }
                         }
 
@@ -173,16 +471,86 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
         }
     });
 
-    // watch for changed state
+    // watch for changes to the subscribed output and run sheet
     let watch_task = tokio::spawn(async move {
-        let mut watch_recv = state.state_service.watch_recv.clone();
-        while let Ok(()) = watch_recv.changed().await {
-            let state = watch_recv.borrow_and_update().clone();
-            let response = StateResponse::State { state };
-            let response_json = serde_json::to_string(&response).unwrap();
-            if queue_send.send(response_json).await.is_err() {
-                return;
+        let mut subscription = subscription_recv.borrow_and_update().clone();
+        let mut switched = false;
+        let mut resync_recv = state.state_service.subscribe_resync();
+
+        loop {
+            let output = state.state_service.output(subscription.request.output_id);
+            let mut state_recv = output.state.subscribe();
+            let mut lock_recv = output.lock.subscribe();
+            let mut run_sheet_recv = subscription
+                .request
+                .run_sheet_id
+                .and_then(|run_sheet_id| state.run_sheets_service.subscribe(run_sheet_id));
+
+            // send everything for a new subscription
+            if switched {
+                state_recv.mark_changed();
+                lock_recv.mark_changed();
+                if let Some(ref mut run_sheet_recv) = run_sheet_recv {
+                    run_sheet_recv.mark_changed();
+                }
             }
+
+            loop {
+                let response = tokio::select! {
+                    result = state_recv.changed() => {
+                        if result.is_err() {
+                            return;
+                        }
+                        let state = client_state(&state, &state_recv.borrow_and_update(), subscription.access);
+                        StateResponse::State { state }
+                    },
+                    result = lock_recv.changed() => {
+                        if result.is_err() {
+                            return;
+                        }
+                        StateResponse::Lock { lock: lock_recv.borrow_and_update().clone() }
+                    },
+                    result = async {
+                        match run_sheet_recv.as_mut() {
+                            Some(run_sheet_recv) => run_sheet_recv
+                                .changed()
+                                .await
+                                .map(|_| run_sheet_recv.borrow_and_update().clone().map(|run_sheet| client_run_sheet(run_sheet, subscription.access))),
+                            None => std::future::pending().await,
+                        }
+                    } => {
+                        let Ok(run_sheet) = result else {
+                            return;
+                        };
+                        StateResponse::RunSheet { run_sheet }
+                    },
+                    result = subscription_recv.changed() => {
+                        if result.is_err() {
+                            return;
+                        }
+                        break;
+                    },
+                    result = resync_recv.recv() => {
+                        if matches!(result, Err(RecvError::Closed)) {
+                            return;
+                        }
+                        let response_json = serde_json::to_string(&StateResponse::Resync { resync: true }).unwrap();
+                        if queue_send.send(response_json).await.is_err() {
+                            return;
+                        }
+                        // resubscribing sends everything again
+                        break;
+                    },
+                };
+
+                let response_json = serde_json::to_string(&response).unwrap();
+                if queue_send.send(response_json).await.is_err() {
+                    return;
+                }
+            }
+
+            subscription = subscription_recv.borrow_and_update().clone();
+            switched = true;
         }
     });
 
@@ -197,4 +565,18 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
             watch_task.abort();
         }
     }
+
+    // nobody is left to release control of outputs controlled through this connection
+    for output_id in r_release_state
+        .state_service
+        .release_connection(connection_id)
+    {
+        r_release_state.audit_service.log_data(
+            None,
+            "state_control_disconnect_release",
+            json!({
+                "output_id": output_id,
+            }),
+        );
+    }
 }
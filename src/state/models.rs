@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -7,6 +8,10 @@ use uuid::Uuid;
 pub struct CurrentState {
     pub id: String,
     pub display: DisplayState,
+    #[serde(default)]
+    pub position: Option<PlaybackPosition>,
+    #[serde(default)]
+    pub stage: Option<Box<StageState>>,
 }
 
 impl CurrentState {
@@ -14,6 +19,8 @@ impl CurrentState {
         Self {
             id: String::default(),
             display: DisplayState::new(),
+            position: None,
+            stage: None,
         }
     }
 }
@@ -28,6 +35,9 @@ impl Default for CurrentState {
 pub struct DisplayState {
     pub content: HashMap<String, String>,
     pub slide_type_id: Option<Uuid>,
+    /// Markup rendered from the slide type template
+    #[serde(default)]
+    pub rendered: Option<String>,
 }
 
 impl DisplayState {
@@ -35,6 +45,7 @@ impl DisplayState {
         Self {
             content: HashMap::new(),
             slide_type_id: None,
+            rendered: None,
         }
     }
 }
@@ -44,3 +55,46 @@ impl Default for DisplayState {
         Self::new()
     }
 }
+
+/// Slide deck (and slide within it) being shown on an output
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlaybackPosition {
+    pub slide_deck_id: Uuid,
+    pub slide_deck_slide_id: Option<Uuid>,
+}
+
+/// Confidence monitor view of a target output, derived by the server
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StageState {
+    pub target_output_id: Option<Uuid>,
+    pub slide_deck_slide_id: Option<Uuid>,
+    pub current: DisplayState,
+    pub next: Option<DisplayState>,
+    /// Speaker notes of the current slide
+    pub notes: HashMap<String, String>,
+    pub timestamp: DateTime<Utc>,
+    /// Time the current slide went live on the target output
+    pub slide_started_at: DateTime<Utc>,
+}
+
+/// Operator currently in control of an output
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ControlLock {
+    pub user_id: Uuid,
+    pub username: String,
+    pub timestamp: DateTime<Utc>,
+    pub handover_request: Option<HandoverRequest>,
+    /// Websocket connection holding the lock, which releases it when it closes
+    #[serde(skip)]
+    pub connection_id: Uuid,
+}
+
+/// Request from another operator to be handed control of an output
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HandoverRequest {
+    pub user_id: Uuid,
+    pub username: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip)]
+    pub connection_id: Uuid,
+}
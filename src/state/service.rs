@@ -1,21 +1,322 @@
-use tokio::sync::watch;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
 
-use super::models::CurrentState;
+use chrono::Utc;
+use tokio::sync::{broadcast, watch};
+use uuid::Uuid;
+
+use crate::users::db::DbUser;
+
+use super::models::{ControlLock, CurrentState, HandoverRequest};
+
+#[derive(Debug)]
+pub enum ControlError {
+    NotLocked,
+    LockedBy(String),
+    NotHolder,
+    NoHandoverRequest,
+}
+
+impl Error for ControlError {}
+
+impl Display for ControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlError::NotLocked => write!(f, "Output is not locked"),
+            ControlError::LockedBy(username) => {
+                write!(f, "Output is controlled by \"{}\"", username)
+            }
+            ControlError::NotHolder => write!(f, "Output is not controlled by this user"),
+            ControlError::NoHandoverRequest => write!(f, "No handover has been requested"),
+        }
+    }
+}
+
+/// Live state and control lock of a single output
+pub struct OutputChannel {
+    pub state: watch::Sender<CurrentState>,
+    pub lock: watch::Sender<Option<ControlLock>>,
+}
+
+impl OutputChannel {
+    pub fn new() -> Self {
+        Self {
+            state: watch::Sender::new(CurrentState::default()),
+            lock: watch::Sender::new(None),
+        }
+    }
+}
+
+impl Default for OutputChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const STATE_CHANGES_CAPACITY: usize = 64;
 
 pub struct StateService {
-    pub watch_send: watch::Sender<CurrentState>,
-    pub watch_recv: watch::Receiver<CurrentState>,
+    /// Outputs by display output id, `None` being the default output
+    outputs: Mutex<HashMap<Option<Uuid>, Arc<OutputChannel>>>,
+    /// Ids of outputs whose state was set
+    changes: broadcast::Sender<Option<Uuid>>,
+    /// Tells clients to reload everything, e.g. after the database was restored
+    resync: broadcast::Sender<()>,
 }
 
 impl StateService {
     pub fn new() -> Self {
-        let (send, recv) = watch::channel(CurrentState::default());
+        let (changes, _) = broadcast::channel(STATE_CHANGES_CAPACITY);
+        let (resync, _) = broadcast::channel(1);
 
         Self {
-            watch_send: send,
-            watch_recv: recv,
+            outputs: Mutex::new(HashMap::new()),
+            changes,
+            resync,
         }
     }
+
+    /// Gets the channel for an output, creating it if it doesn't exist yet
+    pub fn output(&self, output_id: Option<Uuid>) -> Arc<OutputChannel> {
+        self.outputs
+            .lock()
+            .unwrap()
+            .entry(output_id)
+            .or_default()
+            .clone()
+    }
+
+    /// Removes the channel of a deleted output
+    pub fn remove_output(&self, output_id: Uuid) {
+        self.outputs.lock().unwrap().remove(&Some(output_id));
+    }
+
+    /// Sets the state of an output
+    pub fn set_state(&self, output_id: Option<Uuid>, state: CurrentState) {
+        self.output(output_id).state.send_replace(state);
+
+        // nobody listening for changes is fine
+        self.changes.send(output_id).ok();
+    }
+
+    /// Subscribes to the ids of outputs whose state is set
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<Option<Uuid>> {
+        self.changes.subscribe()
+    }
+
+    /// Asks all connected clients to reload data that may have changed behind their back
+    pub fn request_resync(&self) {
+        self.resync.send(()).ok();
+    }
+
+    pub fn subscribe_resync(&self) -> broadcast::Receiver<()> {
+        self.resync.subscribe()
+    }
+
+    /// Sets the state of an output unless another user controls it.
+    /// The lock is held while setting so control can't change hands in between.
+    pub fn set_state_controlled(
+        &self,
+        output_id: Option<Uuid>,
+        user: &DbUser,
+        state: CurrentState,
+    ) -> Result<(), ControlError> {
+        self.set_state_for(output_id, Some(user.id), state)
+    }
+
+    /// Sets the state of an output on behalf of a user, or of nobody in particular,
+    /// unless another user controls it
+    pub fn set_state_for(
+        &self,
+        output_id: Option<Uuid>,
+        user_id: Option<Uuid>,
+        state: CurrentState,
+    ) -> Result<(), ControlError> {
+        let output = self.output(output_id);
+        {
+            let lock = output.lock.borrow();
+            if let Some(ref lock) = *lock {
+                if Some(lock.user_id) != user_id {
+                    return Err(ControlError::LockedBy(lock.username.clone()));
+                }
+            }
+            output.state.send_replace(state);
+        }
+
+        self.changes.send(output_id).ok();
+        Ok(())
+    }
+
+    /// Takes control of an output if it isn't controlled by another user
+    pub fn take_control(
+        &self,
+        output_id: Option<Uuid>,
+        user: &DbUser,
+        connection_id: Uuid,
+    ) -> Result<(), ControlError> {
+        let mut result = Ok(());
+
+        self.output(output_id)
+            .lock
+            .send_if_modified(|lock| match lock {
+                Some(lock) if lock.user_id != user.id => {
+                    result = Err(ControlError::LockedBy(lock.username.clone()));
+                    false
+                }
+                Some(_) => false,
+                None => {
+                    *lock = Some(ControlLock {
+                        user_id: user.id,
+                        username: user.username.clone(),
+                        timestamp: Utc::now(),
+                        handover_request: None,
+                        connection_id,
+                    });
+                    true
+                }
+            });
+
+        result
+    }
+
+    /// Releases control of an output held by the user
+    pub fn release_control(
+        &self,
+        output_id: Option<Uuid>,
+        user: &DbUser,
+    ) -> Result<(), ControlError> {
+        let mut result = Ok(());
+
+        self.output(output_id)
+            .lock
+            .send_if_modified(|lock| match lock {
+                Some(existing) if existing.user_id == user.id => {
+                    *lock = None;
+                    true
+                }
+                Some(_) => {
+                    result = Err(ControlError::NotHolder);
+                    false
+                }
+                None => {
+                    result = Err(ControlError::NotLocked);
+                    false
+                }
+            });
+
+        result
+    }
+
+    /// Asks the user controlling an output to hand control over
+    pub fn request_handover(
+        &self,
+        output_id: Option<Uuid>,
+        user: &DbUser,
+        connection_id: Uuid,
+    ) -> Result<(), ControlError> {
+        let mut result = Ok(());
+
+        self.output(output_id)
+            .lock
+            .send_if_modified(|lock| match lock {
+                Some(lock) if lock.user_id != user.id => {
+                    lock.handover_request = Some(HandoverRequest {
+                        user_id: user.id,
+                        username: user.username.clone(),
+                        timestamp: Utc::now(),
+                        connection_id,
+                    });
+                    true
+                }
+                Some(_) => false,
+                None => {
+                    result = Err(ControlError::NotLocked);
+                    false
+                }
+            });
+
+        result
+    }
+
+    /// Hands control of an output over to the user who requested it
+    pub fn grant_handover(
+        &self,
+        output_id: Option<Uuid>,
+        user: &DbUser,
+    ) -> Result<Uuid, ControlError> {
+        let mut result = Err(ControlError::NotLocked);
+
+        self.output(output_id)
+            .lock
+            .send_if_modified(|lock| match lock {
+                Some(existing) if existing.user_id == user.id => {
+                    let Some(ref request) = existing.handover_request else {
+                        result = Err(ControlError::NoHandoverRequest);
+                        return false;
+                    };
+                    result = Ok(request.user_id);
+                    *lock = Some(ControlLock {
+                        user_id: request.user_id,
+                        username: request.username.clone(),
+                        timestamp: Utc::now(),
+                        handover_request: None,
+                        connection_id: request.connection_id,
+                    });
+                    true
+                }
+                Some(_) => {
+                    result = Err(ControlError::NotHolder);
+                    false
+                }
+                None => false,
+            });
+
+        result
+    }
+
+    /// Releases the locks held by a websocket connection and withdraws its handover requests
+    /// when it closes, returning the outputs it controlled
+    pub fn release_connection(&self, connection_id: Uuid) -> Vec<Option<Uuid>> {
+        let outputs: Vec<(Option<Uuid>, Arc<OutputChannel>)> = self
+            .outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(output_id, output)| (*output_id, output.clone()))
+            .collect();
+
+        let mut released = Vec::new();
+        for (output_id, output) in outputs {
+            output.lock.send_if_modified(|lock| match lock {
+                Some(existing) if existing.connection_id == connection_id => {
+                    *lock = None;
+                    released.push(output_id);
+                    true
+                }
+                Some(existing)
+                    if existing
+                        .handover_request
+                        .as_ref()
+                        .is_some_and(|request| request.connection_id == connection_id) =>
+                {
+                    existing.handover_request = None;
+                    true
+                }
+                _ => false,
+            });
+        }
+
+        released
+    }
+
+    /// Releases control of an output regardless of who holds it, returning the previous lock
+    pub fn force_release(&self, output_id: Option<Uuid>) -> Option<ControlLock> {
+        self.output(output_id).lock.send_replace(None)
+    }
 }
 
 impl Default for StateService {
@@ -0,0 +1,141 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    config::file::AppConfig,
+    content::service::{ContentService, ResolvedSlide},
+    database::Database,
+    display_outputs::service::DisplayOutputsService,
+};
+
+use super::{
+    models::{CurrentState, DisplayState, StageState},
+    service::StateService,
+};
+
+/// Derives the state of stage outputs from the outputs they follow
+pub struct StageService {
+    config: AppConfig,
+    content_service: ContentService,
+    display_outputs_service: DisplayOutputsService,
+}
+
+impl StageService {
+    pub fn new(database: &Database, config: &AppConfig) -> Self {
+        Self {
+            config: config.clone(),
+            content_service: ContentService::new(database, config),
+            display_outputs_service: DisplayOutputsService::new(database),
+        }
+    }
+
+    /// Updates all stage outputs following an output
+    pub fn update_stage_outputs(
+        &self,
+        state_service: &StateService,
+        target_output_id: Option<Uuid>,
+    ) {
+        let stage_outputs = self
+            .display_outputs_service
+            .list_stage_outputs_for(target_output_id);
+        if stage_outputs.is_empty() {
+            return;
+        }
+
+        let target_state = state_service
+            .output(target_output_id)
+            .state
+            .borrow()
+            .clone();
+
+        for stage_output in stage_outputs {
+            let previous = state_service
+                .output(stage_output.id)
+                .state
+                .borrow()
+                .stage
+                .clone();
+
+            let stage = self.derive(stage_output.id, target_output_id, &target_state, previous);
+
+            state_service.set_state(
+                stage_output.id,
+                CurrentState {
+                    id: target_state.id.clone(),
+                    display: stage.current.clone(),
+                    position: target_state.position.clone(),
+                    stage: Some(Box::new(stage)),
+                },
+            );
+        }
+    }
+
+    fn derive(
+        &self,
+        stage_output_id: Option<Uuid>,
+        target_output_id: Option<Uuid>,
+        target_state: &CurrentState,
+        previous: Option<Box<StageState>>,
+    ) -> StageState {
+        let timestamp = Utc::now();
+
+        let position = target_state.position.as_ref();
+        let slide_deck_slide_id = position.and_then(|position| position.slide_deck_slide_id);
+
+        // resolve slides when the target is playing a deck, otherwise mirror its display
+        let (current, next) = match (position, slide_deck_slide_id) {
+            (Some(position), Some(slide_deck_slide_id)) => {
+                let current = self
+                    .content_service
+                    .resolve(slide_deck_slide_id, stage_output_id)
+                    .map(Self::to_display_state)
+                    .unwrap_or_else(|| target_state.display.clone());
+                let next = self
+                    .content_service
+                    .next_slide_id(position.slide_deck_id, slide_deck_slide_id)
+                    .and_then(|next_id| self.content_service.resolve(next_id, stage_output_id))
+                    .map(Self::to_display_state);
+                (current, next)
+            }
+            _ => (target_state.display.clone(), None),
+        };
+
+        let notes = current
+            .content
+            .iter()
+            .filter(|(key, _)| key.starts_with(&self.config.stage_notes_key_prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        // keep the slide timer running unless the target moved on to another slide
+        let slide_started_at = match previous {
+            Some(previous)
+                if previous.target_output_id == target_output_id
+                    && previous.slide_deck_slide_id == slide_deck_slide_id
+                    && (slide_deck_slide_id.is_some()
+                        || previous.current.content == current.content) =>
+            {
+                previous.slide_started_at
+            }
+            _ => timestamp,
+        };
+
+        StageState {
+            target_output_id,
+            slide_deck_slide_id,
+            current,
+            next,
+            notes,
+            timestamp,
+            slide_started_at,
+        }
+    }
+
+    fn to_display_state(slide: ResolvedSlide) -> DisplayState {
+        DisplayState {
+            content: slide.content,
+            slide_type_id: slide.slide_type_id,
+            rendered: None,
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    app::AppServices,
+    backups::service::BackupTrigger,
+    bundles::model::{BundleExportJob, BundleImportJob},
+    helpers::errors::GenericError,
+    imports::model::{ImportJob, ImportOptions, ImportReport, Importer},
+    jobs::{db::JobType, service::JobContext},
+};
+
+/// Runs queued jobs on a pool of workers until shutdown
+pub async fn job_workers(app_state: Arc<AppServices>) {
+    app_state.jobs_service.recover();
+
+    let workers: Vec<_> = (0..app_state.config.job_workers.max(1))
+        .map(|_| tokio::spawn(job_worker(app_state.clone())))
+        .collect();
+
+    for worker in workers {
+        worker.await.unwrap();
+    }
+}
+
+async fn job_worker(app_state: Arc<AppServices>) {
+    loop {
+        let Some(context) = app_state.jobs_service.claim_next() else {
+            tokio::select! {
+                _ = app_state.jobs_service.wait_for_jobs() => {},
+                _ = tokio::time::sleep(Duration::from_secs(app_state.config.job_poll_interval)) => {},
+                _ = app_state.shutdown_token.cancelled() => break,
+            }
+            continue;
+        };
+
+        // jobs run in their own task so a panicking job only fails itself
+        let mut job_task = tokio::spawn({
+            let app_state = app_state.clone();
+            let context = context.clone();
+            async move { run_job(&app_state, &context).await }
+        });
+
+        let result = tokio::select! {
+            result = &mut job_task => result.unwrap_or_else(|_| Err(String::from("Job panicked"))),
+            _ = context.cancel_token.cancelled() => {
+                job_task.abort();
+                Err(String::from("Cancelled"))
+            },
+            _ = app_state.shutdown_token.cancelled() => {
+                job_task.abort();
+                app_state.jobs_service.interrupt(context);
+                break;
+            },
+        };
+
+        app_state.jobs_service.finish(context, result);
+    }
+}
+
+#[derive(Deserialize)]
+struct MediaPayload {
+    media_id: Uuid,
+}
+
+/// Does the work of a job, returning its result
+async fn run_job(
+    app_state: &AppServices,
+    context: &JobContext,
+) -> Result<serde_json::Value, String> {
+    match context.job.job_type {
+        JobType::MediaVariants => {
+            let payload: MediaPayload = context.payload()?;
+            app_state
+                .media_service
+                .generate_variants(payload.media_id)
+                .await;
+            Ok(serde_json::Value::Null)
+        }
+        JobType::DatabaseBackup => app_state
+            .backups_service
+            .create(BackupTrigger::Scheduled, context.job.created_by)
+            .await
+            .map(|backup| json!(backup))
+            .map_err(|err| err.to_string()),
+        JobType::Import => run_import(app_state, context).await,
+        JobType::BundleImport => import_bundle(app_state, context).await,
+        JobType::BundleExport => export_bundle(app_state, context).await,
+    }
+}
+
+/// Saves an import from the files kept for its job
+async fn run_import(
+    app_state: &AppServices,
+    context: &JobContext,
+) -> Result<serde_json::Value, String> {
+    let job: ImportJob = context.payload()?;
+    let user_id = context.job.created_by;
+    let imports_service = &app_state.imports_service;
+
+    let file = job
+        .files
+        .first()
+        .map(|(file_name, path)| (file_name.as_str(), path.as_path()));
+    let no_file = || String::from("Import has no file");
+
+    let (action, options, result) = match &job.importer {
+        Importer::Csv(options) => {
+            let (_, path) = file.ok_or_else(no_file)?;
+            let result = imports_service.import_csv(path, options).await;
+
+            app_state.audit_service.log_data(
+                user_id,
+                "import_csv",
+                json!({
+                    "slide_group_id": options.slide_group_id,
+                    "slide_type_id": options.slide_type_id,
+                    "rows": result.as_ref().ok().map(|preview| preview.row_count),
+                    "success": result.as_ref().is_ok_and(|preview| preview.committed)
+                }),
+            );
+
+            return match result {
+                Ok(preview) if preview.committed => Ok(json!(preview)),
+                // a commit that was refused still shows why, and would be refused again
+                Ok(preview) => {
+                    context.skip_retries();
+                    Err(json!(preview.errors).to_string())
+                }
+                Err(err) => Err(job_error(context, err)),
+            };
+        }
+        Importer::OpenLyrics(options) => {
+            let (_, path) = file.ok_or_else(no_file)?;
+            (
+                "import_openlyrics",
+                options,
+                imports_service
+                    .import_openlyrics(path, options, user_id)
+                    .await,
+            )
+        }
+        Importer::ChordPro(options) => {
+            let (_, path) = file.ok_or_else(no_file)?;
+            (
+                "import_chordpro",
+                &options.import,
+                imports_service
+                    .import_chordpro(path, options, user_id)
+                    .await,
+            )
+        }
+        Importer::Text(options) => {
+            let (_, path) = file.ok_or_else(no_file)?;
+            (
+                "import_text",
+                &options.import,
+                imports_service.import_text(path, options, user_id).await,
+            )
+        }
+        Importer::OpenLp(options) => {
+            let paths: Vec<PathBuf> = job.files.iter().map(|(_, path)| path.clone()).collect();
+            (
+                "import_openlp",
+                options,
+                imports_service
+                    .import_openlp(&paths, options, user_id)
+                    .await,
+            )
+        }
+        Importer::ProPresenter(options) => (
+            "import_propresenter",
+            options,
+            imports_service
+                .import_propresenter(&job.files, options, user_id)
+                .await,
+        ),
+        Importer::Pptx(options) => {
+            let (file_name, path) = file.ok_or_else(no_file)?;
+            (
+                "import_pptx",
+                options,
+                imports_service
+                    .import_pptx(path, file_name, options, user_id)
+                    .await,
+            )
+        }
+        Importer::Images(options) => (
+            "import_images",
+            &options.import,
+            imports_service.import_images(file, options, user_id).await,
+        ),
+    };
+
+    log_import(app_state, user_id, action, options, &result);
+
+    result
+        .map(|report| json!(report))
+        .map_err(|err| job_error(context, err))
+}
+
+/// Describes why a job failed, not retrying it if it would fail the same way again
+fn job_error(context: &JobContext, err: GenericError) -> String {
+    if err.is_client_error() {
+        context.skip_retries();
+    }
+    err.to_string()
+}
+
+/// Logs an import that was saved or failed to save
+fn log_import(
+    app_state: &AppServices,
+    user_id: Option<Uuid>,
+    action: &str,
+    options: &ImportOptions,
+    result: &Result<ImportReport, GenericError>,
+) {
+    app_state.audit_service.log_data(
+        user_id,
+        action,
+        json!({
+            "conflict": options.conflict,
+            "slide_deck_id": result
+                .as_ref()
+                .ok()
+                .and_then(|report| report.result.as_ref())
+                .map(|result| result.slide_deck_id),
+            "success": result.is_ok()
+        }),
+    );
+}
+
+/// Imports a deck bundle from the file kept for its job
+async fn import_bundle(
+    app_state: &AppServices,
+    context: &JobContext,
+) -> Result<serde_json::Value, String> {
+    let job: BundleImportJob = context.payload()?;
+    let user_id = context.job.created_by;
+
+    let result = app_state
+        .bundles_service
+        .import(&job.path, job.conflict, &app_state.media_service, user_id)
+        .await;
+
+    app_state.audit_service.log_data(
+        user_id,
+        "deck_import",
+        json!({
+            "conflict": job.conflict,
+            "slide_deck_id": result.as_ref().ok().map(|(result, _)| result.slide_deck_id),
+            "success": result.is_ok()
+        }),
+    );
+
+    let (result, added_media) = result.map_err(|err| job_error(context, err))?;
+    for media_id in added_media {
+        app_state.media_service.queue_variants(media_id, user_id);
+    }
+
+    Ok(json!(result))
+}
+
+/// Writes a deck bundle into the directory of its job, to be downloaded from there
+async fn export_bundle(
+    app_state: &AppServices,
+    context: &JobContext,
+) -> Result<serde_json::Value, String> {
+    let job: BundleExportJob = context.payload()?;
+
+    let result = app_state
+        .bundles_service
+        .export(
+            job.slide_deck_id,
+            &app_state.media_service,
+            &app_state.jobs_service.files_directory(context.job.id),
+        )
+        .await;
+
+    app_state.audit_service.log_data(
+        context.job.created_by,
+        "deck_export",
+        json!({
+            "slide_deck_id": job.slide_deck_id,
+            "success": result.is_ok()
+        }),
+    );
+
+    result
+        .map(|bundle| json!(bundle))
+        .map_err(|err| err.to_string())
+}
@@ -1,6 +1,11 @@
 use std::{sync::Arc, time::Duration};
 
-use crate::app::AppServices;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{app::AppServices, jobs::db::JobType};
+
+use super::jobs;
 
 /// Runs various maintenance tasks
 pub async fn maintenance_tasks(app_state: Arc<AppServices>) {
@@ -20,6 +25,41 @@ pub async fn maintenance_tasks(app_state: Arc<AppServices>) {
                 .await
                 .unwrap()
         },
+        async {
+            tokio::spawn(scheduled_cues_task(app_state.clone()))
+                .await
+                .unwrap()
+        },
+        async {
+            tokio::spawn(run_sheets_follow_task(app_state.clone()))
+                .await
+                .unwrap()
+        },
+        async {
+            tokio::spawn(show_clock_task(app_state.clone()))
+                .await
+                .unwrap()
+        },
+        async {
+            tokio::spawn(stage_outputs_task(app_state.clone()))
+                .await
+                .unwrap()
+        },
+        async {
+            tokio::spawn(jobs::job_workers(app_state.clone()))
+                .await
+                .unwrap()
+        },
+        async {
+            tokio::spawn(jobs_prune_task(app_state.clone()))
+                .await
+                .unwrap()
+        },
+        async {
+            tokio::spawn(database_backup_task(app_state.clone()))
+                .await
+                .unwrap()
+        },
     );
 }
 
@@ -58,3 +98,113 @@ pub async fn database_full_checkpoint_task(app_state: Arc<AppServices>) {
         app_state.database.checkpoint(true);
     }
 }
+
+/// Fires scheduled cues when they are due
+pub async fn scheduled_cues_task(app_state: Arc<AppServices>) {
+    loop {
+        // skips cues missed while the server was down on the first run
+        app_state.schedule_service.run_due(&app_state.state_service);
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(app_state.config.scheduler_interval)) => {},
+            _ = app_state.shutdown_token.cancelled() => break,
+        }
+    }
+}
+
+/// Advances live run sheets when the output they follow moves on to another slide deck
+pub async fn run_sheets_follow_task(app_state: Arc<AppServices>) {
+    let mut changes = app_state.state_service.subscribe_changes();
+
+    loop {
+        let output_id = tokio::select! {
+            result = changes.recv() => match result {
+                Ok(output_id) => output_id,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            _ = app_state.shutdown_token.cancelled() => break,
+        };
+
+        let position = app_state
+            .state_service
+            .output(output_id)
+            .state
+            .borrow()
+            .position
+            .clone();
+
+        if let Some(position) = position {
+            app_state
+                .run_sheets_service
+                .follow_deck(output_id, position.slide_deck_id);
+        }
+    }
+}
+
+/// Republishes live run sheets so show clocks keep running
+pub async fn show_clock_task(app_state: Arc<AppServices>) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(app_state.config.show_clock_interval)) => {},
+            _ = app_state.shutdown_token.cancelled() => break,
+        }
+
+        app_state.run_sheets_service.refresh_live();
+    }
+}
+
+/// Updates stage outputs whenever the output they follow changes
+pub async fn stage_outputs_task(app_state: Arc<AppServices>) {
+    let mut changes = app_state.state_service.subscribe_changes();
+
+    loop {
+        let output_id = tokio::select! {
+            result = changes.recv() => match result {
+                Ok(output_id) => output_id,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            _ = app_state.shutdown_token.cancelled() => break,
+        };
+
+        app_state
+            .stage_service
+            .update_stage_outputs(&app_state.state_service, output_id);
+    }
+}
+
+/// Removes finished jobs once they are old enough
+pub async fn jobs_prune_task(app_state: Arc<AppServices>) {
+    loop {
+        app_state.jobs_service.prune();
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(app_state.config.database_maintenance_interval)) => {},
+            _ = app_state.shutdown_token.cancelled() => break,
+        }
+    }
+}
+
+/// Queues database backups at the configured interval, counting from the latest backup so
+/// restarts don't delay them
+pub async fn database_backup_task(app_state: Arc<AppServices>) {
+    if app_state.config.backup_interval == 0 {
+        return;
+    }
+
+    loop {
+        let mut due_in = app_state.backups_service.scheduled_due_in();
+        if due_in.is_zero() {
+            app_state
+                .jobs_service
+                .enqueue(JobType::DatabaseBackup, json!({}), None);
+            due_in = Duration::from_secs(app_state.config.backup_interval);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(due_in) => {},
+            _ = app_state.shutdown_token.cancelled() => break,
+        }
+    }
+}